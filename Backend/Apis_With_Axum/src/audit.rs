@@ -0,0 +1,264 @@
+use std::{path::PathBuf, sync::RwLock};
+
+use tokio::sync::broadcast;
+
+use axum::{
+    extract::{FromRequestParts, Path, State},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    book::Book,
+    storage::{append_jsonl, load_jsonl, Storage},
+    AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Deleted,
+    Reverted,
+    Restored,
+    Purged,
+    // Folded into another book; `merged_into` names it
+    Merged,
+}
+
+// One change to one book; `seq` counts across the whole log and `rev`
+// counts per book, both starting at 1
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    #[serde(default)]
+    pub seq: u64,
+    pub book_id: u32,
+    pub rev: u32,
+    pub action: AuditAction,
+    pub actor: String,
+    pub at: DateTime<Utc>,
+    pub before: Option<Book>,
+    pub after: Option<Book>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reverted_to: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merged_into: Option<u32>,
+}
+
+// Who made the change, taken from the X-Actor header
+pub struct Actor(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let actor = parts
+            .headers
+            .get("x-actor")
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .unwrap_or("anonymous");
+        Ok(Actor(actor.to_string()))
+    }
+}
+
+// Append-only change log, persisted as JSON Lines next to the catalog.
+// New entries are also broadcast to live subscribers.
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    entries: RwLock<Vec<AuditEntry>>,
+    sender: broadcast::Sender<AuditEntry>,
+}
+
+impl AuditLog {
+    pub fn open(storage: &Storage) -> Self {
+        let path = storage.sidecar("audit.jsonl");
+        // Starting over with an empty log would hand out sequence numbers
+        // that clients have already seen
+        let mut entries: Vec<AuditEntry> = match &path {
+            Some(path) => load_jsonl(path)
+                .unwrap_or_else(|err| panic!("❌ Failed to load audit log {}: {}", path.display(), err)),
+            None => Vec::new(),
+        };
+
+        // Entries written before sequence numbers existed carry 0 and take
+        // the next number after the entry before them
+        let mut last = 0;
+        for entry in entries.iter_mut() {
+            if entry.seq == 0 {
+                entry.seq = last + 1;
+            }
+            last = entry.seq;
+        }
+
+        let (sender, _) = broadcast::channel(1024);
+        AuditLog { path, entries: RwLock::new(entries), sender }
+    }
+
+    pub fn record(
+        &self,
+        action: AuditAction,
+        actor: &str,
+        before: Option<Book>,
+        after: Option<Book>,
+    ) -> AuditEntry {
+        self.push(action, actor, before, after, None, None)
+    }
+
+    // The merged book leaves the catalog; its entries stay under its own ID
+    pub fn record_merge(&self, actor: &str, merged: Book, into: u32) -> AuditEntry {
+        self.push(AuditAction::Merged, actor, Some(merged), None, None, Some(into))
+    }
+
+    fn push(
+        &self,
+        action: AuditAction,
+        actor: &str,
+        before: Option<Book>,
+        after: Option<Book>,
+        reverted_to: Option<u32>,
+        merged_into: Option<u32>,
+    ) -> AuditEntry {
+        let book_id = after.as_ref().or(before.as_ref()).map(|book| book.id).unwrap_or_default();
+        let mut entries = self.entries.write().unwrap();
+        let rev = entries.iter().filter(|entry| entry.book_id == book_id).map(|entry| entry.rev).max().unwrap_or(0) + 1;
+
+        let entry = AuditEntry {
+            seq: entries.last().map_or(0, |entry| entry.seq) + 1,
+            book_id,
+            rev,
+            action,
+            actor: actor.to_string(),
+            at: Utc::now(),
+            before,
+            after,
+            reverted_to,
+            merged_into,
+        };
+
+        if let Some(path) = &self.path {
+            if let Err(err) = append_jsonl(path, &entry) {
+                tracing::error!("💥 Failed to write audit entry: {}", err);
+            }
+        }
+        entries.push(entry.clone());
+        // Sent while the write lock is held so `subscribe` never sees a gap
+        let _ = self.sender.send(entry.clone());
+        entry
+    }
+
+    // Entries after `since` plus a receiver for everything that follows.
+    // Without `since` only new entries are delivered.
+    pub fn subscribe(&self, since: Option<u64>) -> (Vec<AuditEntry>, broadcast::Receiver<AuditEntry>) {
+        let entries = self.entries.read().unwrap();
+        let since = since.unwrap_or(entries.last().map_or(0, |entry| entry.seq));
+        let backlog = entries.iter().filter(|entry| entry.seq > since).cloned().collect();
+        (backlog, self.sender.subscribe())
+    }
+
+    // Up to `limit` entries after `since`, oldest first
+    pub fn page(&self, since: u64, limit: usize) -> Vec<AuditEntry> {
+        let entries = self.entries.read().unwrap();
+        entries.iter().filter(|entry| entry.seq > since).take(limit).cloned().collect()
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.entries.read().unwrap().last().map_or(0, |entry| entry.seq)
+    }
+
+    pub fn history(&self, book_id: u32) -> Vec<AuditEntry> {
+        self.history_of(&[book_id])
+    }
+
+    // Entries for any of `book_ids`, oldest first
+    pub fn history_of(&self, book_ids: &[u32]) -> Vec<AuditEntry> {
+        let entries = self.entries.read().unwrap();
+        entries.iter().filter(|entry| book_ids.contains(&entry.book_id)).cloned().collect()
+    }
+
+    pub fn max_book_id(&self) -> Option<u32> {
+        let entries = self.entries.read().unwrap();
+        entries.iter().map(|entry| entry.book_id).max()
+    }
+
+    pub fn revision(&self, book_id: u32, rev: u32) -> Option<AuditEntry> {
+        let entries = self.entries.read().unwrap();
+        entries.iter().find(|entry| entry.book_id == book_id && entry.rev == rev).cloned()
+    }
+}
+
+/// List every recorded change to a book and the books merged into it, oldest first
+#[utoipa::path(get, path = "/books/{id}/history", tag = "history",
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "Audit entries for the book; empty for a book loaded unchanged from the CSV", body = Vec<AuditEntry>),
+        (status = 404, description = "Book not found", body = String),
+    ))]
+pub async fn book_history(Path(id): Path<u32>, State(state): State<AppState>) -> impl IntoResponse {
+    let mut book_ids = state.merges.sources(id);
+    book_ids.push(id);
+    let history = state.audit.history_of(&book_ids);
+
+    // Trashed, purged and merged books still have a past to show
+    let known = !history.is_empty()
+        || state.merges.resolve(id).is_some()
+        || state.books.read().unwrap().iter().any(|book| book.id == id);
+    if known {
+        Json(history).into_response()
+    } else {
+        (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response()
+    }
+}
+
+/// Restore a book to how it looked after an earlier revision
+#[utoipa::path(post, path = "/books/{id}/revert/{rev}", tag = "history",
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ("rev" = u32, Path, description = "Revision to restore"),
+        ("X-Actor" = Option<String>, Header, description = "Who is making the change"),
+    ),
+    responses(
+        (status = 200, description = "Book restored", body = Book),
+        (status = 404, description = "Revision not found", body = String),
+        (status = 409, description = "Revision is a deletion, or the book was merged away", body = String),
+        (status = 500, description = "Book could not be saved", body = String),
+    ))]
+pub async fn revert_book(
+    Path((id, rev)): Path<(u32, u32)>,
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+) -> impl IntoResponse {
+    // Bringing it back would split the merged record in two
+    if state.merges.resolve(id).is_some() {
+        return (StatusCode::CONFLICT, "🚫 Book Was Merged").into_response();
+    }
+    let Some(target) = state.audit.revision(id, rev) else {
+        return (StatusCode::NOT_FOUND, "❌ Revision Not Found").into_response();
+    };
+    let Some(restored) = target.after else {
+        return (StatusCode::CONFLICT, "🚫 Cannot Revert To A Deleted Revision").into_response();
+    };
+
+    let mut books_writer = state.books.write().unwrap();
+    let mut draft = books_writer.clone();
+    let before = match draft.iter_mut().find(|book| book.id == id) {
+        Some(book) => Some(std::mem::replace(book, restored.clone())),
+        None => {
+            draft.push(restored.clone());
+            draft.sort_by_key(|book| book.id);
+            None
+        }
+    };
+
+    if state.persist(&draft).is_err() {
+        return crate::handler::storage_error();
+    }
+    *books_writer = draft;
+    state.audit.push(AuditAction::Reverted, &actor, before, Some(restored.clone()), Some(rev), None);
+    Json(restored).into_response()
+}
@@ -1,14 +1,27 @@
+use std::{collections::HashMap, fmt, io, path::Path};
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct Book {
     pub id: u32,
     pub title: String,
     pub author: String,
+    // Set when the book is moved to the trash; older CSV files lack the column
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl Book {
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
 }
 
-pub fn load_books_from_csv() -> Result<Vec<Book>, csv::Error> {
-    let mut reader = csv::Reader::from_path("assets/books.csv")?;
+pub fn load_books_from_csv(path: &Path) -> Result<Vec<Book>, csv::Error> {
+    let mut reader = csv::Reader::from_path(path)?;
     let mut books = Vec::new();
 
     for record in reader.deserialize() {
@@ -19,13 +32,114 @@ pub fn load_books_from_csv() -> Result<Vec<Book>, csv::Error> {
     Ok(books)
 }
 
-pub fn save_books_to_csv(books: &[Book]) -> Result<(), csv::Error> {
-    let mut writer = csv::Writer::from_path("assets/books.csv")?;
+// A row the lenient loader skipped. Lines count from 1, the header included.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RowError {
+    pub line: u64,
+    /// The column at fault, when one can be named
+    pub column: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.column {
+            Some(column) => write!(f, "line {}, column {}: {}", self.line, column, self.reason),
+            None => write!(f, "line {}: {}", self.line, self.reason),
+        }
+    }
+}
+
+// What a lenient load kept, and what it skipped and why
+#[derive(Debug, Clone, Default)]
+pub struct CsvLoad {
+    pub books: Vec<Book>,
+    pub errors: Vec<RowError>,
+    pub headers: csv::ByteRecord,
+    // Skipped rows exactly as read, so they can be set aside rather than lost
+    pub rejected: Vec<csv::ByteRecord>,
+}
+
+const REQUIRED_COLUMNS: [&str; 3] = ["id", "title", "author"];
+
+// Keeps every row that parses and reports the rest, one error per row.
+// Fails outright only when the file or its header cannot be read.
+pub fn load_books_leniently(path: &Path) -> Result<CsvLoad, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let missing: Vec<&str> = REQUIRED_COLUMNS.into_iter().filter(|column| !headers.iter().any(|header| header == *column)).collect();
+    if !missing.is_empty() {
+        let reason = format!("header is missing column(s) {}", missing.join(", "));
+        return Err(io::Error::new(io::ErrorKind::InvalidData, reason).into());
+    }
+
+    let mut load = CsvLoad { headers: headers.as_byte_record().clone(), ..CsvLoad::default() };
+    let mut first_seen: HashMap<u32, u64> = HashMap::new();
+    let mut record = csv::ByteRecord::new();
+    loop {
+        let error = match reader.read_byte_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                let line = record.position().map_or(0, |position| position.line());
+                match parse_row(&record, &headers) {
+                    Ok(book) => match first_seen.get(&book.id) {
+                        Some(first) => row_error(line, Some("id"), format!("duplicate id {} (first on line {})", book.id, first)),
+                        None => {
+                            first_seen.insert(book.id, line);
+                            load.books.push(book);
+                            continue;
+                        }
+                    },
+                    Err((column, reason)) => row_error(line, column.as_deref(), reason),
+                }
+            }
+            // Unreadable bytes; the reader carries on with the next row
+            Err(err) => {
+                let line = err.position().map_or(0, |position| position.line());
+                row_error(line, None, err.to_string())
+            }
+        };
+        load.errors.push(error);
+        load.rejected.push(record.clone());
+    }
+    Ok(load)
+}
+
+fn row_error(line: u64, column: Option<&str>, reason: String) -> RowError {
+    RowError { line, column: column.map(str::to_string), reason }
+}
+
+fn parse_row(record: &csv::ByteRecord, headers: &csv::StringRecord) -> Result<Book, (Option<String>, String)> {
+    if record.len() != headers.len() {
+        return Err((None, format!("expected {} fields, found {}", headers.len(), record.len())));
+    }
+    let record = csv::StringRecord::from_byte_record(record.clone()).map_err(|err| {
+        let column = err.utf8_error().field();
+        (headers.get(column).map(str::to_string), "not valid UTF-8".to_string())
+    })?;
+    let book: Book = record.deserialize(Some(headers)).map_err(|err| match err.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => {
+            let column = err.field().and_then(|field| headers.get(field as usize)).map(str::to_string);
+            (column, err.kind().to_string())
+        }
+        _ => (None, err.to_string()),
+    })?;
+    for (column, value) in [("title", &book.title), ("author", &book.author)] {
+        if value.trim().is_empty() {
+            return Err((Some(column.to_string()), format!("{} is empty", column)));
+        }
+    }
+    Ok(book)
+}
+
+pub fn save_books_to_csv(path: &Path, books: &[Book]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
 
     for book in books {
         writer.serialize(book)?;
     }
 
-    writer.flush()?;
+    let bytes = writer.into_inner().map_err(|err| err.into_error())?;
+    crate::storage::atomic_write(path, bytes)?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    book::Book,
+    events::{ChangeEvent, ChangeKind},
+    AppState,
+};
+
+const DEFAULT_PAGE_SIZE: usize = 500;
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ChangesParams {
+    /// Cursor from the previous response; 0 or omitted starts from the beginning
+    pub since: Option<u64>,
+    /// Maximum number of changes to return (default 500, at most 1000)
+    pub limit: Option<usize>,
+    /// Return the whole catalog as it is now, with the cursor to follow it
+    /// from; `since` and `limit` are ignored. Start here on a first sync.
+    #[serde(default)]
+    pub snapshot: bool,
+}
+
+// One change; deletes are tombstones carrying only the book ID
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Change {
+    pub seq: u64,
+    pub id: u32,
+    pub kind: ChangeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub book: Option<Book>,
+}
+
+impl From<ChangeEvent> for Change {
+    fn from(event: ChangeEvent) -> Self {
+        let book = (event.kind != ChangeKind::Deleted).then_some(event.book.clone());
+        Change { seq: event.id, id: event.book.id, kind: event.kind, book }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChangesPage {
+    pub changes: Vec<Change>,
+    /// Pass this as `since` on the next call
+    pub cursor: u64,
+    /// More changes are waiting after `cursor`
+    pub has_more: bool,
+    /// Every live book, each as a creation; replaces any local copy
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub snapshot: bool,
+}
+
+// Every book change records its audit entry while the catalog is still
+// write-locked, so under the read lock the books and the log agree
+fn snapshot(state: &AppState) -> ChangesPage {
+    let books_reader = state.books.read().unwrap();
+    let cursor = state.audit.last_seq();
+    let changes = books_reader
+        .iter()
+        .filter(|book| !book.is_deleted())
+        .map(|book| Change { seq: cursor, id: book.id, kind: ChangeKind::Created, book: Some(book.clone()) })
+        .collect();
+    ChangesPage { changes, cursor, has_more: false, snapshot: true }
+}
+
+/// Changes since a cursor, for clients keeping a local copy of the catalog.
+/// A first sync asks for a snapshot, which also covers books older than the log.
+#[utoipa::path(get, path = "/books/changes", tag = "sync", params(ChangesParams),
+    responses(
+        (status = 200, description = "Ordered changes and the next cursor", body = ChangesPage),
+        (status = 410, description = "Cursor is ahead of the server; refetch the whole catalog", body = String),
+    ))]
+pub async fn list_changes(
+    State(state): State<AppState>,
+    Query(params): Query<ChangesParams>,
+) -> impl IntoResponse {
+    if params.snapshot {
+        return Json(snapshot(&state)).into_response();
+    }
+    let since = params.since.unwrap_or(0);
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let head = state.audit.last_seq();
+    if since > head {
+        return (StatusCode::GONE, "🔄 Cursor Unknown, Resync Required").into_response();
+    }
+
+    let entries = state.audit.page(since, limit);
+    // Purges produce no change but still move the cursor past them
+    let cursor = entries.last().map_or(since, |entry| entry.seq);
+    let changes = entries
+        .iter()
+        .filter_map(ChangeEvent::from_audit)
+        .map(Change::from)
+        .collect();
+
+    Json(ChangesPage { changes, cursor, has_more: cursor < head, snapshot: false }).into_response()
+}
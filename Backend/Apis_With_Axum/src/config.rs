@@ -0,0 +1,789 @@
+use std::{
+    fmt, fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+
+use crate::{cron::Schedule, emails::check_template};
+
+// Command-line flags (highest priority layer)
+#[derive(Debug, Default, Parser)]
+#[command(name = "Apis_With_Axum", about = "📚 Book catalog API")]
+pub struct Cli {
+    /// Path to a TOML configuration file
+    #[arg(long, env = "BOOKS_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Address to bind the HTTP server to, e.g. 0.0.0.0:3000
+    #[arg(long)]
+    pub bind: Option<String>,
+
+    /// Path of the CSV data file
+    #[arg(long)]
+    pub data_path: Option<PathBuf>,
+
+    /// Storage backend: csv or memory
+    #[arg(long)]
+    pub storage: Option<String>,
+
+    /// Log level: error, warn, info, debug or trace
+    #[arg(long)]
+    pub log_level: Option<String>,
+
+    /// Print the effective configuration as TOML, secrets redacted, and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check a CSV data file and report malformed rows without starting the server
+    Validate {
+        /// File to check; the configured data_path when omitted
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Csv,
+    Memory,
+}
+
+// Browser access from other origins; no origins means no CORS headers at all
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub body_limit: usize,
+    pub request_timeout_secs: u64,
+    pub shutdown_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub min_size: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrashConfig {
+    pub retention_days: u32,
+}
+
+// Cron schedules (UTC) for background jobs; an empty schedule disables the job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub backup: String,
+    pub purge_trash: String,
+    pub overdue_reminders: String,
+    // Older backups are deleted; 0 keeps every backup
+    pub backups_kept: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub timeout_secs: u64,
+    // Let receivers live on loopback, private or link-local addresses
+    pub allow_private_targets: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LendingConfig {
+    pub loan_days: u32,
+    pub max_renewals: u32,
+    pub max_loans_per_member: u32,
+    // How long a returned copy waits for the first holder
+    pub pickup_days: u32,
+}
+
+// Overdue fines, in cents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FinesConfig {
+    pub daily_rate_cents: u32,
+    // Days late that cost nothing; later days are all charged
+    pub grace_days: u32,
+    // Most one loan can be fined; 0 for no cap
+    pub max_per_item_cents: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReviewsConfig {
+    // Flags that hide a review until a moderator restores it; 0 never hides
+    pub auto_hide_flags: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadFailure {
+    // Exit instead of serving a partial catalog
+    Refuse,
+    // Serve what loaded but turn away every change
+    ReadOnly,
+}
+
+// Rows of the data file that do not parse are skipped and set aside at
+// startup. Past max_row_errors, or when the file cannot be read at all,
+// on_too_many_errors decides what happens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoadingConfig {
+    pub max_row_errors: usize,
+    pub on_too_many_errors: LoadFailure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    None,
+    // Upgrade a plain connection, usually on port 587
+    StartTls,
+    // TLS from the first byte, usually on port 465
+    Tls,
+}
+
+// Loan reminders sent by the overdue_reminders job; no smtp_host means no email.
+// Templates may use {member}, {title}, {author}, {due_date}, {days_late} and {fine}.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub timeout_secs: u64,
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub due_soon_subject: String,
+    pub due_soon_body: String,
+    pub overdue_subject: String,
+    pub overdue_body: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LibrariesConfig {
+    pub admin_key: Option<String>,
+}
+
+// Stands in for secrets in printed configuration
+pub const REDACTED: &str = "<redacted>";
+
+// Effective server configuration: defaults < TOML file < environment < CLI flags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    pub data_path: PathBuf,
+    pub storage: StorageBackend,
+    pub log_level: String,
+    pub cors: CorsConfig,
+    pub limits: LimitsConfig,
+    pub compression: CompressionConfig,
+    pub trash: TrashConfig,
+    pub jobs: JobsConfig,
+    pub webhooks: WebhookConfig,
+    pub libraries: LibrariesConfig,
+    pub lending: LendingConfig,
+    pub fines: FinesConfig,
+    pub email: EmailConfig,
+    pub reviews: ReviewsConfig,
+    pub loading: LoadingConfig,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["content-type", "accept", "x-actor", "last-event-id"].map(String::from).to_vec(),
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            body_limit: 1024 * 1024,
+            request_timeout_secs: 30,
+            shutdown_timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            enabled: true,
+            min_size: 1024,
+        }
+    }
+}
+
+impl Default for TrashConfig {
+    fn default() -> Self {
+        TrashConfig {
+            retention_days: 30,
+        }
+    }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            backup: "0 3 * * *".to_string(),
+            purge_trash: "@hourly".to_string(),
+            overdue_reminders: "0 8 * * *".to_string(),
+            backups_kept: 7,
+        }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig {
+            max_attempts: 5,
+            initial_backoff_ms: 1000,
+            timeout_secs: 10,
+            allow_private_targets: false,
+        }
+    }
+}
+
+impl Default for LendingConfig {
+    fn default() -> Self {
+        LendingConfig {
+            loan_days: 14,
+            max_renewals: 2,
+            max_loans_per_member: 5,
+            pickup_days: 3,
+        }
+    }
+}
+
+impl Default for FinesConfig {
+    fn default() -> Self {
+        FinesConfig {
+            daily_rate_cents: 25,
+            grace_days: 1,
+            max_per_item_cents: 1000,
+        }
+    }
+}
+
+impl Default for ReviewsConfig {
+    fn default() -> Self {
+        ReviewsConfig {
+            auto_hide_flags: 3,
+        }
+    }
+}
+
+impl Default for LoadingConfig {
+    fn default() -> Self {
+        LoadingConfig {
+            max_row_errors: 10,
+            on_too_many_errors: LoadFailure::Refuse,
+        }
+    }
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        EmailConfig {
+            smtp_host: String::new(),
+            smtp_port: 587,
+            security: SmtpSecurity::StartTls,
+            username: None,
+            password: None,
+            from: "library@localhost".to_string(),
+            timeout_secs: 10,
+            max_attempts: 3,
+            initial_backoff_ms: 1000,
+            due_soon_subject: "Reminder: \"{title}\" is due {due_date}".to_string(),
+            due_soon_body: "Hello {member},\n\n\"{title}\" by {author} is due back on {due_date}. \
+                If you need more time, you can renew it.\n\nThank you!\n"
+                .to_string(),
+            overdue_subject: "Overdue: \"{title}\"".to_string(),
+            overdue_body: "Hello {member},\n\n\"{title}\" by {author} was due on {due_date} and is \
+                {days_late} day(s) late. The fine so far is {fine}.\n\nPlease return it as soon as you can.\n"
+                .to_string(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind: "0.0.0.0:3000".to_string(),
+            data_path: PathBuf::from("assets/books.csv"),
+            storage: StorageBackend::Csv,
+            log_level: "info".to_string(),
+            cors: CorsConfig::default(),
+            limits: LimitsConfig::default(),
+            compression: CompressionConfig::default(),
+            trash: TrashConfig::default(),
+            jobs: JobsConfig::default(),
+            webhooks: WebhookConfig::default(),
+            libraries: LibrariesConfig::default(),
+            lending: LendingConfig::default(),
+            fines: FinesConfig::default(),
+            email: EmailConfig::default(),
+            reviews: ReviewsConfig::default(),
+            loading: LoadingConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, source: toml::de::Error },
+    Value { name: &'static str, reason: String },
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(f, "cannot read config file {}: {}", path.display(), source)
+            }
+            ConfigError::Parse { path, source } => {
+                write!(f, "invalid config file {}: {}", path.display(), source)
+            }
+            ConfigError::Value { name, reason } => write!(f, "invalid {}: {}", name, reason),
+            ConfigError::Invalid(problems) => {
+                write!(f, "invalid configuration:")?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    // Build the configuration from every layer and validate the result
+    pub fn load(cli: &Cli) -> Result<Config, ConfigError> {
+        let mut config = match &cli.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+
+        config.apply_env(|var| std::env::var(var).ok())?;
+        config.apply_cli(cli)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let text = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    // Environment variables use the BOOKS_ prefix, e.g. BOOKS_BIND
+    pub fn apply_env(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(bind) = lookup("BOOKS_BIND") {
+            self.bind = bind;
+        }
+        if let Some(path) = lookup("BOOKS_DATA_PATH") {
+            self.data_path = PathBuf::from(path);
+        }
+        if let Some(storage) = lookup("BOOKS_STORAGE") {
+            self.storage = parse_storage(&storage).map_err(|reason| ConfigError::Value {
+                name: "BOOKS_STORAGE",
+                reason,
+            })?;
+        }
+        if let Some(level) = lookup("BOOKS_LOG_LEVEL") {
+            self.log_level = level;
+        }
+        if let Some(origins) = lookup("BOOKS_CORS_ALLOWED_ORIGINS") {
+            self.cors.allowed_origins = split_list(&origins);
+        }
+        if let Some(methods) = lookup("BOOKS_CORS_ALLOWED_METHODS") {
+            self.cors.allowed_methods = split_list(&methods);
+        }
+        if let Some(headers) = lookup("BOOKS_CORS_ALLOWED_HEADERS") {
+            self.cors.allowed_headers = split_list(&headers);
+        }
+        if let Some(credentials) = lookup("BOOKS_CORS_ALLOW_CREDENTIALS") {
+            self.cors.allow_credentials = parse_bool("BOOKS_CORS_ALLOW_CREDENTIALS", &credentials)?;
+        }
+        if let Some(max_age) = lookup("BOOKS_CORS_MAX_AGE_SECS") {
+            self.cors.max_age_secs = parse_number("BOOKS_CORS_MAX_AGE_SECS", &max_age)?;
+        }
+        if let Some(limit) = lookup("BOOKS_BODY_LIMIT") {
+            self.limits.body_limit = parse_number("BOOKS_BODY_LIMIT", &limit)?;
+        }
+        if let Some(timeout) = lookup("BOOKS_REQUEST_TIMEOUT_SECS") {
+            self.limits.request_timeout_secs = parse_number("BOOKS_REQUEST_TIMEOUT_SECS", &timeout)?;
+        }
+        if let Some(enabled) = lookup("BOOKS_COMPRESSION") {
+            self.compression.enabled = parse_bool("BOOKS_COMPRESSION", &enabled)?;
+        }
+        if let Some(timeout) = lookup("BOOKS_SHUTDOWN_TIMEOUT_SECS") {
+            self.limits.shutdown_timeout_secs = parse_number("BOOKS_SHUTDOWN_TIMEOUT_SECS", &timeout)?;
+        }
+        if let Some(days) = lookup("BOOKS_TRASH_RETENTION_DAYS") {
+            self.trash.retention_days = parse_number("BOOKS_TRASH_RETENTION_DAYS", &days)?;
+        }
+        if let Some(key) = lookup("BOOKS_LIBRARIES_ADMIN_KEY") {
+            self.libraries.admin_key = Some(key);
+        }
+        if let Some(days) = lookup("BOOKS_LOAN_DAYS") {
+            self.lending.loan_days = parse_number("BOOKS_LOAN_DAYS", &days)?;
+        }
+        if let Some(rate) = lookup("BOOKS_FINE_DAILY_RATE_CENTS") {
+            self.fines.daily_rate_cents = parse_number("BOOKS_FINE_DAILY_RATE_CENTS", &rate)?;
+        }
+        if let Some(host) = lookup("BOOKS_SMTP_HOST") {
+            self.email.smtp_host = host;
+        }
+        if let Some(port) = lookup("BOOKS_SMTP_PORT") {
+            self.email.smtp_port = parse_number("BOOKS_SMTP_PORT", &port)?;
+        }
+        if let Some(username) = lookup("BOOKS_SMTP_USERNAME") {
+            self.email.username = Some(username);
+        }
+        if let Some(password) = lookup("BOOKS_SMTP_PASSWORD") {
+            self.email.password = Some(password);
+        }
+        if let Some(from) = lookup("BOOKS_EMAIL_FROM") {
+            self.email.from = from;
+        }
+        if let Some(attempts) = lookup("BOOKS_WEBHOOK_MAX_ATTEMPTS") {
+            self.webhooks.max_attempts = parse_number("BOOKS_WEBHOOK_MAX_ATTEMPTS", &attempts)?;
+        }
+        if let Some(max) = lookup("BOOKS_MAX_ROW_ERRORS") {
+            self.loading.max_row_errors = parse_number("BOOKS_MAX_ROW_ERRORS", &max)?;
+        }
+        Ok(())
+    }
+
+    pub fn apply_cli(&mut self, cli: &Cli) -> Result<(), ConfigError> {
+        if let Some(bind) = &cli.bind {
+            self.bind = bind.clone();
+        }
+        if let Some(path) = &cli.data_path {
+            self.data_path = path.clone();
+        }
+        if let Some(storage) = &cli.storage {
+            self.storage = parse_storage(storage).map_err(|reason| ConfigError::Value {
+                name: "--storage",
+                reason,
+            })?;
+        }
+        if let Some(level) = &cli.log_level {
+            self.log_level = level.clone();
+        }
+        Ok(())
+    }
+
+    // Collect every problem instead of stopping at the first one
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.bind.parse::<SocketAddr>().is_err() {
+            problems.push(format!("bind: '{}' is not a valid socket address", self.bind));
+        }
+
+        if self.storage == StorageBackend::Csv {
+            if self.data_path.as_os_str().is_empty() {
+                problems.push("data_path: must not be empty".to_string());
+            } else if let Some(parent) = self.data_path.parent() {
+                if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                    problems.push(format!(
+                        "data_path: directory '{}' does not exist",
+                        parent.display()
+                    ));
+                }
+            }
+        }
+
+        if self.log_level.parse::<tracing::Level>().is_err() {
+            problems.push(format!(
+                "log_level: '{}' is not one of error, warn, info, debug, trace",
+                self.log_level
+            ));
+        }
+
+        for origin in &self.cors.allowed_origins {
+            let looks_like_origin = origin == "*"
+                || origin.starts_with("http://")
+                || origin.starts_with("https://");
+            if !looks_like_origin || HeaderValue::from_str(origin).is_err() {
+                problems.push(format!("cors.allowed_origins: '{}' is not a valid origin", origin));
+            }
+        }
+        for method in &self.cors.allowed_methods {
+            if method != "*" && method.parse::<Method>().is_err() {
+                problems.push(format!("cors.allowed_methods: '{}' is not an HTTP method", method));
+            }
+        }
+        for name in &self.cors.allowed_headers {
+            if name != "*" && name.parse::<HeaderName>().is_err() {
+                problems.push(format!("cors.allowed_headers: '{}' is not a header name", name));
+            }
+        }
+        // Browsers refuse credentialed responses that use wildcards
+        if self.cors.allow_credentials {
+            let cors = &self.cors;
+            let wildcard = [&cors.allowed_origins, &cors.allowed_methods, &cors.allowed_headers]
+                .iter()
+                .any(|list| list.iter().any(|item| item == "*"));
+            if wildcard {
+                problems.push("cors.allow_credentials: cannot be combined with '*'".to_string());
+            }
+        }
+
+        if self.limits.body_limit == 0 {
+            problems.push("limits.body_limit: must be greater than zero".to_string());
+        }
+        if self.limits.request_timeout_secs == 0 {
+            problems.push("limits.request_timeout_secs: must be greater than zero".to_string());
+        }
+
+        for (name, expression) in [
+            ("jobs.backup", &self.jobs.backup),
+            ("jobs.purge_trash", &self.jobs.purge_trash),
+            ("jobs.overdue_reminders", &self.jobs.overdue_reminders),
+        ] {
+            if !expression.trim().is_empty() {
+                if let Err(err) = Schedule::parse(expression) {
+                    problems.push(format!("{}: {}", name, err));
+                }
+            }
+        }
+
+        if self.lending.loan_days == 0 {
+            problems.push("lending.loan_days: must be greater than zero".to_string());
+        }
+        if self.lending.max_loans_per_member == 0 {
+            problems.push("lending.max_loans_per_member: must be greater than zero".to_string());
+        }
+        if self.lending.pickup_days == 0 {
+            problems.push("lending.pickup_days: must be greater than zero".to_string());
+        }
+
+        if self.webhooks.max_attempts == 0 {
+            problems.push("webhooks.max_attempts: must be greater than zero".to_string());
+        }
+        if self.webhooks.timeout_secs == 0 {
+            problems.push("webhooks.timeout_secs: must be greater than zero".to_string());
+        }
+
+        let email = &self.email;
+        if !email.smtp_host.trim().is_empty() {
+            if !email.from.contains('@') || email.from.contains(['<', '>', '\r', '\n']) {
+                problems.push(format!("email.from: '{}' is not an email address", email.from));
+            }
+            if email.smtp_port == 0 {
+                problems.push("email.smtp_port: must be greater than zero".to_string());
+            }
+            if email.username.is_some() != email.password.is_some() {
+                problems.push("email.username: username and password go together".to_string());
+            }
+            if email.max_attempts == 0 {
+                problems.push("email.max_attempts: must be greater than zero".to_string());
+            }
+            if email.timeout_secs == 0 {
+                problems.push("email.timeout_secs: must be greater than zero".to_string());
+            }
+        }
+        for (name, template) in [
+            ("email.due_soon_subject", &email.due_soon_subject),
+            ("email.due_soon_body", &email.due_soon_body),
+            ("email.overdue_subject", &email.overdue_subject),
+            ("email.overdue_body", &email.overdue_body),
+        ] {
+            if let Err(err) = check_template(template) {
+                problems.push(format!("{}: {}", name, err));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    // Secrets are masked so the output can be shared or logged
+    pub fn to_toml(&self) -> String {
+        let mut shown = self.clone();
+        for secret in [&mut shown.email.password, &mut shown.libraries.admin_key] {
+            if secret.is_some() {
+                *secret = Some(REDACTED.to_string());
+            }
+        }
+        toml::to_string_pretty(&shown).expect("config is always serializable")
+    }
+}
+
+fn parse_storage(value: &str) -> Result<StorageBackend, String> {
+    match value.trim().to_lowercase().as_str() {
+        "csv" => Ok(StorageBackend::Csv),
+        "memory" => Ok(StorageBackend::Memory),
+        other => Err(format!("unknown storage backend '{}', expected csv or memory", other)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &'static str, value: &str) -> Result<T, ConfigError> {
+    value.trim().parse().map_err(|_| ConfigError::Value {
+        name,
+        reason: format!("'{}' is not a non-negative number", value),
+    })
+}
+
+fn parse_bool(name: &'static str, value: &str) -> Result<bool, ConfigError> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err(ConfigError::Value {
+            name,
+            reason: format!("'{}' is not true or false", value),
+        }),
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn from_toml(text: &str) -> (tempfile::TempDir, Config) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, text).unwrap();
+        let config = Config::from_file(&path).unwrap();
+        (dir, config)
+    }
+
+    #[test]
+    fn env_overrides_the_file_and_cli_overrides_both() {
+        let (_dir, mut config) = from_toml(
+            "bind = \"127.0.0.1:4000\"\nlog_level = \"warn\"\nstorage = \"memory\"\n[trash]\nretention_days = 7\n",
+        );
+        assert_eq!((config.bind.as_str(), config.log_level.as_str()), ("127.0.0.1:4000", "warn"));
+
+        config
+            .apply_env(env(&[("BOOKS_BIND", "127.0.0.1:5000"), ("BOOKS_LOG_LEVEL", "debug")]))
+            .unwrap();
+        let cli = Cli { bind: Some("127.0.0.1:6000".to_string()), ..Cli::default() };
+        config.apply_cli(&cli).unwrap();
+
+        // Each setting comes from the highest layer that sets it
+        assert_eq!(config.bind, "127.0.0.1:6000");
+        assert_eq!(config.log_level, "debug");
+        assert_eq!(config.storage, StorageBackend::Memory);
+        assert_eq!(config.trash.retention_days, 7);
+        // Anything no layer sets keeps its default
+        assert_eq!(config.limits.body_limit, LimitsConfig::default().body_limit);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn bad_env_and_cli_values_name_their_source() {
+        let mut config = Config::default();
+        let err = config.apply_env(env(&[("BOOKS_BODY_LIMIT", "lots")])).unwrap_err();
+        assert!(matches!(err, ConfigError::Value { name: "BOOKS_BODY_LIMIT", .. }));
+
+        let cli = Cli { storage: Some("floppy".to_string()), ..Cli::default() };
+        let err = config.apply_cli(&cli).unwrap_err();
+        assert!(matches!(err, ConfigError::Value { name: "--storage", .. }));
+    }
+
+    #[test]
+    fn printed_config_shows_the_layered_values_with_secrets_redacted() {
+        let (_dir, mut config) = from_toml("[libraries]\nadmin_key = \"from-file\"\n");
+        config.apply_env(env(&[("BOOKS_SMTP_PASSWORD", "hunter2"), ("BOOKS_LOAN_DAYS", "21")])).unwrap();
+        config.apply_cli(&Cli { log_level: Some("trace".to_string()), ..Cli::default() }).unwrap();
+
+        let printed = config.to_toml();
+        assert!(printed.contains("log_level = \"trace\""));
+        assert!(printed.contains("loan_days = 21"));
+        assert!(!printed.contains("from-file") && !printed.contains("hunter2"));
+        assert_eq!(printed.matches(REDACTED).count(), 2);
+
+        // The output reads back as the same configuration
+        let (_dir, reread) = from_toml(&printed);
+        assert_eq!((reread.log_level, reread.lending.loan_days), ("trace".to_string(), 21));
+    }
+
+    #[test]
+    fn validation_reports_every_problem_at_once() {
+        let mut config = Config::default();
+        config
+            .apply_env(env(&[
+                ("BOOKS_BIND", "not-an-address"),
+                ("BOOKS_LOG_LEVEL", "loud"),
+                ("BOOKS_CORS_ALLOWED_ORIGINS", "example.com"),
+            ]))
+            .unwrap();
+
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("expected validation problems");
+        };
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        for field in ["bind", "log_level", "cors.allowed_origins"] {
+            assert!(problems.iter().any(|problem| problem.starts_with(field)), "{field}: {problems:?}");
+        }
+    }
+}
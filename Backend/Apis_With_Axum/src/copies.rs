@@ -0,0 +1,407 @@
+use std::{fs, path::PathBuf, sync::RwLock};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    book::Book,
+    labels::{self, Label},
+    reviews::Rating,
+    storage::{atomic_write, Storage},
+    AppState,
+};
+
+const MAX_LABELS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    New,
+    #[default]
+    Good,
+    Worn,
+    Damaged,
+    Lost,
+    Withdrawn,
+}
+
+impl Condition {
+    // Lost and withdrawn copies stay on record but can never be lent
+    pub fn circulates(self) -> bool {
+        !matches!(self, Condition::Lost | Condition::Withdrawn)
+    }
+}
+
+// A physical copy of a book
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Copy {
+    pub id: u32,
+    pub book_id: u32,
+    pub barcode: String,
+    pub condition: Condition,
+    pub location: String,
+    pub acquired_on: NaiveDate,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateCopy {
+    /// Generated from the copy ID when omitted
+    pub barcode: Option<String>,
+    pub condition: Option<Condition>,
+    pub location: Option<String>,
+    /// Today when omitted
+    pub acquired_on: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct UpdateCopy {
+    pub condition: Option<Condition>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelFormat {
+    Svg,
+    Pdf,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct LabelRequest {
+    #[serde(default)]
+    pub copy_ids: Vec<u32>,
+    /// svg (default) or pdf
+    pub format: Option<LabelFormat>,
+}
+
+// How many copies of a title can go out right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+pub struct Availability {
+    /// Every copy on record
+    pub copies: usize,
+    /// Copies that are not lost or withdrawn
+    pub circulating: usize,
+    pub on_loan: usize,
+    pub available: usize,
+}
+
+// A book with the state of its copies and its rating, as returned by get_book
+// in JSON. Only adds fields next to the book's own, like RatedBook.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BookDetail {
+    #[serde(flatten)]
+    pub book: Book,
+    pub availability: Availability,
+    #[serde(flatten)]
+    pub rating: Option<Rating>,
+}
+
+#[derive(Debug)]
+pub enum CopyError {
+    BookNotFound,
+    CopyNotFound,
+    DuplicateBarcode,
+    InvalidBarcode,
+    OnLoan,
+    Storage(String),
+}
+
+impl IntoResponse for CopyError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            CopyError::BookNotFound => (StatusCode::NOT_FOUND, "❌ Book Not Found"),
+            CopyError::CopyNotFound => (StatusCode::NOT_FOUND, "❌ Copy Not Found"),
+            CopyError::DuplicateBarcode => (StatusCode::CONFLICT, "🚫 Barcode Already In Use"),
+            CopyError::InvalidBarcode => (StatusCode::BAD_REQUEST, "🚫 Barcodes Must Be Printable ASCII"),
+            CopyError::OnLoan => (StatusCode::CONFLICT, "🚫 Copy Is On Loan"),
+            CopyError::Storage(err) => {
+                tracing::error!("💥 Failed to save copies: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Save Copies")
+            }
+        };
+        (status, message).into_response()
+    }
+}
+
+// Physical inventory, persisted next to the catalog as one JSON document
+pub struct Copies {
+    path: Option<PathBuf>,
+    copies: RwLock<Vec<Copy>>,
+}
+
+impl Copies {
+    pub fn open(storage: &Storage) -> Self {
+        let path = storage.sidecar("copies.json");
+        let copies = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load copies {}: {}", path.display(), err);
+                    Vec::new()
+                }),
+            _ => Vec::new(),
+        };
+        Copies { path, copies: RwLock::new(copies) }
+    }
+
+    pub fn read<T>(&self, view: impl FnOnce(&[Copy]) -> T) -> T {
+        view(&self.copies.read().unwrap())
+    }
+
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Copy>) -> Result<T, CopyError>) -> Result<T, CopyError> {
+        let mut copies = self.copies.write().unwrap();
+        let mut draft = copies.clone();
+        let result = change(&mut draft)?;
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&draft).map_err(|err| CopyError::Storage(err.to_string()))?;
+            atomic_write(path, text).map_err(|err| CopyError::Storage(err.to_string()))?;
+        }
+        *copies = draft;
+        Ok(result)
+    }
+
+    // Copies of a book merged into another; returns how many moved
+    pub fn reassign_book(&self, from: u32, to: u32) -> Result<u32, CopyError> {
+        self.update(|copies| {
+            let mut moved = 0;
+            for copy in copies.iter_mut().filter(|copy| copy.book_id == from) {
+                copy.book_id = to;
+                moved += 1;
+            }
+            Ok(moved)
+        })
+    }
+
+    pub fn for_book(&self, book_id: u32) -> Vec<Copy> {
+        self.read(|copies| copies.iter().filter(|copy| copy.book_id == book_id).cloned().collect())
+    }
+
+    // IDs of the copies of a book that can be lent
+    pub fn circulating(&self, book_id: u32) -> Vec<u32> {
+        self.read(|copies| {
+            copies.iter().filter(|copy| copy.book_id == book_id && copy.condition.circulates()).map(|copy| copy.id).collect()
+        })
+    }
+
+    pub fn get(&self, id: u32) -> Option<Copy> {
+        self.read(|copies| copies.iter().find(|copy| copy.id == id).cloned())
+    }
+
+    // The caller checks that the book is in the catalog
+    pub fn add(&self, book_id: u32, new_copy: CreateCopy, today: NaiveDate) -> Result<Copy, CopyError> {
+        self.update(|copies| {
+            let id = copies.iter().map(|copy| copy.id).max().unwrap_or(0) + 1;
+            let barcode = match new_copy.barcode.map(|barcode| barcode.trim().to_string()) {
+                Some(barcode) if !labels::is_encodable(&barcode) => return Err(CopyError::InvalidBarcode),
+                Some(barcode) => barcode,
+                None => format!("{:010}", id),
+            };
+            if copies.iter().any(|copy| copy.barcode == barcode) {
+                return Err(CopyError::DuplicateBarcode);
+            }
+
+            let copy = Copy {
+                id,
+                book_id,
+                barcode,
+                condition: new_copy.condition.unwrap_or_default(),
+                location: new_copy.location.unwrap_or_default(),
+                acquired_on: new_copy.acquired_on.unwrap_or(today),
+            };
+            copies.push(copy.clone());
+            Ok(copy)
+        })
+    }
+
+    pub fn modify(&self, id: u32, changes: UpdateCopy) -> Result<Copy, CopyError> {
+        self.update(|copies| {
+            let copy = copies.iter_mut().find(|copy| copy.id == id).ok_or(CopyError::CopyNotFound)?;
+            if let Some(condition) = changes.condition {
+                copy.condition = condition;
+            }
+            if let Some(location) = changes.location {
+                copy.location = location;
+            }
+            Ok(copy.clone())
+        })
+    }
+
+    pub fn remove(&self, id: u32) -> Result<Copy, CopyError> {
+        self.update(|copies| {
+            let index = copies.iter().position(|copy| copy.id == id).ok_or(CopyError::CopyNotFound)?;
+            Ok(copies.remove(index))
+        })
+    }
+}
+
+// Every active loan has a copy out; available copies are the circulating
+// ones still on the shelf
+pub fn availability(state: &AppState, book_id: u32) -> Availability {
+    let copies = state.copies.read(|copies| copies.iter().filter(|copy| copy.book_id == book_id).count());
+    let circulating = state.copies.circulating(book_id);
+    let (on_loan, available) = state.lending.read(|data| {
+        (data.active_loans_for_book(book_id).len(), data.free_copies(book_id, &circulating).len())
+    });
+    Availability { copies, circulating: circulating.len(), on_loan, available }
+}
+
+// The copy joins the shelf: the first waiting hold on its book gets it
+fn shelve(state: &AppState, copy: &Copy) {
+    let circulating = state.copies.circulating(copy.book_id);
+    if let Err(err) = state.lending.copy_shelved(copy.book_id, &circulating, Utc::now()) {
+        tracing::error!("💥 Failed to reserve copy {} for a hold: {:?}", copy.id, err);
+    }
+}
+
+pub(crate) fn find_book(state: &AppState, id: u32) -> Option<Book> {
+    state.books.read().unwrap().iter().find(|book| book.id == id && !book.is_deleted()).cloned()
+}
+
+/// Register a physical copy of a book
+#[utoipa::path(post, path = "/books/{id}/copies", tag = "copies", request_body = CreateCopy,
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 201, description = "Copy added", body = Copy),
+        (status = 400, description = "Barcode cannot be printed", body = String),
+        (status = 404, description = "Book not found", body = String),
+        (status = 409, description = "Barcode already in use", body = String),
+        (status = 500, description = "Copy could not be saved", body = String),
+    ))]
+pub async fn add_copy(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Json(new_copy): Json<CreateCopy>,
+) -> Response {
+    if find_book(&state, id).is_none() {
+        return CopyError::BookNotFound.into_response();
+    }
+    match state.copies.add(id, new_copy, Utc::now().date_naive()) {
+        Ok(copy) => {
+            if copy.condition.circulates() {
+                shelve(&state, &copy);
+            }
+            (StatusCode::CREATED, Json(copy)).into_response()
+        }
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Copies of a book
+#[utoipa::path(get, path = "/books/{id}/copies", tag = "copies",
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "Every copy of the book", body = Vec<Copy>),
+        (status = 404, description = "Book not found", body = String),
+    ))]
+pub async fn book_copies(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    if find_book(&state, id).is_none() {
+        return CopyError::BookNotFound.into_response();
+    }
+    Json(state.copies.for_book(id)).into_response()
+}
+
+/// Get a copy by ID
+#[utoipa::path(get, path = "/copies/{id}", tag = "copies",
+    params(("id" = u32, Path, description = "Copy ID")),
+    responses(
+        (status = 200, description = "The copy", body = Copy),
+        (status = 404, description = "Copy not found", body = String),
+    ))]
+pub async fn get_copy(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    match state.copies.get(id) {
+        Some(copy) => Json(copy).into_response(),
+        None => CopyError::CopyNotFound.into_response(),
+    }
+}
+
+/// Record a copy's condition or new shelf location
+#[utoipa::path(put, path = "/copies/{id}", tag = "copies", request_body = UpdateCopy,
+    params(("id" = u32, Path, description = "Copy ID")),
+    responses(
+        (status = 200, description = "Copy updated", body = Copy),
+        (status = 404, description = "Copy not found", body = String),
+        (status = 409, description = "Copy is on loan and cannot be withdrawn", body = String),
+        (status = 500, description = "Copy could not be saved", body = String),
+    ))]
+pub async fn update_copy(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Json(changes): Json<UpdateCopy>,
+) -> Response {
+    let withdrawing = changes.condition == Some(Condition::Withdrawn);
+    // Holding the lending data keeps the copy from going out meanwhile
+    let result = state.lending.read(|data| {
+        let before = state.copies.get(id).ok_or(CopyError::CopyNotFound)?;
+        if withdrawing && data.copy_on_loan(id) {
+            return Err(CopyError::OnLoan);
+        }
+        state.copies.modify(id, changes).map(|copy| (before, copy))
+    });
+    match result {
+        Ok((before, copy)) => {
+            if !before.condition.circulates() && copy.condition.circulates() {
+                shelve(&state, &copy);
+            }
+            Json(copy).into_response()
+        }
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Remove a copy from the inventory
+#[utoipa::path(delete, path = "/copies/{id}", tag = "copies",
+    params(("id" = u32, Path, description = "Copy ID")),
+    responses(
+        (status = 200, description = "Copy removed", body = Copy),
+        (status = 404, description = "Copy not found", body = String),
+        (status = 409, description = "Copy is on loan", body = String),
+        (status = 500, description = "Copies could not be saved", body = String),
+    ))]
+pub async fn delete_copy(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    let result = state.lending.read(|data| {
+        if data.copy_on_loan(id) {
+            return Err(CopyError::OnLoan);
+        }
+        state.copies.remove(id)
+    });
+    match result {
+        Ok(copy) => Json(copy).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Printable Code 128 labels for a batch of copies, 30 to a Letter page
+#[utoipa::path(post, path = "/copies/labels", tag = "copies", request_body = LabelRequest,
+    responses(
+        (status = 200, description = "Label sheet", content(
+            (String = "image/svg+xml"), (Vec<u8> = "application/pdf"))),
+        (status = 400, description = "No copies, or too many for one batch", body = String),
+        (status = 404, description = "A copy was not found", body = String),
+    ))]
+pub async fn print_labels(State(state): State<AppState>, Json(request): Json<LabelRequest>) -> Response {
+    if request.copy_ids.is_empty() || request.copy_ids.len() > MAX_LABELS {
+        return (StatusCode::BAD_REQUEST, format!("🚫 Between 1 And {} Copies Per Batch", MAX_LABELS)).into_response();
+    }
+
+    let mut batch = Vec::with_capacity(request.copy_ids.len());
+    for id in &request.copy_ids {
+        let Some(copy) = state.copies.get(*id) else {
+            return CopyError::CopyNotFound.into_response();
+        };
+        // Labels for a trashed book still print, under its old title
+        let title = state.books.read().unwrap().iter().find(|book| book.id == copy.book_id).map(|book| book.title.clone());
+        batch.push(Label { barcode: copy.barcode, caption: title.unwrap_or_default() });
+    }
+
+    match request.format.unwrap_or(LabelFormat::Svg) {
+        LabelFormat::Svg => ([(header::CONTENT_TYPE, "image/svg+xml")], labels::svg_sheet(&batch)).into_response(),
+        LabelFormat::Pdf => ([(header::CONTENT_TYPE, "application/pdf")], labels::pdf_sheet(&batch)).into_response(),
+    }
+}
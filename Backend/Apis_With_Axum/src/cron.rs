@@ -0,0 +1,131 @@
+use std::fmt;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Utc};
+
+// Searching further than this many steps means the schedule never fires,
+// e.g. "0 0 30 2 *"
+const MAX_STEPS: usize = 100_000;
+
+// A five-field cron expression (minute hour day-of-month month day-of-week),
+// evaluated in UTC. Fields take *, numbers, ranges, lists and /steps; the
+// usual @hourly, @daily, @weekly, @monthly and @yearly shorthands work too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // With both day fields restricted, either may match (as in cron). Like
+    // Vixie cron, a field starting with `*` (such as `*/2`) is unrestricted.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Schedule, String> {
+        let expression = expression.trim();
+        let expanded = match expression {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        };
+
+        // 7 is Sunday as well as 0
+        let weekdays = parse_field(weekday, 0, 7, "day-of-week")?;
+        let weekdays = (weekdays | (weekdays >> 7)) & 0x7f;
+
+        Ok(Schedule {
+            source: expression.to_string(),
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days: parse_field(day, 1, 31, "day-of-month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = bit(self.days, date.day());
+        let weekday = bit(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    // The first matching minute strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.naive_utc().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut at: NaiveDateTime = start;
+
+        for _ in 0..MAX_STEPS {
+            let date = at.date();
+            if !bit(self.months, date.month()) {
+                let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+                at = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.day_matches(date) {
+                at = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !bit(self.hours, at.hour()) {
+                at = date.and_hms_opt(at.hour(), 0, 0)? + Duration::hours(1);
+            } else if !bit(self.minutes, at.minute()) {
+                at += Duration::minutes(1);
+            } else {
+                return Some(at.and_utc());
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid {} field '{}'", name, field);
+    let number = |text: &str| -> Result<u32, String> {
+        text.parse::<u32>().ok().filter(|value| (min..=max).contains(value)).ok_or_else(invalid)
+    };
+
+    let mut mask = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(invalid)?),
+            None => (item, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (number(first)?, number(last)?),
+                // "5/15" means from 5 to the end in steps of 15
+                None if item.contains('/') => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            },
+        };
+        if first > last {
+            return Err(invalid());
+        }
+        for value in (first..=last).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
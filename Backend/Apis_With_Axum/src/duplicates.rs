@@ -0,0 +1,360 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard, RwLock},
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    audit::Actor,
+    book::Book,
+    copies::find_book,
+    handler::storage_error,
+    stats::normalize_title,
+    storage::{atomic_write, Storage},
+    AppState,
+};
+
+const DEFAULT_MIN_SCORE: u32 = 80;
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+// How much the title and the author count towards a pair's score
+const TITLE_WEIGHT: f64 = 0.6;
+const AUTHOR_WEIGHT: f64 = 0.4;
+
+// Too common to make two titles candidates on their own
+const STOP_WORDS: [&str; 5] = ["a", "an", "and", "of", "the"];
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DuplicateParams {
+    /// Only pairs scoring at least this, out of 100 (default 80)
+    pub min_score: Option<u32>,
+    /// At most this many pairs (default 100, at most 1000)
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DuplicatePair {
+    /// 0 to 100; 100 for books sharing an ISBN
+    pub score: u32,
+    /// Percent alike after normalizing case, punctuation and spacing
+    pub title_similarity: u32,
+    /// Percent alike, ignoring the order of names
+    pub author_similarity: u32,
+    /// Null unless both books have an ISBN
+    pub same_isbn: Option<bool>,
+    pub reasons: Vec<String>,
+    pub first: Book,
+    pub second: Book,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct MergeBooks {
+    /// The book that remains
+    pub keep: Option<u32>,
+    /// The book folded into it; its ID then redirects to `keep`
+    pub merge: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MergeOutcome {
+    pub book: Book,
+    pub merged: u32,
+    /// Loans, holds, copies and reviews moved onto the kept book
+    pub loans: u32,
+    pub holds: u32,
+    pub copies: u32,
+    pub reviews: u32,
+}
+
+// Edits needed to turn one string into the other, counted in characters
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+// 1.0 for equal strings down to 0.0 for nothing in common
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / longest as f64
+}
+
+// "Martin, Robert C." and "Robert C. Martin" become the same words in the same order
+fn normalize_author(author: &str) -> String {
+    let mut words: Vec<String> = normalize_title(author).split(' ').map(str::to_string).collect();
+    words.sort();
+    words.join(" ")
+}
+
+struct Candidate<'a> {
+    book: &'a Book,
+    title: String,
+    author: String,
+    isbn: Option<&'a str>,
+}
+
+fn score_pair(a: &Candidate, b: &Candidate) -> DuplicatePair {
+    let title = similarity(&a.title, &b.title);
+    let author = similarity(&a.author, &b.author);
+    let percent = |value: f64| (value * 100.0).round() as u32;
+
+    let mut score = TITLE_WEIGHT * title + AUTHOR_WEIGHT * author;
+    let mut reasons = vec![
+        format!("Titles are {}% alike", percent(title)),
+        format!("Authors are {}% alike", percent(author)),
+    ];
+    let same_isbn = a.isbn.zip(b.isbn).map(|(a, b)| a == b);
+    match same_isbn {
+        Some(true) => {
+            score = 1.0;
+            reasons.insert(0, format!("Same ISBN {}", a.isbn.unwrap_or_default()));
+        }
+        // Likely different editions, or different books altogether
+        Some(false) => {
+            score /= 2.0;
+            reasons.push("Different ISBNs".to_string());
+        }
+        None => {}
+    }
+
+    DuplicatePair {
+        score: percent(score),
+        title_similarity: percent(title),
+        author_similarity: percent(author),
+        same_isbn,
+        reasons,
+        first: a.book.clone(),
+        second: b.book.clone(),
+    }
+}
+
+// Pairs of live books scoring at least `min_score`, best first. Only books
+// sharing a title word or an ISBN are compared, not every pair.
+pub fn find_duplicates(state: &AppState, min_score: u32) -> Vec<DuplicatePair> {
+    let books: Vec<Book> = state.books.read().unwrap().iter().filter(|book| !book.is_deleted()).cloned().collect();
+    let metadata = state.metadata.read(Clone::clone);
+    let candidates: Vec<Candidate> = books
+        .iter()
+        .map(|book| Candidate {
+            book,
+            title: normalize_title(&book.title),
+            author: normalize_author(&book.author),
+            isbn: metadata.get(&book.id).and_then(|details| details.isbn.as_deref()),
+        })
+        .collect();
+
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let words = candidate.title.split(' ').filter(|word| !word.is_empty() && !STOP_WORDS.contains(word));
+        let mut keys: BTreeSet<String> = words.map(|word| format!("word:{}", word)).collect();
+        keys.insert(format!("title:{}", candidate.title));
+        if let Some(isbn) = candidate.isbn {
+            keys.insert(format!("isbn:{}", isbn));
+        }
+        for key in keys {
+            groups.entry(key).or_default().push(index);
+        }
+    }
+    let pairs: BTreeSet<(usize, usize)> = groups
+        .values()
+        .flat_map(|members| {
+            members.iter().enumerate().flat_map(move |(n, a)| members[n + 1..].iter().map(move |b| (*a, *b)))
+        })
+        .collect();
+
+    let mut duplicates: Vec<DuplicatePair> = pairs
+        .into_iter()
+        .map(|(a, b)| score_pair(&candidates[a], &candidates[b]))
+        .filter(|pair| pair.score >= min_score)
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.score.cmp(&a.score).then(a.first.id.cmp(&b.first.id)).then(a.second.id.cmp(&b.second.id))
+    });
+    duplicates
+}
+
+// Where merged-away book IDs now live, persisted next to the catalog
+pub struct Merges {
+    path: Option<PathBuf>,
+    redirects: RwLock<BTreeMap<u32, u32>>,
+    // Held for a whole merge, so two merges never move the same books at once
+    running: Mutex<()>,
+}
+
+impl Merges {
+    pub fn open(storage: &Storage) -> Self {
+        let path = storage.sidecar("merges.json");
+        let redirects = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load merges {}: {}", path.display(), err);
+                    BTreeMap::new()
+                }),
+            _ => BTreeMap::new(),
+        };
+        Merges { path, redirects: RwLock::new(redirects), running: Mutex::new(()) }
+    }
+
+    pub(crate) fn begin(&self) -> MutexGuard<'_, ()> {
+        self.running.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // The book an ID was merged into, if it was
+    pub fn resolve(&self, id: u32) -> Option<u32> {
+        self.redirects.read().unwrap().get(&id).copied()
+    }
+
+    // Every ID merged into `id`, directly or through earlier merges
+    pub fn sources(&self, id: u32) -> Vec<u32> {
+        self.redirects.read().unwrap().iter().filter(|(_, to)| **to == id).map(|(from, _)| *from).collect()
+    }
+
+    // Refuses anything that would make an ID lead back to itself
+    pub fn record(&self, from: u32, to: u32) -> Result<(), String> {
+        let mut redirects = self.redirects.write().unwrap();
+        if from == to || redirects.contains_key(&to) || redirects.contains_key(&from) {
+            return Err(format!("merging {} into {} would loop", from, to));
+        }
+        let mut draft = redirects.clone();
+        // Books already merged into `from` now lead straight to `to`
+        for target in draft.values_mut().filter(|target| **target == from) {
+            *target = to;
+        }
+        draft.insert(from, to);
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&draft).map_err(|err| err.to_string())?;
+            atomic_write(path, text).map_err(|err| err.to_string())?;
+        }
+        *redirects = draft;
+        Ok(())
+    }
+}
+
+// Sent in place of a merged-away book. The Location is relative, so it stays
+// right under /libraries/{lib} too.
+pub(crate) fn merged_redirect(target: u32) -> Response {
+    (StatusCode::PERMANENT_REDIRECT, [(header::LOCATION, target.to_string())], "↪️ Book Was Merged").into_response()
+}
+
+/// Likely duplicate books, scored by title and author similarity and ISBN
+#[utoipa::path(get, path = "/books/duplicates", tag = "books", params(DuplicateParams),
+    responses(
+        (status = 200, description = "Candidate pairs, most alike first, each with its reasons", body = Vec<DuplicatePair>),
+    ))]
+pub async fn list_duplicates(State(state): State<AppState>, Query(params): Query<DuplicateParams>) -> Json<Vec<DuplicatePair>> {
+    let min_score = params.min_score.unwrap_or(DEFAULT_MIN_SCORE).min(100);
+    let mut duplicates = find_duplicates(&state, min_score);
+    duplicates.truncate(params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+    Json(duplicates)
+}
+
+/// Fold one book into another. Its loans, holds, copies, reviews, tags and
+/// metadata move to the kept book, its history stays readable through the
+/// kept book, and its ID redirects there from then on. Cannot be undone, so it
+/// needs the admin key.
+#[utoipa::path(post, path = "/books/merge", tag = "books", request_body = MergeBooks,
+    params(
+        ("X-Actor" = Option<String>, Header, description = "Who is making the change"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 200, description = "Books merged", body = MergeOutcome),
+        (status = 400, description = "Both books are required and must differ", body = String),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Book not found", body = String),
+        (status = 409, description = "The kept book was merged or deleted meanwhile", body = String),
+        (status = 500, description = "The merge could not be saved", body = String),
+    ))]
+pub async fn merge_books(
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+    headers: HeaderMap,
+    Json(request): Json<MergeBooks>,
+) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let (Some(keep), Some(merge)) = (request.keep, request.merge) else {
+        return (StatusCode::BAD_REQUEST, "🚫 Keep And Merge Required").into_response();
+    };
+    if keep == merge {
+        return (StatusCode::BAD_REQUEST, "🚫 Cannot Merge A Book Into Itself").into_response();
+    }
+    let _merging = state.merges.begin();
+    let (Some(book), Some(_)) = (find_book(&state, keep), find_book(&state, merge)) else {
+        return (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response();
+    };
+
+    // Everything that points at the book moves first, so a failure part way
+    // leaves it in the catalog and the merge can simply be retried
+    let (loans, holds) = match state.lending.reassign_book(merge, keep) {
+        Ok(moved) => moved,
+        Err(err) => return err.into_response(),
+    };
+    let copies = match state.copies.reassign_book(merge, keep) {
+        Ok(moved) => moved,
+        Err(err) => return err.into_response(),
+    };
+    let reviews = match state.reviews.reassign_book(merge, keep) {
+        Ok(moved) => moved,
+        Err(err) => return err.into_response(),
+    };
+    let moved = state
+        .tags
+        .merge(merge, keep)
+        .and_then(|_| state.metadata.merge(merge, keep));
+    if let Err(err) = moved {
+        tracing::error!("💥 Failed to merge books: {}", err);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Merge Books").into_response();
+    }
+
+    let mut books_writer = state.books.write().unwrap();
+    let Some(position) = books_writer.iter().position(|book| book.id == merge) else {
+        return (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response();
+    };
+    // Deletes do not wait for merges, so the kept book is checked again
+    let keep_is_live = books_writer.iter().any(|book| book.id == keep && !book.is_deleted());
+    if !keep_is_live || state.merges.resolve(keep).is_some() {
+        return (StatusCode::CONFLICT, "🚫 Kept Book Changed During The Merge").into_response();
+    }
+    let mut draft = books_writer.clone();
+    let merged = draft.remove(position);
+    if state.persist(&draft).is_err() {
+        return storage_error();
+    }
+    // The redirect is saved only once the book has left the catalog; if it
+    // cannot be, the book is written back so the merge can be retried
+    if let Err(err) = state.merges.record(merge, keep) {
+        tracing::error!("💥 Failed to record merge redirect: {}", err);
+        let _ = state.persist(&books_writer);
+        return (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Merge Books").into_response();
+    }
+    *books_writer = draft;
+    state.audit.record_merge(&actor, merged, keep);
+
+    Json(MergeOutcome { book, merged: merge, loans, holds, copies, reviews }).into_response()
+}
@@ -0,0 +1,329 @@
+use std::{path::PathBuf, sync::RwLock, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    config::EmailConfig,
+    fines::assess,
+    lending::{Lending, LendingError, Loan, Member},
+    smtp::{self, Message},
+    storage::{append_jsonl, load_jsonl, Storage},
+    AppState,
+};
+
+// Names a template may use, written as {name}
+const PLACEHOLDERS: [&str; 6] = ["member", "title", "author", "due_date", "days_late", "fine"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderKind {
+    // Due within the next day
+    DueSoon,
+    Overdue,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailStatus {
+    Sent,
+    // Every attempt failed; the next run tries again
+    Failed,
+}
+
+// One reminder, after all of its attempts
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SentEmail {
+    pub id: u32,
+    pub member_id: u32,
+    pub loan_id: u32,
+    pub kind: ReminderKind,
+    // A renewal moves the due date, which earns a fresh reminder
+    pub due_at: DateTime<Utc>,
+    pub to: String,
+    pub subject: String,
+    pub status: EmailStatus,
+    pub attempts: u32,
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct EmailPreferences {
+    /// false opts the member out of reminder emails
+    pub reminders: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EmailParams {
+    /// Only emails to this member
+    pub member_id: Option<u32>,
+}
+
+// What one reminder run did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReminderSummary {
+    pub sent: usize,
+    pub failed: usize,
+    // Opted out or without an email address
+    pub skipped: usize,
+}
+
+// Fills in {name} placeholders in one pass, so braces inside a value are
+// left alone; unknown names were rejected by check_template
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let filled = placeholder.find('}').and_then(|end| {
+            let value = values.iter().find(|(name, _)| *name == &placeholder[1..end])?;
+            Some((&value.1, end + 1))
+        });
+        match filled {
+            Some((value, len)) => {
+                text.push_str(value);
+                rest = &placeholder[len..];
+            }
+            None => {
+                text.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+pub fn check_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err("unclosed '{'".to_string());
+        };
+        let name = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!("unknown placeholder '{{{}}}', expected one of {}", name, PLACEHOLDERS.join(", ")));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(())
+}
+
+fn format_cents(cents: u64) -> String {
+    format!("{}.{:02}", cents / 100, cents % 100)
+}
+
+// The reminder a loan is due for at `now`, if any
+fn reminder_for(loan: &Loan, now: DateTime<Utc>) -> Option<ReminderKind> {
+    if !loan.is_active() {
+        None
+    } else if loan.is_overdue(now) {
+        Some(ReminderKind::Overdue)
+    } else if loan.due_at <= now + chrono::Duration::days(1) {
+        Some(ReminderKind::DueSoon)
+    } else {
+        None
+    }
+}
+
+// Reminder settings and the log of every reminder sent, next to the catalog
+pub struct Emails {
+    log_path: Option<PathBuf>,
+    log: RwLock<Vec<SentEmail>>,
+    settings: RwLock<EmailConfig>,
+}
+
+impl Emails {
+    pub fn open(storage: &Storage) -> Self {
+        let log_path = storage.sidecar("emails.jsonl");
+        let log = match &log_path {
+            Some(path) => load_jsonl(path).unwrap_or_else(|err| {
+                tracing::warn!("⚠️ Failed to load email log {}: {}", path.display(), err);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        Emails { log_path, log: RwLock::new(log), settings: RwLock::new(EmailConfig::default()) }
+    }
+
+    pub fn configure(&self, settings: EmailConfig) {
+        *self.settings.write().unwrap() = settings;
+    }
+
+    pub fn settings(&self) -> EmailConfig {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub fn log(&self) -> Vec<SentEmail> {
+        self.log.read().unwrap().clone()
+    }
+
+    fn already_sent(&self, loan: &Loan, kind: ReminderKind) -> bool {
+        let log = self.log.read().unwrap();
+        log.iter().any(|email| {
+            email.loan_id == loan.id && email.kind == kind && email.due_at == loan.due_at && email.status == EmailStatus::Sent
+        })
+    }
+
+    fn record(&self, mut email: SentEmail) -> SentEmail {
+        let mut log = self.log.write().unwrap();
+        email.id = log.iter().map(|email| email.id).max().unwrap_or(0) + 1;
+        if let Some(path) = &self.log_path {
+            if let Err(err) = append_jsonl(path, &email) {
+                tracing::error!("💥 Failed to write email log: {}", err);
+            }
+        }
+        log.push(email.clone());
+        email
+    }
+}
+
+// Send with exponential backoff; a permanent refusal stops early
+async fn send_with_retry(settings: &EmailConfig, message: &Message) -> (u32, Result<(), String>) {
+    let mut backoff = Duration::from_millis(settings.initial_backoff_ms);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match smtp::send(settings, message).await {
+            Ok(()) => return (attempt, Ok(())),
+            Err(err) if err.permanent || attempt >= settings.max_attempts => return (attempt, Err(err.message)),
+            Err(err) => tracing::warn!("⚠️ Email to {} failed (attempt {}): {}", message.to, attempt, err),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+}
+
+// Emails every member whose loan is due within a day or has gone overdue,
+// once per loan, kind and due date
+pub async fn send_reminders(state: &AppState, now: DateTime<Utc>) -> ReminderSummary {
+    let mut summary = ReminderSummary::default();
+    let settings = state.emails.settings();
+    if settings.smtp_host.trim().is_empty() {
+        return summary;
+    }
+    let fines = state.lending.fine_settings();
+    let due: Vec<(Loan, Member, ReminderKind)> = state.lending.read(|data| {
+        data.loans
+            .iter()
+            .filter_map(|loan| {
+                let kind = reminder_for(loan, now)?;
+                Some((loan.clone(), data.member(loan.member_id)?.clone(), kind))
+            })
+            .collect()
+    });
+
+    for (loan, member, kind) in due {
+        if state.emails.already_sent(&loan, kind) {
+            continue;
+        }
+        let Some(to) = member.email.clone().filter(|_| !member.email_opt_out) else {
+            summary.skipped += 1;
+            continue;
+        };
+
+        let (title, author) = state
+            .books
+            .read()
+            .unwrap()
+            .iter()
+            .find(|book| book.id == loan.book_id)
+            .map(|book| (book.title.clone(), book.author.clone()))
+            .unwrap_or_else(|| (format!("Book #{}", loan.book_id), "an unknown author".to_string()));
+        let fine = assess(&loan, now, &fines);
+        let values = [
+            ("member", member.name.clone()),
+            ("title", title),
+            ("author", author),
+            ("due_date", loan.due_at.format("%Y-%m-%d").to_string()),
+            ("days_late", fine.days_late.to_string()),
+            ("fine", format_cents(fine.amount_cents)),
+        ];
+        let (subject, body) = match kind {
+            ReminderKind::DueSoon => (&settings.due_soon_subject, &settings.due_soon_body),
+            ReminderKind::Overdue => (&settings.overdue_subject, &settings.overdue_body),
+        };
+        let message = Message {
+            from: settings.from.clone(),
+            to: to.clone(),
+            subject: render(subject, &values),
+            body: render(body, &values),
+        };
+
+        let (attempts, result) = send_with_retry(&settings, &message).await;
+        let status = match &result {
+            Ok(()) => {
+                summary.sent += 1;
+                EmailStatus::Sent
+            }
+            Err(err) => {
+                tracing::error!("💥 Reminder for loan {} to {} failed: {}", loan.id, to, err);
+                summary.failed += 1;
+                EmailStatus::Failed
+            }
+        };
+        state.emails.record(SentEmail {
+            id: 0,
+            member_id: member.id,
+            loan_id: loan.id,
+            kind,
+            due_at: loan.due_at,
+            to,
+            subject: message.subject,
+            status,
+            attempts,
+            error: result.err(),
+            at: Utc::now(),
+        });
+    }
+    summary
+}
+
+impl Lending {
+    pub fn set_email_opt_out(&self, member_id: u32, opt_out: bool) -> Result<Member, LendingError> {
+        self.update(|data| {
+            let member = data.members.iter_mut().find(|member| member.id == member_id).ok_or(LendingError::MemberNotFound)?;
+            member.email_opt_out = opt_out;
+            Ok(member.clone())
+        })
+    }
+}
+
+/// Turn a member's reminder emails on or off
+#[utoipa::path(put, path = "/members/{id}/email-preferences", tag = "emails", request_body = EmailPreferences,
+    params(("id" = u32, Path, description = "Member ID")),
+    responses(
+        (status = 200, description = "Preferences saved", body = Member),
+        (status = 400, description = "Reminders setting missing", body = String),
+        (status = 404, description = "Member not found", body = String),
+        (status = 500, description = "Member could not be saved", body = String),
+    ))]
+pub async fn set_email_preferences(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Json(preferences): Json<EmailPreferences>,
+) -> Response {
+    let Some(reminders) = preferences.reminders else {
+        return (StatusCode::BAD_REQUEST, "🚫 Reminders Setting Required").into_response();
+    };
+    match state.lending.set_email_opt_out(id, !reminders) {
+        Ok(member) => Json(member).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Reminder emails sent or attempted, oldest first
+#[utoipa::path(get, path = "/emails", tag = "emails", params(EmailParams),
+    responses((status = 200, description = "Email log", body = Vec<SentEmail>)))]
+pub async fn list_emails(State(state): State<AppState>, Query(params): Query<EmailParams>) -> Json<Vec<SentEmail>> {
+    let log = state.emails.log();
+    Json(log.into_iter().filter(|email| params.member_id.is_none_or(|id| email.member_id == id)).collect())
+}
@@ -0,0 +1,162 @@
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::HeaderMap,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    book::Book,
+    AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+// What clients see: the audit sequence number doubles as the event ID
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChangeEvent {
+    pub id: u64,
+    pub kind: ChangeKind,
+    pub book: Book,
+    pub at: DateTime<Utc>,
+}
+
+impl ChangeEvent {
+    // Purges are invisible to clients: the book already left the catalog when it was deleted
+    pub fn from_audit(entry: &AuditEntry) -> Option<Self> {
+        let kind = match (entry.action, &entry.before, &entry.after) {
+            (AuditAction::Purged, _, _) => return None,
+            (_, _, None) => ChangeKind::Deleted,
+            (_, None, _) => ChangeKind::Created,
+            (_, Some(before), _) if before.is_deleted() => ChangeKind::Created,
+            _ => ChangeKind::Updated,
+        };
+        let book = entry.after.clone().or_else(|| entry.before.clone())?;
+
+        Some(ChangeEvent { id: entry.seq, kind, book, at: entry.at })
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ResumeParams {
+    /// Resume after this event ID (the Last-Event-ID header wins for SSE).
+    /// Without it only changes made after connecting are sent.
+    pub since: Option<u64>,
+}
+
+// Missed events from the audit log followed by live ones. The stream ends if
+// the client falls too far behind or the server shuts down; it can reconnect
+// with its last event ID.
+fn change_stream(state: &AppState, since: Option<u64>) -> impl Stream<Item = ChangeEvent> {
+    let (backlog, receiver) = state.audit.subscribe(since);
+    let last_replayed = backlog.last().map_or(since.unwrap_or(0), |entry| entry.seq);
+
+    let live = BroadcastStream::new(receiver)
+        .take_while(|item| {
+            let lagged = matches!(item, Err(BroadcastStreamRecvError::Lagged(_)));
+            std::future::ready(!lagged)
+        })
+        .filter_map(move |item| {
+            std::future::ready(item.ok().filter(|entry| entry.seq > last_replayed))
+        });
+
+    stream::iter(backlog)
+        .chain(live)
+        .take_until(state.shutting_down())
+        .filter_map(|entry| std::future::ready(ChangeEvent::from_audit(&entry)))
+}
+
+/// Stream catalog changes as Server-Sent Events
+#[utoipa::path(get, path = "/books/events", tag = "events",
+    params(
+        ResumeParams,
+        ("Last-Event-ID" = Option<u64>, Header, description = "Last event the client saw"),
+    ),
+    responses((status = 200, description = "text/event-stream of ChangeEvent", content_type = "text/event-stream", body = ChangeEvent)))]
+pub async fn sse_events(
+    State(state): State<AppState>,
+    Query(params): Query<ResumeParams>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok());
+    let since = last_event_id.or(params.since);
+
+    let events = change_stream(&state, since).map(|change| {
+        let event = Event::default()
+            .id(change.id.to_string())
+            .event(change.kind.as_str())
+            .json_data(&change)
+            .expect("change events always serialize");
+        Ok(event)
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
+
+/// Stream catalog changes over a WebSocket as JSON text messages
+#[utoipa::path(get, path = "/books/ws", tag = "events", params(ResumeParams),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 400, description = "Not a WebSocket upgrade request", body = String),
+    ))]
+pub async fn ws_events(
+    State(state): State<AppState>,
+    Query(params): Query<ResumeParams>,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    let since = params.since;
+    upgrade.on_upgrade(move |socket| forward_changes(socket, state, since))
+}
+
+async fn forward_changes(mut socket: WebSocket, state: AppState, since: Option<u64>) {
+    let mut changes = Box::pin(change_stream(&state, since));
+
+    loop {
+        tokio::select! {
+            change = changes.next() => {
+                let Some(change) = change else { break };
+                let text = serde_json::to_string(&change).expect("change events always serialize");
+                if socket.send(Message::Text(text.into())).await.is_err() {
+                    break;
+                }
+            }
+            // Only used to notice the client going away
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::FinesConfig,
+    lending::{Lending, LendingData, LendingError, Loan},
+    AppState,
+};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// What a loan owes at a given moment; amounts are in cents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Assessment {
+    pub loan_id: u32,
+    /// Started days past the due date
+    pub days_late: u32,
+    /// Days late beyond the grace period
+    pub charged_days: u32,
+    pub amount_cents: u64,
+    /// The per-item cap was reached
+    pub capped: bool,
+    /// The loan is returned, so the amount will not grow
+    pub settled: bool,
+}
+
+// The fine for `loan` as of `now`, or as of its return. Pure, so the same
+// dates always give the same amount.
+pub fn assess(loan: &Loan, now: DateTime<Utc>, settings: &FinesConfig) -> Assessment {
+    let end = loan.returned_at.unwrap_or(now);
+    let late_seconds = (end - loan.due_at).num_seconds().max(0);
+    let days_late = u32::try_from((late_seconds + SECONDS_PER_DAY - 1) / SECONDS_PER_DAY).unwrap_or(u32::MAX);
+    let charged_days = days_late.saturating_sub(settings.grace_days);
+
+    let uncapped = u64::from(charged_days) * u64::from(settings.daily_rate_cents);
+    let cap = u64::from(settings.max_per_item_cents);
+    let capped = cap > 0 && uncapped > cap;
+    let amount_cents = if capped { cap } else { uncapped };
+
+    Assessment { loan_id: loan.id, days_late, charged_days, amount_cents, capped, settled: loan.returned_at.is_some() }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Charge,
+    Payment,
+    Waiver,
+}
+
+// One line on a member's account
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LedgerEntry {
+    pub id: u32,
+    pub member_id: u32,
+    pub kind: EntryKind,
+    pub amount_cents: u64,
+    pub loan_id: Option<u32>,
+    pub note: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Balance {
+    pub member_id: u32,
+    pub charges_cents: u64,
+    pub payments_cents: u64,
+    pub waivers_cents: u64,
+    /// Charges less payments and waivers
+    pub owed_cents: u64,
+    /// Fines still growing on overdue loans, not yet charged
+    pub accruing_cents: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreatePayment {
+    pub amount_cents: Option<u64>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateWaiver {
+    /// Defaults to what is still owed for `loan_id`, and may not exceed it
+    pub amount_cents: Option<u64>,
+    pub loan_id: Option<u32>,
+    pub note: Option<String>,
+}
+
+impl LendingData {
+    pub fn ledger_for(&self, member_id: u32) -> Vec<LedgerEntry> {
+        self.ledger.iter().filter(|entry| entry.member_id == member_id).cloned().collect()
+    }
+
+    pub fn balance(&self, member_id: u32, now: DateTime<Utc>, settings: &FinesConfig) -> Balance {
+        let total = |kind: EntryKind| -> u64 {
+            self.ledger
+                .iter()
+                .filter(|entry| entry.member_id == member_id && entry.kind == kind)
+                .map(|entry| entry.amount_cents)
+                .sum()
+        };
+        let (charges_cents, payments_cents, waivers_cents) =
+            (total(EntryKind::Charge), total(EntryKind::Payment), total(EntryKind::Waiver));
+        let accruing_cents = self
+            .active_loans_for_member(member_id)
+            .iter()
+            .map(|loan| assess(loan, now, settings).amount_cents)
+            .sum();
+
+        Balance {
+            member_id,
+            charges_cents,
+            payments_cents,
+            waivers_cents,
+            owed_cents: charges_cents.saturating_sub(payments_cents + waivers_cents),
+            accruing_cents,
+        }
+    }
+
+    // Charged for a loan and not yet waived
+    fn unwaived(&self, loan_id: u32) -> u64 {
+        let total = |kind: EntryKind| -> u64 {
+            self.ledger
+                .iter()
+                .filter(|entry| entry.loan_id == Some(loan_id) && entry.kind == kind)
+                .map(|entry| entry.amount_cents)
+                .sum()
+        };
+        total(EntryKind::Charge).saturating_sub(total(EntryKind::Waiver))
+    }
+
+    pub(crate) fn post(
+        &mut self,
+        member_id: u32,
+        kind: EntryKind,
+        amount_cents: u64,
+        loan_id: Option<u32>,
+        note: Option<String>,
+        at: DateTime<Utc>,
+    ) -> LedgerEntry {
+        let id = self.ledger.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+        let entry = LedgerEntry { id, member_id, kind, amount_cents, loan_id, note, at };
+        self.ledger.push(entry.clone());
+        entry
+    }
+}
+
+impl Lending {
+    pub fn pay(
+        &self,
+        member_id: u32,
+        amount_cents: u64,
+        note: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<LedgerEntry, LendingError> {
+        let fines = self.fine_settings();
+        self.update(|data| {
+            if data.member(member_id).is_none() {
+                return Err(LendingError::MemberNotFound);
+            }
+            if amount_cents > data.balance(member_id, now, &fines).owed_cents {
+                return Err(LendingError::ExceedsBalance);
+            }
+            Ok(data.post(member_id, EntryKind::Payment, amount_cents, None, note, now))
+        })
+    }
+
+    pub fn waive(
+        &self,
+        member_id: u32,
+        waiver: CreateWaiver,
+        now: DateTime<Utc>,
+    ) -> Result<LedgerEntry, LendingError> {
+        let fines = self.fine_settings();
+        self.update(|data| {
+            if data.member(member_id).is_none() {
+                return Err(LendingError::MemberNotFound);
+            }
+            // A waiver for one loan forgives at most what that loan was charged
+            let mut owed = data.balance(member_id, now, &fines).owed_cents;
+            if let Some(loan_id) = waiver.loan_id {
+                if !data.loans.iter().any(|loan| loan.id == loan_id && loan.member_id == member_id) {
+                    return Err(LendingError::LoanNotFound);
+                }
+                owed = owed.min(data.unwaived(loan_id));
+            }
+            let amount_cents = waiver.amount_cents.unwrap_or(owed);
+            if amount_cents == 0 || amount_cents > owed {
+                return Err(LendingError::ExceedsBalance);
+            }
+            Ok(data.post(member_id, EntryKind::Waiver, amount_cents, waiver.loan_id, waiver.note, now))
+        })
+    }
+}
+
+/// A member's charges, payments and waivers, oldest first
+#[utoipa::path(get, path = "/members/{id}/ledger", tag = "fines",
+    params(("id" = u32, Path, description = "Member ID")),
+    responses(
+        (status = 200, description = "Ledger entries", body = Vec<LedgerEntry>),
+        (status = 404, description = "Member not found", body = String),
+    ))]
+pub async fn member_ledger(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    match state.lending.read(|data| data.member(id).map(|_| data.ledger_for(id))) {
+        Some(ledger) => Json(ledger).into_response(),
+        None => LendingError::MemberNotFound.into_response(),
+    }
+}
+
+/// What a member owes, and what is still accruing on overdue loans
+#[utoipa::path(get, path = "/members/{id}/balance", tag = "fines",
+    params(("id" = u32, Path, description = "Member ID")),
+    responses(
+        (status = 200, description = "Account balance in cents", body = Balance),
+        (status = 404, description = "Member not found", body = String),
+    ))]
+pub async fn member_balance(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    let fines = state.lending.fine_settings();
+    match state.lending.read(|data| data.member(id).map(|_| data.balance(id, Utc::now(), &fines))) {
+        Some(balance) => Json(balance).into_response(),
+        None => LendingError::MemberNotFound.into_response(),
+    }
+}
+
+/// Record a payment against a member's fines; desk staff only, with the admin key
+#[utoipa::path(post, path = "/members/{id}/payments", tag = "fines", request_body = CreatePayment,
+    params(
+        ("id" = u32, Path, description = "Member ID"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 201, description = "Payment recorded", body = LedgerEntry),
+        (status = 400, description = "Amount missing or zero", body = String),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Member not found", body = String),
+        (status = 409, description = "Payment is more than the member owes", body = String),
+        (status = 500, description = "Ledger could not be saved", body = String),
+    ))]
+pub async fn add_payment(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payment): Json<CreatePayment>,
+) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let Some(amount_cents) = payment.amount_cents.filter(|amount| *amount > 0) else {
+        return (StatusCode::BAD_REQUEST, "🚫 Amount Required").into_response();
+    };
+    match state.lending.pay(id, amount_cents, payment.note, Utc::now()) {
+        Ok(entry) => (StatusCode::CREATED, Json(entry)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Forgive some or all of a member's fines; needs the admin key
+#[utoipa::path(post, path = "/members/{id}/waivers", tag = "fines", request_body = CreateWaiver,
+    params(
+        ("id" = u32, Path, description = "Member ID"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 201, description = "Waiver recorded", body = LedgerEntry),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Member or loan not found", body = String),
+        (status = 409, description = "Nothing to waive, or more than the member or loan owes", body = String),
+        (status = 500, description = "Ledger could not be saved", body = String),
+    ))]
+pub async fn add_waiver(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(waiver): Json<CreateWaiver>,
+) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    match state.lending.waive(id, waiver, Utc::now()) {
+        Ok(entry) => (StatusCode::CREATED, Json(entry)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// The fine on a loan so far, or its final fine once returned
+#[utoipa::path(get, path = "/loans/{id}/fine", tag = "fines",
+    params(("id" = u32, Path, description = "Loan ID")),
+    responses(
+        (status = 200, description = "Fine in cents", body = Assessment),
+        (status = 404, description = "Loan not found", body = String),
+    ))]
+pub async fn loan_fine(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    let fines = state.lending.fine_settings();
+    let now = Utc::now();
+    match state.lending.read(|data| data.loans.iter().find(|loan| loan.id == id).map(|loan| assess(loan, now, &fines))) {
+        Some(assessment) => Json(assessment).into_response(),
+        None => LendingError::LoanNotFound.into_response(),
+    }
+}
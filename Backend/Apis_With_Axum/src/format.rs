@@ -0,0 +1,205 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::book::Book;
+
+// Representations the book routes can produce and consume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    Xml,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Xml => "application/xml",
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            // Only an explicit text/csv; text/* or text/html should not get CSV
+            "text/csv" => Some(Format::Csv),
+            "application/xml" | "text/xml" => Some(Format::Xml),
+            _ => None,
+        }
+    }
+
+    // Pick the best format for an Accept header; a missing header means JSON
+    pub fn negotiate(accept: Option<&str>) -> Option<Format> {
+        let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+            return Some(Format::Json);
+        };
+
+        let mut ranges: Vec<(String, f32)> = accept
+            .split(',')
+            .map(|range| {
+                let mut parts = range.split(';');
+                let media_type = parts.next().unwrap_or_default().trim().to_lowercase();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+                (media_type, quality)
+            })
+            .filter(|(_, quality)| *quality > 0.0)
+            .collect();
+        // Stable, so equally weighted types keep the client's order
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges.iter().find_map(|(media_type, _)| Format::from_media_type(media_type))
+    }
+
+    fn from_content_type(content_type: &str) -> Option<Format> {
+        let media_type = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+        match media_type.as_str() {
+            "application/json" => Some(Format::Json),
+            json if json.starts_with("application/") && json.ends_with("+json") => Some(Format::Json),
+            "text/csv" => Some(Format::Csv),
+            "application/xml" | "text/xml" => Some(Format::Xml),
+            _ => None,
+        }
+    }
+}
+
+// The response format chosen from the Accept header
+pub struct Negotiated(pub Format);
+
+impl<S: Send + Sync> FromRequestParts<S> for Negotiated {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accept = parts.headers.get(header::ACCEPT).and_then(|value| value.to_str().ok());
+        Format::negotiate(accept).map(Negotiated).ok_or_else(|| {
+            (
+                StatusCode::NOT_ACCEPTABLE,
+                "🚫 Not Acceptable, Use application/json, text/csv or application/xml",
+            )
+                .into_response()
+        })
+    }
+}
+
+// A request body in JSON, CSV (header row plus one record) or XML.
+// JSON keeps axum's own rejections (400, 415, 422).
+pub struct Payload<T>(pub T);
+
+impl<T, S> FromRequest<S> for Payload<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = req.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok());
+        let format = match content_type.map(Format::from_content_type) {
+            // Let the JSON extractor explain a missing content type
+            None | Some(Some(Format::Json)) => Format::Json,
+            Some(Some(format)) => format,
+            Some(None) => {
+                return Err((
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "🚫 Unsupported Media Type, Use application/json, text/csv or application/xml",
+                )
+                    .into_response())
+            }
+        };
+
+        if format == Format::Json {
+            let Json(value) = Json::<T>::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+            return Ok(Payload(value));
+        }
+
+        let bytes = Bytes::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        let parsed = match format {
+            Format::Csv => parse_csv(&bytes),
+            _ => std::str::from_utf8(&bytes)
+                .map_err(|err| err.to_string())
+                .and_then(|text| quick_xml::de::from_str(text).map_err(|err| err.to_string())),
+        };
+        parsed
+            .map(Payload)
+            .map_err(|reason| (StatusCode::BAD_REQUEST, format!("🚫 Malformed Body: {}", reason)).into_response())
+    }
+}
+
+fn parse_csv<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    let mut reader = csv::Reader::from_reader(bytes);
+    let mut records = reader.deserialize();
+    let value = records
+        .next()
+        .ok_or_else(|| "expected a header row and one record".to_string())?
+        .map_err(|err| err.to_string())?;
+    if records.next().is_some() {
+        return Err("expected exactly one record".to_string());
+    }
+    Ok(value)
+}
+
+#[derive(Serialize)]
+#[serde(rename = "books")]
+struct BookList<'a> {
+    book: &'a [Book],
+}
+
+const CSV_HEADER: [&str; 4] = ["id", "title", "author", "deleted_at"];
+
+fn write_csv(books: &[Book]) -> Result<String, csv::Error> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    // Written by hand so an empty list still has a header
+    writer.write_record(CSV_HEADER)?;
+    for book in books {
+        writer.serialize(book)?;
+    }
+    let bytes = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(String::from_utf8(bytes).expect("csv output is utf-8"))
+}
+
+fn text_response(status: StatusCode, format: Format, body: Result<String, String>) -> Response {
+    match body {
+        Ok(body) => (status, [(header::CONTENT_TYPE, format.content_type())], body).into_response(),
+        Err(err) => {
+            tracing::error!("💥 Failed to encode response: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Encode Response").into_response()
+        }
+    }
+}
+
+pub fn books_response(format: Format, books: &[Book]) -> Response {
+    match format {
+        Format::Json => Json(books).into_response(),
+        Format::Csv => text_response(StatusCode::OK, format, write_csv(books).map_err(|err| err.to_string())),
+        Format::Xml => text_response(
+            StatusCode::OK,
+            format,
+            quick_xml::se::to_string(&BookList { book: books }).map_err(|err| err.to_string()),
+        ),
+    }
+}
+
+pub fn book_response(status: StatusCode, format: Format, book: &Book) -> Response {
+    match format {
+        Format::Json => (status, Json(book)).into_response(),
+        Format::Csv => text_response(
+            status,
+            format,
+            write_csv(std::slice::from_ref(book)).map_err(|err| err.to_string()),
+        ),
+        Format::Xml => text_response(
+            status,
+            format,
+            quick_xml::se::to_string_with_root("book", book).map_err(|err| err.to_string()),
+        ),
+    }
+}
@@ -1,108 +1,274 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response:: {IntoResponse, Json}
+    response:: {IntoResponse, Json, Response}
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{book::*, AppState};
+use crate::{
+    audit::{Actor, AuditAction},
+    book::*,
+    copies::{availability, BookDetail},
+    duplicates::merged_redirect,
+    format::{book_response, books_response, Format, Negotiated, Payload},
+    reviews::{rated_books, RatedBook},
+    AppState,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BookSort {
+    #[default]
+    Id,
+    // Best average first, then most reviews; unrated books last
+    Rating,
+}
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListParams {
+    /// id (default) or rating
+    pub sort: Option<BookSort>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
 pub struct SearchParams {
+    /// Case-insensitive substring of the title
     pub title: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct CreateBook {
     pub title: Option<String>,
     pub author: Option<String>,
 }
 
-// List all books
-pub async fn list_books(State(books): AppState) -> Json<Vec<Book>> {
-    let books_reader = books.read().unwrap();
-    Json(books_reader.clone())
+// The change is kept in memory and retried on the next save or at shutdown
+pub(crate) fn storage_error() -> axum::response::Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Save Books").into_response()
 }
 
-// Get a specific book by ID
-pub async fn get_book(Path(id): Path<u32>, State(books): AppState) -> impl IntoResponse {
-    let books_reader = books.read().unwrap();
+/// List all books as JSON, CSV or XML depending on the Accept header
+#[utoipa::path(get, path = "/books", tag = "books", params(ListParams),
+    responses(
+        (status = 200, description = "Every book in the catalog. In JSON each book keeps all of its fields \
+            and, once it has visible reviews, also carries `average_rating` and `review_count`; \
+            clients reading plain books can ignore them", content(
+            (Vec<RatedBook> = "application/json"), (String = "text/csv"), (String = "application/xml"))),
+        (status = 406, description = "No supported format is acceptable", body = String),
+    ))]
+pub async fn list_books(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+    Negotiated(format): Negotiated,
+) -> Response {
+    let books: Vec<Book> = {
+        let books_reader = state.books.read().unwrap();
+        books_reader.iter().filter(|book| !book.is_deleted()).cloned().collect()
+    };
+    let mut rated = rated_books(&state, books);
+    if params.sort.unwrap_or_default() == BookSort::Rating {
+        rated.sort_by(|a, b| {
+            let score = |book: &RatedBook| book.rating.map(|rating| (rating.average_rating, rating.review_count));
+            score(b).partial_cmp(&score(a)).unwrap_or(std::cmp::Ordering::Equal).then(a.book.id.cmp(&b.book.id))
+        });
+    }
 
-    match books_reader.iter().find(|book| book.id == id) {
-        Some(book) => Json(book).into_response(),
-        None => (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response(),
+    match format {
+        Format::Json => Json(rated).into_response(),
+        _ => books_response(format, &rated.into_iter().map(|rated| rated.book).collect::<Vec<_>>()),
     }
 }
 
-// Add a new book
-pub async fn add_book(State(books): AppState, Json(new_book): Json<CreateBook>) -> impl IntoResponse {
+/// Get a specific book by ID
+#[utoipa::path(get, path = "/books/{id}", tag = "books",
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "The book. In JSON it keeps all of its fields and also carries \
+            `availability` and, once reviewed, `average_rating` and `review_count`; \
+            clients reading a plain book can ignore them", content(
+            (BookDetail = "application/json"), (String = "text/csv"), (String = "application/xml"))),
+        (status = 308, description = "The book was merged into the one at Location", body = String),
+        (status = 404, description = "Book not found", body = String),
+        (status = 406, description = "No supported format is acceptable", body = String),
+    ))]
+pub async fn get_book(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Negotiated(format): Negotiated,
+) -> impl IntoResponse {
+    let books_reader = state.books.read().unwrap();
+
+    match books_reader.iter().find(|book| book.id == id && !book.is_deleted()) {
+        Some(book) if format == Format::Json => {
+            let detail = BookDetail {
+                book: book.clone(),
+                availability: availability(&state, id),
+                rating: state.reviews.rating(id),
+            };
+            Json(detail).into_response()
+        }
+        Some(book) => book_response(StatusCode::OK, format, book),
+        None => match state.merges.resolve(id) {
+            Some(target) => merged_redirect(target),
+            None => (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response(),
+        },
+    }
+}
+
+/// Add a new book
+#[utoipa::path(post, path = "/books/new", tag = "books",
+    request_body(content(
+        (CreateBook = "application/json"), (CreateBook = "text/csv"), (CreateBook = "application/xml"))),
+    params(("X-Actor" = Option<String>, Header, description = "Who is making the change")),
+    responses(
+        (status = 201, description = "Book created", content(
+            (Book = "application/json"), (String = "text/csv"), (String = "application/xml"))),
+        (status = 400, description = "Title or author missing", body = String),
+        (status = 406, description = "No supported format is acceptable", body = String),
+        (status = 415, description = "Body is not JSON, CSV or XML", body = String),
+        (status = 500, description = "Book could not be saved", body = String),
+    ))]
+pub async fn add_book(
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+    Negotiated(format): Negotiated,
+    Payload(new_book): Payload<CreateBook>,
+) -> impl IntoResponse {
     if new_book.title.is_none() || new_book.author.is_none() {
         return (StatusCode::BAD_REQUEST, "🚫 Title & Author Required").into_response()
     }
 
-    let mut books_writer = books.write().unwrap();
-    let new_book_id = books_writer.iter().map(|book| book.id).max().unwrap_or(0) + 1;
+    let mut books_writer = state.books.write().unwrap();
+    // Purged books keep their ID in the audit log, so never hand it out again
+    let new_book_id = books_writer
+        .iter()
+        .map(|book| book.id)
+        .chain(state.audit.max_book_id())
+        .max()
+        .unwrap_or(0)
+        + 1;
 
     let book = Book {
         id: new_book_id,
         title: new_book.title.unwrap(),
         author: new_book.author.unwrap(),
+        deleted_at: None,
     };
-    books_writer.push(book.clone());
+    // Changes go to a copy so a failed save leaves the catalog as it was
+    let mut draft = books_writer.clone();
+    draft.push(book.clone());
 
-    save_books_to_csv(&books_writer).unwrap();
-    (StatusCode::CREATED, Json(book)).into_response()
+    if state.persist(&draft).is_err() {
+        return storage_error();
+    }
+    *books_writer = draft;
+    state.audit.record(AuditAction::Created, &actor, None, Some(book.clone()));
+    book_response(StatusCode::CREATED, format, &book)
 }
 
-// Update an existing book
+/// Update an existing book
+#[utoipa::path(put, path = "/books/{id}", tag = "books",
+    request_body(content(
+        (CreateBook = "application/json"), (CreateBook = "text/csv"), (CreateBook = "application/xml"))),
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ("X-Actor" = Option<String>, Header, description = "Who is making the change"),
+    ),
+    responses(
+        (status = 200, description = "Book updated", body = String),
+        (status = 400, description = "Title or author missing", body = String),
+        (status = 404, description = "Book not found", body = String),
+        (status = 415, description = "Body is not JSON, CSV or XML", body = String),
+        (status = 500, description = "Book could not be saved", body = String),
+    ))]
 pub async fn update_book(
     Path(id): Path<u32>,
-    State(books): AppState,
-    Json(updated): Json<CreateBook>,
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+    Payload(updated): Payload<CreateBook>,
 ) -> impl IntoResponse {
     if updated.title.is_none() || updated.author.is_none() {
         return (StatusCode::BAD_REQUEST, "🚫 Title & Author Required").into_response()
     }
 
-    let mut books_writer = books.write().unwrap();
-    let mut found = false;
+    let mut books_writer = state.books.write().unwrap();
+    let mut draft = books_writer.clone();
+    let mut change = None;
 
-    for book in books_writer.iter_mut() {
-        if book.id == id {
+    for book in draft.iter_mut() {
+        if book.id == id && !book.is_deleted() {
+            let before = book.clone();
             book.title = updated.title.unwrap();
             book.author = updated.author.unwrap();
-            found = true;
+            change = Some((before, book.clone()));
             break;
         }
     }
 
-    if found {
-        save_books_to_csv(&books_writer).unwrap();
+    if let Some((before, after)) = change {
+        if state.persist(&draft).is_err() {
+            return storage_error();
+        }
+        *books_writer = draft;
+        state.audit.record(AuditAction::Updated, &actor, Some(before), Some(after));
         (StatusCode::OK, "✅ Book Updated").into_response()
     } else {
         (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response()
     }
 }
 
-// Delete a book
-pub async fn delete_book(Path(id): Path<u32>, State(books): AppState) -> impl IntoResponse {
-    let mut books_writer = books.write().unwrap();
-    let len_before = books_writer.len();
-    books_writer.retain(|book| book.id != id);
+/// Move a book to the trash
+#[utoipa::path(delete, path = "/books/{id}", tag = "books",
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ("X-Actor" = Option<String>, Header, description = "Who is making the change"),
+    ),
+    responses(
+        (status = 200, description = "Book moved to the trash", body = String),
+        (status = 404, description = "Book not found", body = String),
+        (status = 500, description = "Book could not be saved", body = String),
+    ))]
+pub async fn delete_book(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+) -> impl IntoResponse {
+    let mut books_writer = state.books.write().unwrap();
+    let mut draft = books_writer.clone();
+    let book = draft.iter_mut().find(|book| book.id == id && !book.is_deleted());
 
-    if books_writer.len() < len_before {
-        save_books_to_csv(&books_writer).unwrap();
+    if let Some(book) = book {
+        let before = book.clone();
+        book.deleted_at = Some(Utc::now());
+        if state.persist(&draft).is_err() {
+            return storage_error();
+        }
+        *books_writer = draft;
+        state.audit.record(AuditAction::Deleted, &actor, Some(before), None);
         (StatusCode::OK, "🗑️ Book Deleted").into_response()
     } else {
         (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response()
     }
 }
 
-// Search books by title
-pub async fn search_book(Query(params): Query<SearchParams>, State(books): AppState) -> Json<Vec<Book>> {
-    let books_reader = books.read().unwrap();
+/// Search books by title
+#[utoipa::path(get, path = "/books/search", tag = "books", params(SearchParams),
+    responses(
+        (status = 200, description = "Books whose title matches. In JSON each book keeps all of its fields \
+            and, once it has visible reviews, also carries `average_rating` and `review_count`", content(
+            (Vec<RatedBook> = "application/json"), (String = "text/csv"), (String = "application/xml"))),
+        (status = 406, description = "No supported format is acceptable", body = String),
+    ))]
+pub async fn search_book(
+    Query(params): Query<SearchParams>,
+    State(state): State<AppState>,
+    Negotiated(format): Negotiated,
+) -> Response {
+    let books_reader = state.books.read().unwrap();
     
-    let filtered_books = books_reader.iter().filter(|book| {
+    let filtered_books: Vec<Book> = books_reader.iter().filter(|book| !book.is_deleted()).filter(|book| {
         if let Some(title) = &params.title {
             book.title.to_lowercase().contains(&title.to_lowercase())
         } else {
@@ -111,5 +277,8 @@ pub async fn search_book(Query(params): Query<SearchParams>, State(books): AppSt
     })
     .cloned().collect();
 
-    Json(filtered_books)
+    match format {
+        Format::Json => Json(rated_books(&state, filtered_books)).into_response(),
+        _ => books_response(format, &filtered_books),
+    }
 }
\ No newline at end of file
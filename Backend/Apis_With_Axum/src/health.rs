@@ -0,0 +1,150 @@
+use std::{fs, io};
+
+use axum::{
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{book::RowError, storage::Storage, AppState};
+
+// Result of the most recent load or save
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OpStatus {
+    pub ok: bool,
+    pub at: DateTime<Utc>,
+    pub error: Option<String>,
+}
+
+impl OpStatus {
+    pub fn success() -> Self {
+        OpStatus { ok: true, at: Utc::now(), error: None }
+    }
+
+    pub fn from_result<T, E: std::fmt::Display>(result: &Result<T, E>) -> Self {
+        OpStatus {
+            ok: result.is_ok(),
+            at: Utc::now(),
+            error: result.as_ref().err().map(|err| err.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StorageStatus {
+    pub last_load: OpStatus,
+    pub last_save: Option<OpStatus>,
+    // Rows of the data file skipped at load
+    pub row_errors: Vec<RowError>,
+    // Set when loading went too badly to accept changes
+    pub read_only: bool,
+}
+
+impl StorageStatus {
+    pub fn new(last_load: OpStatus) -> Self {
+        StorageStatus { last_load, last_save: None, row_errors: Vec::new(), read_only: false }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StorageReport {
+    pub backend: &'static str,
+    pub path: Option<String>,
+    pub reachable: bool,
+    pub last_load: OpStatus,
+    pub last_save: Option<OpStatus>,
+    /// Rows of the data file skipped at load, with line, column and reason
+    pub row_errors: Vec<RowError>,
+    /// Changes are refused until the data file is fixed and the server restarted
+    pub read_only: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessReport {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    pub catalog_size: usize,
+    pub storage: StorageReport,
+}
+
+// Can the data file be reached right now? One not created yet is reachable
+// when its directory is there to hold it.
+fn storage_reachable(storage: &Storage) -> bool {
+    let writable = |meta: fs::Metadata| !meta.permissions().readonly();
+    match storage {
+        Storage::Csv(path) => match fs::metadata(path) {
+            Ok(meta) => meta.is_file() && writable(meta),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => path
+                .parent()
+                .map(|dir| if dir.as_os_str().is_empty() { std::path::Path::new(".") } else { dir })
+                .and_then(|dir| fs::metadata(dir).ok())
+                .is_some_and(|meta| meta.is_dir() && writable(meta)),
+            Err(_) => false,
+        },
+        Storage::Memory => true,
+    }
+}
+
+/// Liveness: the process is up and serving requests
+#[utoipa::path(get, path = "/health/live", tag = "health",
+    responses((status = 200, description = "Process is alive", body = serde_json::Value)))]
+pub async fn live(State(state): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "status": "alive",
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": state.started_at.elapsed().as_secs(),
+    }))
+}
+
+/// Readiness: storage is reachable and the catalog was loaded or saved successfully
+#[utoipa::path(get, path = "/health/ready", tag = "health",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = ReadinessReport),
+        (status = 503, description = "Storage unreachable, or catalog neither loaded nor saved since", body = ReadinessReport),
+    ))]
+pub async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let status = state.status.read().unwrap().clone();
+    let catalog_size = state.books.read().unwrap().iter().filter(|book| !book.is_deleted()).count();
+    let reachable = storage_reachable(&state.storage);
+
+    let (backend, path) = match state.storage.as_ref() {
+        Storage::Csv(path) => ("csv", Some(path.display().to_string())),
+        Storage::Memory => ("memory", None),
+    };
+
+    // A later save replaces whatever could not be loaded
+    let saved = status.last_save.as_ref().is_some_and(|save| save.ok);
+    let is_ready = reachable && (status.last_load.ok || saved);
+    let report = ReadinessReport {
+        status: if is_ready { "ready" } else { "not_ready" },
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        catalog_size,
+        storage: StorageReport {
+            backend,
+            path,
+            reachable,
+            last_load: status.last_load,
+            last_save: status.last_save,
+            row_errors: status.row_errors,
+            read_only: status.read_only,
+        },
+    };
+
+    let code = if is_ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (code, Json(report))
+}
+
+// Turns away every change while the catalog is read-only; reads still work
+pub async fn read_only_guard(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !is_read && state.status.read().unwrap().read_only {
+        return (StatusCode::SERVICE_UNAVAILABLE, "🔒 Catalog Is Read-Only").into_response();
+    }
+    next.run(request).await
+}
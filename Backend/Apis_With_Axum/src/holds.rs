@@ -0,0 +1,372 @@
+use std::cmp::Reverse;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    lending::{Lending, LendingData, LendingError},
+    AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldStatus {
+    // In the queue
+    Waiting,
+    // A returned copy is set aside until expires_at
+    Ready,
+    // The holder checked the book out
+    Fulfilled,
+    Cancelled,
+    // Not picked up in time
+    Expired,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Hold {
+    pub id: u32,
+    pub book_id: u32,
+    pub member_id: u32,
+    /// Higher priorities are served first; equal priorities in the order placed
+    pub priority: u32,
+    pub placed_at: DateTime<Utc>,
+    pub status: HoldStatus,
+    pub ready_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Hold {
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, HoldStatus::Waiting | HoldStatus::Ready)
+    }
+}
+
+// A hold and where it stands; closed holds have no position
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueuedHold {
+    #[serde(flatten)]
+    pub hold: Hold,
+    /// 1 is next in line (or already holding the reserved copy)
+    pub position: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateHold {
+    pub book_id: Option<u32>,
+    pub member_id: Option<u32>,
+    pub priority: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct HoldPriority {
+    pub priority: Option<u32>,
+}
+
+impl LendingData {
+    pub fn hold(&self, id: u32) -> Option<&Hold> {
+        self.holds.iter().find(|hold| hold.id == id)
+    }
+
+    // Active holds on a book in pickup order: the reserved copy first,
+    // then higher priority, then first come first served
+    pub fn hold_queue(&self, book_id: u32) -> Vec<&Hold> {
+        let mut queue: Vec<&Hold> =
+            self.holds.iter().filter(|hold| hold.book_id == book_id && hold.is_active()).collect();
+        queue.sort_by_key(|hold| (hold.status != HoldStatus::Ready, Reverse(hold.priority), hold.placed_at, hold.id));
+        queue
+    }
+
+    pub fn queued(&self, hold: &Hold) -> QueuedHold {
+        let position = self.hold_queue(hold.book_id).iter().position(|queued| queued.id == hold.id);
+        QueuedHold { hold: hold.clone(), position: position.map(|index| index + 1) }
+    }
+
+    // Holds with a copy set aside, one copy each
+    pub fn ready_holds(&self, book_id: u32) -> Vec<&Hold> {
+        self.holds.iter().filter(|hold| hold.book_id == book_id && hold.status == HoldStatus::Ready).collect()
+    }
+
+    // A copy came back or its reservation lapsed: set it aside for whoever
+    // is first among those still waiting
+    pub(crate) fn reserve_for_next(&mut self, book_id: u32, now: DateTime<Utc>, pickup_days: u32) {
+        let next = self.hold_queue(book_id).into_iter().find(|hold| hold.status == HoldStatus::Waiting).map(|hold| hold.id);
+        let Some(next) = next else {
+            return;
+        };
+        let hold = self.holds.iter_mut().find(|hold| hold.id == next).expect("queued hold exists");
+        hold.status = HoldStatus::Ready;
+        hold.ready_at = Some(now);
+        hold.expires_at = Some(now + Duration::days(pickup_days.into()));
+    }
+
+    // Reservations past their pickup window expire and roll to the next
+    // holder, whose window starts when the previous one ended
+    pub(crate) fn expire_holds(&mut self, now: DateTime<Utc>, pickup_days: u32) -> usize {
+        let mut expired = 0;
+        while let Some(hold) = self
+            .holds
+            .iter_mut()
+            .find(|hold| hold.status == HoldStatus::Ready && hold.expires_at.is_some_and(|expires| expires <= now))
+        {
+            hold.status = HoldStatus::Expired;
+            let (book_id, ended) = (hold.book_id, hold.expires_at.unwrap_or(now));
+            self.reserve_for_next(book_id, ended, pickup_days);
+            expired += 1;
+        }
+        expired
+    }
+
+    fn has_expired_holds(&self, now: DateTime<Utc>) -> bool {
+        self.holds
+            .iter()
+            .any(|hold| hold.status == HoldStatus::Ready && hold.expires_at.is_some_and(|expires| expires <= now))
+    }
+}
+
+impl Lending {
+    // `circulating` are the book's lendable copies. The caller checks that
+    // the book is in the catalog.
+    pub fn place_hold(
+        &self,
+        book_id: u32,
+        member_id: u32,
+        priority: u32,
+        circulating: &[u32],
+        now: DateTime<Utc>,
+    ) -> Result<QueuedHold, LendingError> {
+        let pickup_days = self.settings().pickup_days;
+        self.update(|data| {
+            data.expire_holds(now, pickup_days);
+            if data.member(member_id).is_none() {
+                return Err(LendingError::MemberNotFound);
+            }
+            let holding = data.hold_queue(book_id).iter().any(|hold| hold.member_id == member_id);
+            let borrowing = data.active_loans_for_book(book_id).iter().any(|loan| loan.member_id == member_id);
+            if holding || borrowing {
+                return Err(LendingError::AlreadyHolding);
+            }
+            // A copy on the shelf that no holder is waiting to collect
+            if data.free_copies(book_id, circulating).len() > data.ready_holds(book_id).len() {
+                return Err(LendingError::Available);
+            }
+
+            let id = data.holds.iter().map(|hold| hold.id).max().unwrap_or(0) + 1;
+            let hold = Hold {
+                id,
+                book_id,
+                member_id,
+                priority,
+                placed_at: now,
+                status: HoldStatus::Waiting,
+                ready_at: None,
+                expires_at: None,
+            };
+            data.holds.push(hold.clone());
+            Ok(data.queued(&hold))
+        })
+    }
+
+    // A copy was added or put back into circulation: if it is not already
+    // spoken for, set it aside for the first holder still waiting
+    pub fn copy_shelved(&self, book_id: u32, circulating: &[u32], now: DateTime<Utc>) -> Result<(), LendingError> {
+        let pickup_days = self.settings().pickup_days;
+        self.update(|data| {
+            data.expire_holds(now, pickup_days);
+            if data.free_copies(book_id, circulating).len() > data.ready_holds(book_id).len() {
+                data.reserve_for_next(book_id, now, pickup_days);
+            }
+            Ok(())
+        })
+    }
+
+    pub fn cancel_hold(&self, hold_id: u32, now: DateTime<Utc>) -> Result<Hold, LendingError> {
+        let pickup_days = self.settings().pickup_days;
+        self.update(|data| {
+            data.expire_holds(now, pickup_days);
+            let hold = data.holds.iter_mut().find(|hold| hold.id == hold_id).ok_or(LendingError::HoldNotFound)?;
+            if !hold.is_active() {
+                return Err(LendingError::HoldClosed);
+            }
+            let was_ready = hold.status == HoldStatus::Ready;
+            hold.status = HoldStatus::Cancelled;
+            let cancelled = hold.clone();
+            if was_ready {
+                data.reserve_for_next(cancelled.book_id, now, pickup_days);
+            }
+            Ok(cancelled)
+        })
+    }
+
+    // Staff override of a hold's place in the queue
+    pub fn set_hold_priority(&self, hold_id: u32, priority: u32, now: DateTime<Utc>) -> Result<QueuedHold, LendingError> {
+        let pickup_days = self.settings().pickup_days;
+        self.update(|data| {
+            data.expire_holds(now, pickup_days);
+            let hold = data.holds.iter_mut().find(|hold| hold.id == hold_id).ok_or(LendingError::HoldNotFound)?;
+            if !hold.is_active() {
+                return Err(LendingError::HoldClosed);
+            }
+            hold.priority = priority;
+            let hold = hold.clone();
+            Ok(data.queued(&hold))
+        })
+    }
+
+    // Saves any reservations that lapsed since the last change
+    pub fn expire_holds(&self, now: DateTime<Utc>) -> Result<usize, LendingError> {
+        if !self.read(|data| data.has_expired_holds(now)) {
+            return Ok(0);
+        }
+        let pickup_days = self.settings().pickup_days;
+        self.update(|data| Ok(data.expire_holds(now, pickup_days)))
+    }
+
+    fn current<T>(&self, view: impl FnOnce(&LendingData) -> T) -> Result<T, LendingError> {
+        self.expire_holds(Utc::now())?;
+        Ok(self.read(view))
+    }
+}
+
+/// Join the queue for a book with no copy free; a priority needs the admin key
+#[utoipa::path(post, path = "/holds", tag = "lending", request_body = CreateHold,
+    params(("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; only needed to set a priority")),
+    responses(
+        (status = 201, description = "Hold placed, with its queue position", body = QueuedHold),
+        (status = 400, description = "Book or member missing", body = String),
+        (status = 401, description = "Priority given without the admin key", body = String),
+        (status = 404, description = "Book or member not found", body = String),
+        (status = 409, description = "Book is available or the member already holds or has it", body = String),
+        (status = 500, description = "Hold could not be saved", body = String),
+    ))]
+pub async fn place_hold(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(new_hold): Json<CreateHold>,
+) -> Response {
+    let (Some(book_id), Some(member_id)) = (new_hold.book_id, new_hold.member_id) else {
+        return (StatusCode::BAD_REQUEST, "🚫 Book & Member Required").into_response();
+    };
+    if new_hold.priority.is_some() && !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let in_catalog = state.books.read().unwrap().iter().any(|book| book.id == book_id && !book.is_deleted());
+    if !in_catalog {
+        return LendingError::BookNotFound.into_response();
+    }
+
+    let circulating = state.copies.circulating(book_id);
+    match state.lending.place_hold(book_id, member_id, new_hold.priority.unwrap_or(0), &circulating, Utc::now()) {
+        Ok(hold) => (StatusCode::CREATED, Json(hold)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// A hold and its position in the queue
+#[utoipa::path(get, path = "/holds/{id}", tag = "lending",
+    params(("id" = u32, Path, description = "Hold ID")),
+    responses(
+        (status = 200, description = "The hold", body = QueuedHold),
+        (status = 404, description = "Hold not found", body = String),
+    ))]
+pub async fn get_hold(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    match state.lending.current(|data| data.hold(id).map(|hold| data.queued(hold))) {
+        Ok(Some(hold)) => Json(hold).into_response(),
+        Ok(None) => LendingError::HoldNotFound.into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Leave the queue; a reserved copy passes to the next holder
+#[utoipa::path(delete, path = "/holds/{id}", tag = "lending",
+    params(("id" = u32, Path, description = "Hold ID")),
+    responses(
+        (status = 200, description = "Hold cancelled", body = Hold),
+        (status = 404, description = "Hold not found", body = String),
+        (status = 409, description = "Hold already closed", body = String),
+        (status = 500, description = "Hold could not be saved", body = String),
+    ))]
+pub async fn cancel_hold(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    match state.lending.cancel_hold(id, Utc::now()) {
+        Ok(hold) => Json(hold).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Move a hold up or down the queue; needs the admin key
+#[utoipa::path(put, path = "/holds/{id}/priority", tag = "lending", request_body = HoldPriority,
+    params(
+        ("id" = u32, Path, description = "Hold ID"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 200, description = "Hold with its new position", body = QueuedHold),
+        (status = 400, description = "Priority missing", body = String),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Hold not found", body = String),
+        (status = 409, description = "Hold already closed", body = String),
+        (status = 500, description = "Hold could not be saved", body = String),
+    ))]
+pub async fn set_hold_priority(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(update): Json<HoldPriority>,
+) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let Some(priority) = update.priority else {
+        return (StatusCode::BAD_REQUEST, "🚫 Priority Required").into_response();
+    };
+    match state.lending.set_hold_priority(id, priority, Utc::now()) {
+        Ok(hold) => Json(hold).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// The hold queue for a book, in pickup order
+#[utoipa::path(get, path = "/books/{id}/holds", tag = "lending",
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "Active holds", body = Vec<QueuedHold>),
+        (status = 500, description = "Expired holds could not be saved", body = String),
+    ))]
+pub async fn book_holds(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    let queue = state.lending.current(|data| {
+        data.hold_queue(id).into_iter().map(|hold| data.queued(hold)).collect::<Vec<_>>()
+    });
+    match queue {
+        Ok(queue) => Json(queue).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// A member's active holds with their positions
+#[utoipa::path(get, path = "/members/{id}/holds", tag = "lending",
+    params(("id" = u32, Path, description = "Member ID")),
+    responses(
+        (status = 200, description = "Active holds", body = Vec<QueuedHold>),
+        (status = 404, description = "Member not found", body = String),
+    ))]
+pub async fn member_holds(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    let holds = state.lending.current(|data| {
+        data.member(id).map(|_| {
+            data.holds
+                .iter()
+                .filter(|hold| hold.member_id == id && hold.is_active())
+                .map(|hold| data.queued(hold))
+                .collect::<Vec<_>>()
+        })
+    });
+    match holds {
+        Ok(Some(holds)) => Json(holds).into_response(),
+        Ok(None) => LendingError::MemberNotFound.into_response(),
+        Err(err) => err.into_response(),
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    config::{JobsConfig, TrashConfig},
+    cron::Schedule,
+    emails::{send_reminders, ReminderSummary},
+    storage::{append_jsonl, load_jsonl, Storage},
+    trash::purge_expired,
+    AppState,
+};
+
+// How many runs are kept in memory for GET /jobs/runs
+const HISTORY_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Backup,
+    PurgeTrash,
+    OverdueReminders,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 3] = [JobKind::Backup, JobKind::PurgeTrash, JobKind::OverdueReminders];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            JobKind::Backup => "backup",
+            JobKind::PurgeTrash => "purge_trash",
+            JobKind::OverdueReminders => "overdue_reminders",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<JobKind> {
+        JobKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    Scheduled,
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+    // The previous run was still going
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobRun {
+    pub id: u64,
+    pub job: JobKind,
+    pub trigger: Trigger,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub outcome: Outcome,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobStatus {
+    pub job: JobKind,
+    /// Cron expression; absent when the job only runs on demand
+    pub schedule: Option<String>,
+    pub next_run: Option<DateTime<Utc>>,
+    pub running: bool,
+    pub last_run: Option<JobRun>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RunsParams {
+    /// Only runs of this job
+    pub job: Option<JobKind>,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    NotFound,
+    AlreadyRunning,
+}
+
+impl IntoResponse for JobError {
+    fn into_response(self) -> Response {
+        match self {
+            JobError::NotFound => (StatusCode::NOT_FOUND, "❌ Job Not Found").into_response(),
+            JobError::AlreadyRunning => (StatusCode::CONFLICT, "🚫 Job Already Running").into_response(),
+        }
+    }
+}
+
+fn schedule_for(jobs: &JobsConfig, kind: JobKind) -> &str {
+    match kind {
+        JobKind::Backup => &jobs.backup,
+        JobKind::PurgeTrash => &jobs.purge_trash,
+        JobKind::OverdueReminders => &jobs.overdue_reminders,
+    }
+}
+
+// Clears a job's running flag however the run ends
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+// Background jobs for the whole server: the main catalog and every library.
+// Each job runs at most once at a time; runs are logged next to the catalog.
+pub struct Jobs {
+    log_path: Option<PathBuf>,
+    schedules: RwLock<BTreeMap<JobKind, Schedule>>,
+    settings: RwLock<(JobsConfig, TrashConfig)>,
+    running: [AtomicBool; 3],
+    history: RwLock<VecDeque<JobRun>>,
+    next_id: AtomicU64,
+}
+
+impl Jobs {
+    pub fn open(storage: &Storage) -> Self {
+        let log_path = storage.sidecar("jobs.jsonl");
+        let runs: Vec<JobRun> = match &log_path {
+            Some(path) => load_jsonl(path).unwrap_or_else(|err| {
+                tracing::warn!("⚠️ Failed to load job history {}: {}", path.display(), err);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        let next_id = runs.iter().map(|run| run.id).max().unwrap_or(0) + 1;
+        let history = runs.into_iter().rev().take(HISTORY_SIZE).rev().collect();
+
+        let jobs = Jobs {
+            log_path,
+            schedules: RwLock::new(BTreeMap::new()),
+            settings: RwLock::new((JobsConfig::default(), TrashConfig::default())),
+            running: Default::default(),
+            history: RwLock::new(history),
+            next_id: AtomicU64::new(next_id),
+        };
+        jobs.configure(JobsConfig::default(), TrashConfig::default());
+        jobs
+    }
+
+    // Schedules were checked in Config::validate; an empty one disables the job
+    pub fn configure(&self, jobs: JobsConfig, trash: TrashConfig) {
+        let schedules = JobKind::ALL
+            .into_iter()
+            .filter_map(|kind| {
+                let expression = schedule_for(&jobs, kind);
+                if expression.trim().is_empty() {
+                    return None;
+                }
+                Schedule::parse(expression).ok().map(|schedule| (kind, schedule))
+            })
+            .collect();
+        *self.schedules.write().unwrap() = schedules;
+        *self.settings.write().unwrap() = (jobs, trash);
+    }
+
+    fn running(&self, kind: JobKind) -> &AtomicBool {
+        &self.running[kind as usize]
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> Vec<JobStatus> {
+        let schedules = self.schedules.read().unwrap();
+        let history = self.history.read().unwrap();
+        JobKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let schedule = schedules.get(&kind);
+                JobStatus {
+                    job: kind,
+                    schedule: schedule.map(ToString::to_string),
+                    next_run: schedule.and_then(|schedule| schedule.next_after(now)),
+                    running: self.running(kind).load(Ordering::SeqCst),
+                    last_run: history.iter().rev().find(|run| run.job == kind).cloned(),
+                }
+            })
+            .collect()
+    }
+
+    // Newest first
+    pub fn runs(&self, job: Option<JobKind>) -> Vec<JobRun> {
+        let history = self.history.read().unwrap();
+        history.iter().rev().filter(|run| job.is_none_or(|job| run.job == job)).cloned().collect()
+    }
+
+    // The next scheduled moment after `after`, and the jobs due then
+    pub fn next_due(&self, after: DateTime<Utc>) -> Option<(DateTime<Utc>, Vec<JobKind>)> {
+        let schedules = self.schedules.read().unwrap();
+        let upcoming: Vec<(JobKind, DateTime<Utc>)> = schedules
+            .iter()
+            .filter_map(|(kind, schedule)| schedule.next_after(after).map(|at| (*kind, at)))
+            .collect();
+        let first = upcoming.iter().map(|(_, at)| *at).min()?;
+        Some((first, upcoming.into_iter().filter(|(_, at)| *at == first).map(|(kind, _)| kind).collect()))
+    }
+
+    fn record(&self, run: JobRun) -> JobRun {
+        if let Some(path) = &self.log_path {
+            if let Err(err) = append_jsonl(path, &run) {
+                tracing::error!("💥 Failed to write job history: {}", err);
+            }
+        }
+        let mut history = self.history.write().unwrap();
+        if history.len() == HISTORY_SIZE {
+            history.pop_front();
+        }
+        history.push_back(run.clone());
+        run
+    }
+
+    // Runs `kind` to completion, file work on the blocking pool. A run that would
+    // overlap the previous one is refused and, when scheduled, logged as skipped.
+    pub async fn run(&self, state: &AppState, kind: JobKind, trigger: Trigger) -> Result<JobRun, JobError> {
+        let started_at = Utc::now();
+        let id = || self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        if self.running(kind).swap(true, Ordering::SeqCst) {
+            if trigger == Trigger::Scheduled {
+                tracing::warn!("⏭️ Skipping {}: previous run still going", kind.name());
+                self.record(JobRun {
+                    id: id(),
+                    job: kind,
+                    trigger,
+                    started_at,
+                    finished_at: started_at,
+                    outcome: Outcome::Skipped,
+                    message: "previous run still going".to_string(),
+                });
+            }
+            return Err(JobError::AlreadyRunning);
+        }
+        let _guard = RunningGuard(self.running(kind));
+
+        let (jobs, trash) = self.settings.read().unwrap().clone();
+        let job_state = state.clone();
+        let result = match kind {
+            JobKind::Backup => tokio::task::spawn_blocking(move || backup(&job_state, jobs.backups_kept, Utc::now())).await,
+            JobKind::PurgeTrash => tokio::task::spawn_blocking(move || Ok(purge_trash(&job_state, &trash, Utc::now()))).await,
+            // Mostly waiting on the mail server, which needs no blocking thread
+            JobKind::OverdueReminders => tokio::spawn(async move { overdue_reminders(&job_state, Utc::now()).await }).await,
+        }
+        .unwrap_or_else(|err| Err(format!("job panicked: {}", err)));
+
+        let (outcome, message) = match result {
+            Ok(message) => (Outcome::Succeeded, message),
+            Err(message) => {
+                tracing::error!("💥 Job {} failed: {}", kind.name(), message);
+                (Outcome::Failed, message)
+            }
+        };
+        Ok(self.record(JobRun { id: id(), job: kind, trigger, started_at, finished_at: Utc::now(), outcome, message }))
+    }
+}
+
+// Every catalog the server holds, named for logs and backup folders
+fn catalogs(state: &AppState) -> Vec<(String, AppState)> {
+    let libraries = state.libraries.all().into_iter().map(|library| (library.name().to_string(), library.state.clone()));
+    std::iter::once((String::new(), state.clone())).chain(libraries).collect()
+}
+
+pub fn purge_trash(state: &AppState, trash: &TrashConfig, now: DateTime<Utc>) -> String {
+    let retention = chrono::Duration::days(trash.retention_days.into());
+    let purged: usize = catalogs(state).iter().map(|(_, catalog)| purge_expired(catalog, retention, now).len()).sum();
+    format!("purged {} book(s)", purged)
+}
+
+// Logs overdue loans and emails due and overdue reminders, for every catalog.
+// The run fails if any reminder could not be sent; those are retried next run.
+pub async fn overdue_reminders(state: &AppState, now: DateTime<Utc>) -> Result<String, String> {
+    let (mut loans, mut members) = (0, 0);
+    let mut emails = ReminderSummary::default();
+    for (name, catalog) in catalogs(state) {
+        let overdue = catalog.lending.overdue(now);
+        let mut by_member: BTreeMap<u32, usize> = BTreeMap::new();
+        for loan in &overdue {
+            *by_member.entry(loan.member_id).or_default() += 1;
+        }
+        for (member_id, count) in &by_member {
+            tracing::info!("⏰ Member {} has {} overdue loan(s) {}", member_id, count, name);
+        }
+        loans += overdue.len();
+        members += by_member.len();
+
+        let summary = send_reminders(&catalog, now).await;
+        emails.sent += summary.sent;
+        emails.failed += summary.failed;
+        emails.skipped += summary.skipped;
+    }
+    let message = format!(
+        "{} overdue loan(s) for {} member(s); {} reminder(s) sent, {} failed, {} skipped",
+        loans, members, emails.sent, emails.failed, emails.skipped
+    );
+    if emails.failed > 0 {
+        Err(message)
+    } else {
+        Ok(message)
+    }
+}
+
+// The CSV and every sidecar file next to it, e.g. books.csv and books.*.json
+fn catalog_files(csv: &Path) -> io::Result<Vec<PathBuf>> {
+    let Some(dir) = csv.parent().filter(|dir| dir.exists()) else {
+        return Ok(Vec::new());
+    };
+    let name = csv.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let prefix = format!("{}.", csv.file_stem().unwrap_or_default().to_string_lossy());
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_file() && (file_name == name || file_name.starts_with(&prefix)) {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Copies every catalog's files into <data dir>/backups/<timestamp>/, keeping
+// the newest `keep` backups (0 keeps them all)
+pub fn backup(state: &AppState, keep: u32, now: DateTime<Utc>) -> Result<String, String> {
+    let Storage::Csv(main_csv) = &*state.storage else {
+        return Ok("nothing to back up with memory storage".to_string());
+    };
+    state.flush().map_err(|err| format!("flush failed: {}", err))?;
+
+    let root = main_csv.parent().unwrap_or(Path::new("")).join("backups");
+    let target = root.join(now.format("%Y%m%dT%H%M%S%.3fZ").to_string());
+    let mut copied = 0;
+    for (name, catalog) in catalogs(state) {
+        let Storage::Csv(csv) = &*catalog.storage else {
+            continue;
+        };
+        let dir = if name.is_empty() { target.clone() } else { target.join("libraries").join(&name) };
+        fs::create_dir_all(&dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        for file in catalog_files(csv).map_err(|err| err.to_string())? {
+            let destination = dir.join(file.file_name().unwrap_or_default());
+            fs::copy(&file, &destination).map_err(|err| format!("{}: {}", file.display(), err))?;
+            copied += 1;
+        }
+    }
+
+    let mut pruned = 0;
+    if keep > 0 {
+        let mut backups: Vec<PathBuf> = fs::read_dir(&root)
+            .map_err(|err| err.to_string())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_dir())
+            .collect();
+        backups.sort();
+        let excess = backups.len().saturating_sub(keep as usize);
+        for old in &backups[..excess] {
+            fs::remove_dir_all(old).map_err(|err| format!("{}: {}", old.display(), err))?;
+            pruned += 1;
+        }
+    }
+
+    tracing::info!("💾 Backed up {} file(s) to {}", copied, target.display());
+    Ok(format!("copied {} file(s) to {}, pruned {} old backup(s)", copied, target.display(), pruned))
+}
+
+impl AppState {
+    // Run scheduled jobs until shutdown
+    pub fn spawn_scheduler(&self) {
+        let state = self.clone();
+        let shutdown = self.shutting_down();
+        tokio::spawn(async move {
+            tokio::pin!(shutdown);
+            let mut after = Utc::now();
+            loop {
+                let Some((at, due)) = state.jobs.next_due(after) else {
+                    return;
+                };
+                let wait = (at - Utc::now()).to_std().unwrap_or_default();
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = &mut shutdown => return,
+                }
+                // Runs go to their own tasks so a slow job never delays another
+                for kind in due {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        let _ = state.jobs.run(&state, kind, Trigger::Scheduled).await;
+                    });
+                }
+                // After a suspend, carry on from now rather than replaying missed runs
+                after = at.max(Utc::now());
+            }
+        });
+    }
+}
+
+/// Scheduled jobs, when they next run and how they last went
+#[utoipa::path(get, path = "/jobs", tag = "jobs",
+    responses((status = 200, description = "Every job", body = Vec<JobStatus>)))]
+pub async fn list_jobs(State(state): State<AppState>) -> Json<Vec<JobStatus>> {
+    Json(state.jobs.status(Utc::now()))
+}
+
+/// Past job runs, newest first
+#[utoipa::path(get, path = "/jobs/runs", tag = "jobs", params(RunsParams),
+    responses((status = 200, description = "Run history", body = Vec<JobRun>)))]
+pub async fn list_runs(State(state): State<AppState>, Query(params): Query<RunsParams>) -> Json<Vec<JobRun>> {
+    Json(state.jobs.runs(params.job))
+}
+
+/// Run a job now and wait for it to finish
+#[utoipa::path(post, path = "/jobs/{job}/run", tag = "jobs",
+    params(
+        ("job" = String, Path, description = "backup, purge_trash or overdue_reminders"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 200, description = "The finished run; a failed job is reported in its outcome", body = JobRun),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "No such job", body = String),
+        (status = 409, description = "The job is already running", body = String),
+    ))]
+pub async fn run_job(UrlPath(job): UrlPath<String>, State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let Some(kind) = JobKind::from_name(&job) else {
+        return JobError::NotFound.into_response();
+    };
+    match state.jobs.run(&state, kind, Trigger::Manual).await {
+        Ok(run) => Json(run).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
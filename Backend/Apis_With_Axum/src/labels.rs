@@ -0,0 +1,265 @@
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+// Code 128 symbol widths, alternating bar and space, indexed by symbol value
+const PATTERNS: [&str; 106] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212", "221213",
+    "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221", "223211", "221132",
+    "221231", "213212", "223112", "312131", "311222", "321122", "321221", "312212", "322112", "322211",
+    "212123", "212321", "232121", "111323", "131123", "131321", "112313", "132113", "132311", "211313",
+    "231113", "231311", "112133", "112331", "132131", "113123", "113321", "133121", "313121", "211331",
+    "231131", "213113", "213311", "213131", "311123", "311321", "331121", "312113", "312311", "332111",
+    "314111", "221411", "431111", "111224", "111422", "121124", "121421", "141122", "141221", "112214",
+    "112412", "122114", "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111",
+    "111242", "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311", "113141",
+    "114131", "311141", "411131", "211412", "211214", "211232",
+];
+const STOP: &str = "2331112";
+const START_B: usize = 104;
+const START_C: usize = 105;
+
+// Whether `text` can be printed as a Code 128 barcode
+pub fn is_encodable(text: &str) -> bool {
+    !text.is_empty() && text.bytes().all(|byte| (32..=126).contains(&byte))
+}
+
+// Symbol values for `text`: code set C for even-length digit strings,
+// code set B for everything else, with the checksum but not the stop symbol
+pub fn code128_symbols(text: &str) -> Option<Vec<usize>> {
+    if !is_encodable(text) {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let mut symbols = if bytes.len().is_multiple_of(2) && bytes.iter().all(u8::is_ascii_digit) {
+        let pairs = bytes.chunks(2).map(|pair| usize::from((pair[0] - b'0') * 10 + (pair[1] - b'0')));
+        std::iter::once(START_C).chain(pairs).collect::<Vec<_>>()
+    } else {
+        std::iter::once(START_B).chain(bytes.iter().map(|byte| usize::from(byte - 32))).collect()
+    };
+
+    let weighted: usize = symbols.iter().enumerate().map(|(position, value)| position.max(1) * value).sum();
+    symbols.push(weighted % 103);
+    Some(symbols)
+}
+
+// Modules left to right, true for a bar; no quiet zone
+pub fn code128(text: &str) -> Option<Vec<bool>> {
+    let symbols = code128_symbols(text)?;
+    let widths = symbols.iter().map(|&value| PATTERNS[value]).chain(std::iter::once(STOP));
+
+    let mut modules = Vec::new();
+    for pattern in widths {
+        for (index, width) in pattern.bytes().enumerate() {
+            modules.extend(std::iter::repeat_n(index % 2 == 0, usize::from(width - b'0')));
+        }
+    }
+    Some(modules)
+}
+
+// One printed label
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub barcode: String,
+    pub caption: String,
+}
+
+// Sheet geometry in points, laid out like 30-up 1" x 2⅝" address labels on US Letter
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const COLUMNS: usize = 3;
+const ROWS: usize = 10;
+const LABEL_WIDTH: f32 = 189.0;
+const LABEL_HEIGHT: f32 = 72.0;
+const COLUMN_PITCH: f32 = 198.0;
+const LEFT_MARGIN: f32 = 13.5;
+const TOP_MARGIN: f32 = 36.0;
+const PADDING: f32 = 9.0;
+// Code 128 needs clear space of at least ten module widths on either side
+const QUIET_MODULES: f32 = 10.0;
+const BAR_HEIGHT: f32 = 34.0;
+const CAPTION_CHARS: usize = 40;
+
+pub const LABELS_PER_PAGE: usize = COLUMNS * ROWS;
+
+// Bars of one label as (x, width) runs, x measured from the label's left edge
+fn bar_runs(modules: &[bool]) -> Vec<(f32, f32)> {
+    let module = ((LABEL_WIDTH - 2.0 * PADDING) / (modules.len() as f32 + 2.0 * QUIET_MODULES)).min(1.5);
+    let left = (LABEL_WIDTH - module * modules.len() as f32) / 2.0;
+
+    let mut runs: Vec<(f32, f32)> = Vec::new();
+    let mut start = None;
+    for (index, &bar) in modules.iter().chain(std::iter::once(&false)).enumerate() {
+        match (bar, start) {
+            (true, None) => start = Some(index),
+            (false, Some(first)) => {
+                runs.push((left + first as f32 * module, (index - first) as f32 * module));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+fn caption(text: &str) -> String {
+    if text.chars().count() <= CAPTION_CHARS {
+        return text.to_string();
+    }
+    let mut caption: String = text.chars().take(CAPTION_CHARS - 3).collect();
+    caption.push_str("...");
+    caption
+}
+
+// Top-left corner of the label at `index`, within its page
+fn origin(index: usize) -> (f32, f32) {
+    let slot = index % LABELS_PER_PAGE;
+    (LEFT_MARGIN + (slot % COLUMNS) as f32 * COLUMN_PITCH, TOP_MARGIN + (slot / COLUMNS) as f32 * LABEL_HEIGHT)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Labels whose barcode cannot be encoded are skipped; callers validate first
+pub fn svg_sheet(labels: &[Label]) -> String {
+    let pages = labels.len().div_ceil(LABELS_PER_PAGE).max(1);
+    let height = PAGE_HEIGHT * pages as f32;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{PAGE_WIDTH}pt\" height=\"{height}pt\" \
+         viewBox=\"0 0 {PAGE_WIDTH} {height}\" font-family=\"Helvetica, Arial, sans-serif\">\n"
+    );
+
+    for (index, label) in labels.iter().enumerate() {
+        let Some(modules) = code128(&label.barcode) else {
+            continue;
+        };
+        let (x, y) = origin(index);
+        let y = y + (index / LABELS_PER_PAGE) as f32 * PAGE_HEIGHT;
+        svg.push_str(&format!("<g transform=\"translate({x} {y})\">\n"));
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"7\" text-anchor=\"middle\">{}</text>\n",
+            LABEL_WIDTH / 2.0,
+            PADDING + 5.0,
+            escape_xml(&caption(&label.caption))
+        ));
+        for (bar_x, width) in bar_runs(&modules) {
+            svg.push_str(&format!(
+                "<rect x=\"{bar_x:.3}\" y=\"{}\" width=\"{width:.3}\" height=\"{BAR_HEIGHT}\"/>\n",
+                PADDING + 9.0
+            ));
+        }
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"8\" text-anchor=\"middle\">{}</text>\n</g>\n",
+            LABEL_WIDTH / 2.0,
+            PADDING + 9.0 + BAR_HEIGHT + 9.0,
+            escape_xml(&label.barcode)
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// The font uses WinAnsiEncoding: Latin-1 plus the Windows-1252 extras in
+// 0x80-0x9F (€, smart quotes, dashes...). Anything else prints as '?'.
+pub fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|ch| match ch {
+            '€' => 0x80,
+            '‚' => 0x82,
+            'ƒ' => 0x83,
+            '„' => 0x84,
+            '…' => 0x85,
+            '†' => 0x86,
+            '‡' => 0x87,
+            'ˆ' => 0x88,
+            '‰' => 0x89,
+            'Š' => 0x8A,
+            '‹' => 0x8B,
+            'Œ' => 0x8C,
+            'Ž' => 0x8E,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            '˜' => 0x98,
+            '™' => 0x99,
+            'š' => 0x9A,
+            '›' => 0x9B,
+            'œ' => 0x9C,
+            'ž' => 0x9E,
+            'Ÿ' => 0x9F,
+            // The C1 controls have no glyphs in WinAnsi
+            '\u{80}'..='\u{9F}' => b'?',
+            _ => u8::try_from(u32::from(ch)).unwrap_or(b'?'),
+        })
+        .collect()
+}
+
+// Helvetica is about half an em wide on average, close enough to centre a line
+fn centred_x(text: &[u8], size: f32) -> f32 {
+    (LABEL_WIDTH - text.len() as f32 * size * 0.5) / 2.0
+}
+
+pub fn pdf_sheet(labels: &[Label]) -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let font_name = Name(b"F1");
+
+    let chunks: Vec<&[Label]> = if labels.is_empty() { vec![&[]] } else { labels.chunks(LABELS_PER_PAGE).collect() };
+    let page_ids: Vec<Ref> = (0..chunks.len()).map(|page| Ref::new(4 + 2 * page as i32)).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(page_ids.len() as i32);
+    pdf.type1_font(font_id).base_font(Name(b"Helvetica")).encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (page, chunk) in chunks.iter().enumerate() {
+        let page_id = page_ids[page];
+        let content_id = Ref::new(page_id.get() + 1);
+
+        let mut content = Content::new();
+        for (index, label) in chunk.iter().enumerate() {
+            let Some(modules) = code128(&label.barcode) else {
+                continue;
+            };
+            // PDF measures y from the bottom of the page
+            let (x, top) = origin(index);
+            let top = PAGE_HEIGHT - top;
+
+            let caption = win_ansi(&caption(&label.caption));
+            content
+                .begin_text()
+                .set_font(font_name, 7.0)
+                .next_line(x + centred_x(&caption, 7.0), top - PADDING - 5.0)
+                .show(Str(&caption))
+                .end_text();
+            for (bar_x, width) in bar_runs(&modules) {
+                content.rect(x + bar_x, top - PADDING - 9.0 - BAR_HEIGHT, width, BAR_HEIGHT);
+            }
+            content.fill_nonzero();
+            let barcode = win_ansi(&label.barcode);
+            content
+                .begin_text()
+                .set_font(font_name, 8.0)
+                .next_line(x + centred_x(&barcode, 8.0), top - PADDING - 9.0 - BAR_HEIGHT - 9.0)
+                .show(Str(&barcode))
+                .end_text();
+        }
+
+        let mut page_writer = pdf.page(page_id);
+        page_writer
+            .parent(page_tree_id)
+            .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .contents(content_id);
+        page_writer.resources().fonts().pair(font_name, font_id);
+        page_writer.finish();
+        pdf.stream(content_id, &content.finish());
+    }
+
+    pdf.finish()
+}
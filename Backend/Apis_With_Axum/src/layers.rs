@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{HeaderName, Method, StatusCode},
+    Router,
+};
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer},
+    decompression::RequestDecompressionLayer,
+    limit::RequestBodyLimitLayer,
+    timeout::TimeoutLayer,
+};
+
+use crate::config::{Config, CorsConfig};
+
+// Transport concerns shared by every route, outermost last:
+// - bodies sent with Content-Encoding gzip, br or zstd are decompressed
+// - body_limit applies to the decompressed body, so small gzip bombs fail too
+// - responses are compressed per Accept-Encoding (never event streams)
+// - requests running longer than request_timeout_secs get 408
+// - CORS preflights are answered before anything else runs
+pub fn http_layers(router: Router, config: &Config) -> Router {
+    let limits = &config.limits;
+    let router = router
+        .layer(DefaultBodyLimit::max(limits.body_limit))
+        .layer(RequestBodyLimitLayer::new(limits.body_limit))
+        .layer(RequestDecompressionLayer::new());
+
+    let router = if config.compression.enabled {
+        let predicate = SizeAbove::new(config.compression.min_size)
+            .and(NotForContentType::GRPC)
+            .and(NotForContentType::IMAGES)
+            .and(NotForContentType::SSE);
+        router.layer(CompressionLayer::new().compress_when(predicate))
+    } else {
+        router
+    };
+
+    router
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            Duration::from_secs(limits.request_timeout_secs),
+        ))
+        .layer(cors_layer(&config.cors))
+}
+
+// Values were checked in Config::validate, so parsing cannot fail here
+pub fn cors_layer(cors: &CorsConfig) -> CorsLayer {
+    let is_any = |list: &[String]| list.iter().any(|item| item == "*");
+
+    let origins = if is_any(&cors.allowed_origins) {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(cors.allowed_origins.iter().map(|origin| origin.parse().unwrap()))
+    };
+    let methods = if is_any(&cors.allowed_methods) {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(cors.allowed_methods.iter().map(|method| method.to_uppercase().parse::<Method>().unwrap()))
+    };
+    let headers = if is_any(&cors.allowed_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(cors.allowed_headers.iter().map(|name| name.parse::<HeaderName>().unwrap()))
+    };
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(cors.allow_credentials)
+        .max_age(Duration::from_secs(cors.max_age_secs))
+}
@@ -0,0 +1,491 @@
+use std::{fs, path::PathBuf, sync::RwLock};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    config::{FinesConfig, LendingConfig},
+    fines::{self, EntryKind, LedgerEntry},
+    holds::{Hold, HoldStatus},
+    storage::{atomic_write, Storage},
+    AppState,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Member {
+    pub id: u32,
+    pub name: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// No reminder emails
+    #[serde(default)]
+    pub email_opt_out: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Loan {
+    pub id: u32,
+    pub book_id: u32,
+    /// The copy lent out; loans made before copies were tracked have none
+    #[serde(default)]
+    pub copy_id: Option<u32>,
+    pub member_id: u32,
+    pub checked_out_at: DateTime<Utc>,
+    pub due_at: DateTime<Utc>,
+    pub renewals: u32,
+    pub returned_at: Option<DateTime<Utc>>,
+}
+
+impl Loan {
+    pub fn is_active(&self) -> bool {
+        self.returned_at.is_none()
+    }
+
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.is_active() && self.due_at < now
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateMember {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateLoan {
+    pub book_id: Option<u32>,
+    pub member_id: Option<u32>,
+    /// The copy whose label was scanned; any free copy when omitted
+    pub copy_id: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum LendingError {
+    BookNotFound,
+    MemberNotFound,
+    LoanNotFound,
+    CopyNotFound,
+    OnLoan,
+    CopyOnLoan,
+    LoanLimit,
+    AlreadyReturned,
+    RenewalLimit,
+    Overdue,
+    HoldsWaiting,
+    HoldNotFound,
+    Reserved,
+    AlreadyHolding,
+    Available,
+    HoldClosed,
+    ExceedsBalance,
+    Storage(String),
+}
+
+impl IntoResponse for LendingError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            LendingError::BookNotFound => (StatusCode::NOT_FOUND, "❌ Book Not Found"),
+            LendingError::MemberNotFound => (StatusCode::NOT_FOUND, "❌ Member Not Found"),
+            LendingError::LoanNotFound => (StatusCode::NOT_FOUND, "❌ Loan Not Found"),
+            LendingError::CopyNotFound => (StatusCode::NOT_FOUND, "❌ Copy Not Found"),
+            LendingError::OnLoan => (StatusCode::CONFLICT, "🚫 No Copy Is Free To Lend"),
+            LendingError::CopyOnLoan => (StatusCode::CONFLICT, "🚫 Copy Is On Loan Or Set Aside"),
+            LendingError::LoanLimit => (StatusCode::CONFLICT, "🚫 Member Has Too Many Loans"),
+            LendingError::AlreadyReturned => (StatusCode::CONFLICT, "🚫 Loan Already Returned"),
+            LendingError::RenewalLimit => (StatusCode::CONFLICT, "🚫 No Renewals Left"),
+            LendingError::Overdue => (StatusCode::CONFLICT, "🚫 Overdue Loans Cannot Be Renewed"),
+            LendingError::HoldsWaiting => (StatusCode::CONFLICT, "🚫 Other Members Are Waiting For This Book"),
+            LendingError::HoldNotFound => (StatusCode::NOT_FOUND, "❌ Hold Not Found"),
+            LendingError::Reserved => (StatusCode::CONFLICT, "🚫 Book Is Reserved For Another Member"),
+            LendingError::AlreadyHolding => (StatusCode::CONFLICT, "🚫 Member Already Holds Or Has This Book"),
+            LendingError::Available => (StatusCode::CONFLICT, "🚫 Book Is Available, Check It Out Instead"),
+            LendingError::HoldClosed => (StatusCode::CONFLICT, "🚫 Hold Is No Longer Active"),
+            LendingError::ExceedsBalance => (StatusCode::CONFLICT, "🚫 Amount Exceeds What Is Owed"),
+            LendingError::Storage(err) => {
+                tracing::error!("💥 Failed to save lending data: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Save Loans")
+            }
+        };
+        (status, message).into_response()
+    }
+}
+
+// Everything the lending desk knows, stored as one JSON document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LendingData {
+    pub members: Vec<Member>,
+    pub loans: Vec<Loan>,
+    #[serde(default)]
+    pub holds: Vec<Hold>,
+    #[serde(default)]
+    pub ledger: Vec<LedgerEntry>,
+}
+
+impl LendingData {
+    pub fn member(&self, id: u32) -> Option<&Member> {
+        self.members.iter().find(|member| member.id == id)
+    }
+
+    pub fn active_loans_for_book(&self, book_id: u32) -> Vec<&Loan> {
+        self.loans.iter().filter(|loan| loan.book_id == book_id && loan.is_active()).collect()
+    }
+
+    // Which of a book's circulating copies are on the shelf. Loans made
+    // before copies were tracked each take one copy without naming it.
+    pub fn free_copies(&self, book_id: u32, circulating: &[u32]) -> Vec<u32> {
+        let loans = self.active_loans_for_book(book_id);
+        let unnamed = loans.iter().filter(|loan| loan.copy_id.is_none()).count();
+        let mut free: Vec<u32> =
+            circulating.iter().copied().filter(|id| !loans.iter().any(|loan| loan.copy_id == Some(*id))).collect();
+        free.truncate(free.len().saturating_sub(unnamed));
+        free
+    }
+
+    // A copy that is out on a loan cannot leave the inventory
+    pub fn copy_on_loan(&self, copy_id: u32) -> bool {
+        self.loans.iter().any(|loan| loan.copy_id == Some(copy_id) && loan.is_active())
+    }
+
+    pub fn active_loans_for_member(&self, member_id: u32) -> Vec<Loan> {
+        self.loans.iter().filter(|loan| loan.member_id == member_id && loan.is_active()).cloned().collect()
+    }
+}
+
+// Members and loans, persisted next to the catalog. Every change is applied
+// to a copy and only kept once it has been written to disk.
+pub struct Lending {
+    path: Option<PathBuf>,
+    data: RwLock<LendingData>,
+    settings: RwLock<LendingConfig>,
+    fines: RwLock<FinesConfig>,
+}
+
+impl Lending {
+    pub fn open(storage: &Storage) -> Self {
+        let path = storage.sidecar("lending.json");
+        let data = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load lending data {}: {}", path.display(), err);
+                    LendingData::default()
+                }),
+            _ => LendingData::default(),
+        };
+        Lending {
+            path,
+            data: RwLock::new(data),
+            settings: RwLock::new(LendingConfig::default()),
+            fines: RwLock::new(FinesConfig::default()),
+        }
+    }
+
+    pub fn configure(&self, settings: LendingConfig) {
+        *self.settings.write().unwrap() = settings;
+    }
+
+    pub fn settings(&self) -> LendingConfig {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub fn configure_fines(&self, fines: FinesConfig) {
+        *self.fines.write().unwrap() = fines;
+    }
+
+    pub fn fine_settings(&self) -> FinesConfig {
+        self.fines.read().unwrap().clone()
+    }
+
+    pub fn read<T>(&self, view: impl FnOnce(&LendingData) -> T) -> T {
+        view(&self.data.read().unwrap())
+    }
+
+    pub fn update<T>(
+        &self,
+        change: impl FnOnce(&mut LendingData) -> Result<T, LendingError>,
+    ) -> Result<T, LendingError> {
+        let mut data = self.data.write().unwrap();
+        let mut draft = data.clone();
+        let result = change(&mut draft)?;
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&draft).map_err(|err| LendingError::Storage(err.to_string()))?;
+            atomic_write(path, text).map_err(|err| LendingError::Storage(err.to_string()))?;
+        }
+        *data = draft;
+        Ok(result)
+    }
+
+    pub fn add_member(&self, name: String, email: Option<String>, now: DateTime<Utc>) -> Result<Member, LendingError> {
+        self.update(|data| {
+            let id = data.members.iter().map(|member| member.id).max().unwrap_or(0) + 1;
+            let member = Member { id, name, email, created_at: now, email_opt_out: false };
+            data.members.push(member.clone());
+            Ok(member)
+        })
+    }
+
+    // Loans and holds follow a book merged into another, as do its copies.
+    // A member queued for both keeps their place for the surviving book only.
+    // Returns how many loans and holds moved.
+    pub fn reassign_book(&self, from: u32, to: u32) -> Result<(u32, u32), LendingError> {
+        self.update(|data| {
+            let mut loans = 0;
+            for loan in data.loans.iter_mut().filter(|loan| loan.book_id == from) {
+                loan.book_id = to;
+                loans += 1;
+            }
+            let holders: Vec<u32> =
+                data.holds.iter().filter(|hold| hold.book_id == to && hold.is_active()).map(|hold| hold.member_id).collect();
+            let mut holds = 0;
+            for hold in data.holds.iter_mut().filter(|hold| hold.book_id == from) {
+                if hold.is_active() && holders.contains(&hold.member_id) {
+                    hold.status = HoldStatus::Cancelled;
+                }
+                hold.book_id = to;
+                holds += 1;
+            }
+            Ok((loans, holds))
+        })
+    }
+
+    // Lends one of `circulating`, the book's lendable copies, or `copy_id`
+    // when the desk scanned a particular one. Copies set aside for other
+    // holders stay on the shelf. The caller checks that the book is in the
+    // catalog and that `copy_id`, if any, is one of `circulating`.
+    pub fn checkout(
+        &self,
+        book_id: u32,
+        member_id: u32,
+        circulating: &[u32],
+        copy_id: Option<u32>,
+        now: DateTime<Utc>,
+    ) -> Result<Loan, LendingError> {
+        let settings = self.settings();
+        self.update(|data| {
+            data.expire_holds(now, settings.pickup_days);
+            if data.member(member_id).is_none() {
+                return Err(LendingError::MemberNotFound);
+            }
+            let free = data.free_copies(book_id, circulating);
+            let ready = data.ready_holds(book_id);
+            let own_hold = ready.iter().find(|hold| hold.member_id == member_id).map(|hold| hold.id);
+            let set_aside = ready.len() - usize::from(own_hold.is_some());
+            if free.len() <= set_aside {
+                return Err(if free.is_empty() { LendingError::OnLoan } else { LendingError::Reserved });
+            }
+            let copy_id = match copy_id {
+                Some(id) if !free.contains(&id) => return Err(LendingError::CopyOnLoan),
+                Some(id) => id,
+                None => free[0],
+            };
+            if data.active_loans_for_member(member_id).len() >= settings.max_loans_per_member as usize {
+                return Err(LendingError::LoanLimit);
+            }
+            if let Some(hold_id) = own_hold {
+                let hold = data.holds.iter_mut().find(|hold| hold.id == hold_id).expect("reserved hold exists");
+                hold.status = HoldStatus::Fulfilled;
+            }
+
+            let id = data.loans.iter().map(|loan| loan.id).max().unwrap_or(0) + 1;
+            let loan = Loan {
+                id,
+                book_id,
+                copy_id: Some(copy_id),
+                member_id,
+                checked_out_at: now,
+                due_at: now + Duration::days(settings.loan_days.into()),
+                renewals: 0,
+                returned_at: None,
+            };
+            data.loans.push(loan.clone());
+            Ok(loan)
+        })
+    }
+
+    // Late returns are charged their fine, and the returned copy is set
+    // aside for the first holder, if there is one
+    pub fn return_loan(&self, loan_id: u32, now: DateTime<Utc>) -> Result<Loan, LendingError> {
+        let pickup_days = self.settings().pickup_days;
+        let fine_settings = self.fine_settings();
+        self.update(|data| {
+            let loan = data.loans.iter_mut().find(|loan| loan.id == loan_id).ok_or(LendingError::LoanNotFound)?;
+            if !loan.is_active() {
+                return Err(LendingError::AlreadyReturned);
+            }
+            loan.returned_at = Some(now);
+            let loan = loan.clone();
+            let fine = fines::assess(&loan, now, &fine_settings);
+            if fine.amount_cents > 0 {
+                let note = format!("{} day(s) late", fine.days_late);
+                data.post(loan.member_id, EntryKind::Charge, fine.amount_cents, Some(loan.id), Some(note), now);
+            }
+            data.expire_holds(now, pickup_days);
+            data.reserve_for_next(loan.book_id, now, pickup_days);
+            Ok(loan)
+        })
+    }
+
+    // Extends the due date by another loan period, counted from the old due
+    // date. A title others are queued for has to come back instead.
+    pub fn renew(&self, loan_id: u32, now: DateTime<Utc>) -> Result<Loan, LendingError> {
+        let settings = self.settings();
+        self.update(|data| {
+            let book_id = data.loans.iter().find(|loan| loan.id == loan_id).map(|loan| loan.book_id);
+            let waiting = book_id.is_some_and(|book_id| {
+                data.hold_queue(book_id).iter().any(|hold| hold.status == HoldStatus::Waiting)
+            });
+            let loan = data.loans.iter_mut().find(|loan| loan.id == loan_id).ok_or(LendingError::LoanNotFound)?;
+            if !loan.is_active() {
+                return Err(LendingError::AlreadyReturned);
+            }
+            if loan.is_overdue(now) {
+                return Err(LendingError::Overdue);
+            }
+            if loan.renewals >= settings.max_renewals {
+                return Err(LendingError::RenewalLimit);
+            }
+            if waiting {
+                return Err(LendingError::HoldsWaiting);
+            }
+            loan.renewals += 1;
+            loan.due_at += Duration::days(settings.loan_days.into());
+            Ok(loan.clone())
+        })
+    }
+
+    // Active loans past their due date, most overdue first
+    pub fn overdue(&self, now: DateTime<Utc>) -> Vec<Loan> {
+        let mut overdue: Vec<Loan> = self.read(|data| data.loans.iter().filter(|loan| loan.is_overdue(now)).cloned().collect());
+        overdue.sort_by_key(|loan| loan.due_at);
+        overdue
+    }
+}
+
+/// Register a library member
+#[utoipa::path(post, path = "/members", tag = "lending", request_body = CreateMember,
+    responses(
+        (status = 201, description = "Member created", body = Member),
+        (status = 400, description = "Name missing", body = String),
+        (status = 500, description = "Member could not be saved", body = String),
+    ))]
+pub async fn add_member(State(state): State<AppState>, Json(new_member): Json<CreateMember>) -> Response {
+    let Some(name) = new_member.name.filter(|name| !name.trim().is_empty()) else {
+        return (StatusCode::BAD_REQUEST, "🚫 Name Required").into_response();
+    };
+    match state.lending.add_member(name, new_member.email, Utc::now()) {
+        Ok(member) => (StatusCode::CREATED, Json(member)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// List members
+#[utoipa::path(get, path = "/members", tag = "lending",
+    responses((status = 200, description = "Every member", body = Vec<Member>)))]
+pub async fn list_members(State(state): State<AppState>) -> Json<Vec<Member>> {
+    Json(state.lending.read(|data| data.members.clone()))
+}
+
+/// Get a member by ID
+#[utoipa::path(get, path = "/members/{id}", tag = "lending",
+    params(("id" = u32, Path, description = "Member ID")),
+    responses(
+        (status = 200, description = "The member", body = Member),
+        (status = 404, description = "Member not found", body = String),
+    ))]
+pub async fn get_member(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    match state.lending.read(|data| data.member(id).cloned()) {
+        Some(member) => Json(member).into_response(),
+        None => LendingError::MemberNotFound.into_response(),
+    }
+}
+
+/// A member's current loans
+#[utoipa::path(get, path = "/members/{id}/loans", tag = "lending",
+    params(("id" = u32, Path, description = "Member ID")),
+    responses(
+        (status = 200, description = "Loans not yet returned", body = Vec<Loan>),
+        (status = 404, description = "Member not found", body = String),
+    ))]
+pub async fn member_loans(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    let loans = state.lending.read(|data| data.member(id).map(|_| data.active_loans_for_member(id)));
+    match loans {
+        Some(loans) => Json(loans).into_response(),
+        None => LendingError::MemberNotFound.into_response(),
+    }
+}
+
+/// Check a copy of a book out to a member
+#[utoipa::path(post, path = "/loans", tag = "lending", request_body = CreateLoan,
+    responses(
+        (status = 201, description = "Loan created", body = Loan),
+        (status = 400, description = "Book or member missing", body = String),
+        (status = 404, description = "Book, member or copy not found", body = String),
+        (status = 409, description = "No free copy, the copy is out, the book is reserved for another member or the member is at the loan limit", body = String),
+        (status = 500, description = "Loan could not be saved", body = String),
+    ))]
+pub async fn checkout(State(state): State<AppState>, Json(new_loan): Json<CreateLoan>) -> Response {
+    let (Some(book_id), Some(member_id)) = (new_loan.book_id, new_loan.member_id) else {
+        return (StatusCode::BAD_REQUEST, "🚫 Book & Member Required").into_response();
+    };
+    let in_catalog = state.books.read().unwrap().iter().any(|book| book.id == book_id && !book.is_deleted());
+    if !in_catalog {
+        return LendingError::BookNotFound.into_response();
+    }
+
+    let circulating = state.copies.circulating(book_id);
+    if new_loan.copy_id.is_some_and(|id| !circulating.contains(&id)) {
+        return LendingError::CopyNotFound.into_response();
+    }
+
+    match state.lending.checkout(book_id, member_id, &circulating, new_loan.copy_id, Utc::now()) {
+        Ok(loan) => (StatusCode::CREATED, Json(loan)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Return a loaned book
+#[utoipa::path(post, path = "/loans/{id}/return", tag = "lending",
+    params(("id" = u32, Path, description = "Loan ID")),
+    responses(
+        (status = 200, description = "Loan closed; a late return is charged its fine", body = Loan),
+        (status = 404, description = "Loan not found", body = String),
+        (status = 409, description = "Loan already returned", body = String),
+        (status = 500, description = "Loan could not be saved", body = String),
+    ))]
+pub async fn return_loan(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    match state.lending.return_loan(id, Utc::now()) {
+        Ok(loan) => Json(loan).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Extend a loan's due date
+#[utoipa::path(post, path = "/loans/{id}/renew", tag = "lending",
+    params(("id" = u32, Path, description = "Loan ID")),
+    responses(
+        (status = 200, description = "Loan renewed", body = Loan),
+        (status = 404, description = "Loan not found", body = String),
+        (status = 409, description = "Returned, overdue, out of renewals, or other members are waiting", body = String),
+        (status = 500, description = "Loan could not be saved", body = String),
+    ))]
+pub async fn renew_loan(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    match state.lending.renew(id, Utc::now()) {
+        Ok(loan) => Json(loan).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Loans past their due date, most overdue first
+#[utoipa::path(get, path = "/loans/overdue", tag = "lending",
+    responses((status = 200, description = "Overdue loans", body = Vec<Loan>)))]
+pub async fn overdue_loans(State(state): State<AppState>) -> Json<Vec<Loan>> {
+    Json(state.lending.overdue(Utc::now()))
+}
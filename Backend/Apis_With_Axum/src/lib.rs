@@ -0,0 +1,400 @@
+use std::{io, sync::{Arc, Mutex, RwLock}, time::Instant};
+use tokio::sync::watch;
+use axum::{middleware, routing::{any, get}, Router};
+use utoipa::OpenApi;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::audit::AuditLog;
+use crate::book::*;
+use crate::config::{
+    Config, EmailConfig, FinesConfig, JobsConfig, LendingConfig, LoadFailure, LoadingConfig, ReviewsConfig,
+    TrashConfig, WebhookConfig,
+};
+use crate::health::{OpStatus, StorageStatus};
+use crate::copies::Copies;
+use crate::duplicates::Merges;
+use crate::emails::Emails;
+use crate::jobs::Jobs;
+use crate::lending::Lending;
+use crate::libraries::Libraries;
+use crate::metadata::Metadata;
+use crate::openapi::ApiDoc;
+use crate::reviews::Reviews;
+use crate::similar::Similarity;
+use crate::storage::Storage;
+use crate::tags::Tags;
+use crate::webhooks::Webhooks;
+use handler::*;
+
+pub mod handler;
+pub mod audit;
+pub mod book;
+pub mod changes;
+pub mod config;
+pub mod copies;
+pub mod cron;
+pub mod duplicates;
+pub mod emails;
+pub mod events;
+pub mod fines;
+pub mod format;
+pub mod health;
+pub mod holds;
+pub mod jobs;
+pub mod labels;
+pub mod layers;
+pub mod lending;
+pub mod libraries;
+pub mod metadata;
+pub mod openapi;
+pub mod reviews;
+pub mod shutdown;
+pub mod similar;
+pub mod smtp;
+pub mod stats;
+pub mod storage;
+pub mod tags;
+pub mod trash;
+pub mod webhooks;
+
+// Per-catalog settings, shared by the main catalog and every library
+#[derive(Debug, Clone, Default)]
+pub struct CatalogSettings {
+    pub webhooks: WebhookConfig,
+    pub lending: LendingConfig,
+    pub fines: FinesConfig,
+    pub email: EmailConfig,
+    pub reviews: ReviewsConfig,
+}
+
+// Shared state across routes
+#[derive(Clone)]
+pub struct AppState {
+    pub books: Arc<RwLock<Vec<Book>>>,
+    pub storage: Arc<Storage>,
+    pub audit: Arc<AuditLog>,
+    pub webhooks: Arc<Webhooks>,
+    pub lending: Arc<Lending>,
+    pub copies: Arc<Copies>,
+    pub emails: Arc<Emails>,
+    pub reviews: Arc<Reviews>,
+    pub tags: Arc<Tags>,
+    pub metadata: Arc<Metadata>,
+    pub merges: Arc<Merges>,
+    // Recommendation index, caught up from the audit log and loans on use
+    pub similar: Arc<Similarity>,
+    // Scheduled jobs; only the main catalog's run, covering every library
+    pub jobs: Arc<Jobs>,
+    // Named catalogs served under /libraries/{lib}; empty inside a library
+    pub libraries: Arc<Libraries>,
+    pub status: Arc<RwLock<StorageStatus>>,
+    // Rows skipped at load, set aside before the first save drops them from the file
+    pub rejected: Arc<Mutex<CsvLoad>>,
+    pub started_at: Instant,
+    // Flipped to true once shutdown starts so long-lived streams can end
+    pub shutdown: Arc<watch::Sender<bool>>,
+}
+
+impl AppState {
+    pub fn new(books: Vec<Book>, storage: Storage) -> Self {
+        let load = CsvLoad { books, ..CsvLoad::default() };
+        Self::with_status(load, storage, StorageStatus::new(OpStatus::success()))
+    }
+
+    // Load the catalog from storage, skipping rows that do not parse and
+    // falling back to an empty list when the file cannot be read at all.
+    // Both are remembered so /health/ready can report them.
+    pub fn load(storage: Storage) -> Self {
+        let (load, status) = read_catalog(&storage, "books");
+        Self::with_status(load, storage, status)
+    }
+
+    // Like `load`, but an unreadable data file or more bad rows than
+    // `loading` allows either stops startup or leaves the catalog read-only.
+    // A missing data file is still a fresh, empty catalog.
+    pub fn open(storage: Storage, loading: &LoadingConfig) -> Result<Self, String> {
+        let (load, mut status) = read_catalog(&storage, "books");
+        let missing = matches!(&storage, Storage::Csv(path) if !path.exists());
+        if missing {
+            status.last_load = OpStatus::success();
+        }
+        let problem = match &status.last_load.error {
+            Some(err) if !missing => Some(format!("Data file could not be read: {}", err)),
+            _ if load.errors.len() > loading.max_row_errors => Some(format!(
+                "{} rows could not be loaded, more than the {} allowed",
+                load.errors.len(),
+                loading.max_row_errors
+            )),
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            match loading.on_too_many_errors {
+                LoadFailure::Refuse => return Err(problem),
+                LoadFailure::ReadOnly => {
+                    tracing::warn!("⚠️ {}. Serving the catalog read-only.", problem);
+                    status.read_only = true;
+                }
+            }
+        }
+        Ok(Self::with_status(load, storage, status))
+    }
+
+    fn with_status(load: CsvLoad, storage: Storage, status: StorageStatus) -> Self {
+        let shutdown = Arc::new(watch::Sender::new(false));
+        let libraries = Libraries::open(&storage, shutdown.clone());
+        Self::assemble(load, storage, status, libraries, shutdown)
+    }
+
+    // A library's catalog; it stops together with the server that holds it
+    pub(crate) fn load_library(
+        storage: Storage,
+        shutdown: Arc<watch::Sender<bool>>,
+        admin_key: Arc<RwLock<Option<String>>>,
+        settings: &CatalogSettings,
+    ) -> Self {
+        let (load, status) = read_catalog(&storage, "library books");
+        let libraries = Libraries::none(shutdown.clone(), admin_key);
+        let state = Self::assemble(load, storage, status, libraries, shutdown);
+        state.apply_settings(settings);
+        state
+    }
+
+    fn assemble(
+        mut load: CsvLoad,
+        storage: Storage,
+        status: StorageStatus,
+        libraries: Libraries,
+        shutdown: Arc<watch::Sender<bool>>,
+    ) -> Self {
+        AppState {
+            books: Arc::new(RwLock::new(std::mem::take(&mut load.books))),
+            audit: Arc::new(AuditLog::open(&storage)),
+            webhooks: Arc::new(Webhooks::open(&storage, WebhookConfig::default())),
+            lending: Arc::new(Lending::open(&storage)),
+            copies: Arc::new(Copies::open(&storage)),
+            emails: Arc::new(Emails::open(&storage)),
+            reviews: Arc::new(Reviews::open(&storage)),
+            tags: Arc::new(Tags::open(&storage)),
+            metadata: Arc::new(Metadata::open(&storage)),
+            merges: Arc::new(Merges::open(&storage)),
+            similar: Arc::new(Similarity::default()),
+            jobs: Arc::new(Jobs::open(&storage)),
+            libraries: Arc::new(libraries),
+            storage: Arc::new(storage),
+            status: Arc::new(RwLock::new(status)),
+            rejected: Arc::new(Mutex::new(load)),
+            started_at: Instant::now(),
+            shutdown,
+        }
+    }
+
+    fn apply_settings(&self, settings: &CatalogSettings) {
+        self.webhooks.configure(settings.webhooks.clone());
+        self.lending.configure(settings.lending.clone());
+        self.lending.configure_fines(settings.fines.clone());
+        self.emails.configure(settings.email.clone());
+        self.reviews.configure(settings.reviews.clone());
+    }
+
+    // Use non-default webhook retry settings, for every library too
+    pub fn with_webhook_settings(self, settings: WebhookConfig) -> Self {
+        self.webhooks.configure(settings.clone());
+        self.libraries.update_settings(|shared| shared.webhooks = settings);
+        self
+    }
+
+    // Fine rates, grace and caps, for every library too
+    pub fn with_fine_settings(self, settings: FinesConfig) -> Self {
+        self.lending.configure_fines(settings.clone());
+        self.libraries.update_settings(|shared| shared.fines = settings);
+        self
+    }
+
+    // Loan periods and limits, for every library too
+    pub fn with_lending_settings(self, settings: LendingConfig) -> Self {
+        self.lending.configure(settings.clone());
+        self.libraries.update_settings(|shared| shared.lending = settings);
+        self
+    }
+
+    // SMTP server and reminder templates, for every library too
+    pub fn with_email_settings(self, settings: EmailConfig) -> Self {
+        self.emails.configure(settings.clone());
+        self.libraries.update_settings(|shared| shared.email = settings);
+        self
+    }
+
+    // Review moderation, for every library too
+    pub fn with_review_settings(self, settings: ReviewsConfig) -> Self {
+        self.reviews.configure(settings.clone());
+        self.libraries.update_settings(|shared| shared.reviews = settings);
+        self
+    }
+
+    // Job schedules, backup retention and trash retention
+    pub fn with_job_settings(self, jobs: JobsConfig, trash: TrashConfig) -> Self {
+        self.jobs.configure(jobs, trash);
+        self
+    }
+
+    // The X-Admin-Key for library management, job runs, review moderation,
+    // book merges, fine payments and waivers, and webhook subscriptions (also
+    // accepted for any library); without one those stay closed
+    pub fn with_library_admin_key(self, key: Option<String>) -> Self {
+        self.libraries.set_admin_key(key);
+        self
+    }
+
+    // Deliver audit log changes to webhook subscribers until shutdown
+    pub fn spawn_webhook_dispatcher(&self) {
+        self.webhooks.clone().spawn_dispatcher(self.audit.clone(), self.shutting_down());
+        self.libraries.start_dispatchers();
+    }
+
+    // Resolves once shutdown has started
+    pub fn shutting_down(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut receiver = self.shutdown.subscribe();
+        async move {
+            let _ = receiver.wait_for(|stopping| *stopping).await;
+        }
+    }
+
+    // Save the catalog and remember whether it worked
+    pub fn persist(&self, books: &[Book]) -> Result<(), csv::Error> {
+        if self.status.read().unwrap().read_only {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "catalog is read-only").into());
+        }
+        let result = self.set_aside_rejected().and_then(|()| self.storage.save(books));
+        if let Err(err) = &result {
+            tracing::error!("💥 Failed to save books: {}", err);
+        }
+        self.status.write().unwrap().last_save = Some(OpStatus::from_result(&result));
+        result
+    }
+
+    // Write the current catalog to storage, waiting for any in-flight writer.
+    // If the data file never loaded and nothing was saved since, the empty
+    // fallback list must not overwrite it.
+    pub fn flush(&self) -> Result<(), csv::Error> {
+        let libraries = self.libraries.flush();
+        let books_reader = self.books.read().unwrap();
+        let status = self.status.read().unwrap().clone();
+        if status.read_only {
+            tracing::warn!("⚠️ Catalog is read-only, skipping flush");
+            return libraries;
+        }
+        if !status.last_load.ok && status.last_save.is_none() {
+            tracing::warn!("⚠️ Catalog was never loaded, skipping flush");
+            return libraries;
+        }
+        self.persist(&books_reader).and(libraries)
+    }
+
+    // Saving rewrites the data file from the loaded books, so skipped rows
+    // are copied out first. Only done once; they are gone from the file after.
+    fn set_aside_rejected(&self) -> Result<(), csv::Error> {
+        let mut rejected = self.rejected.lock().unwrap();
+        if let Some(path) = self.storage.set_aside(&rejected)? {
+            tracing::warn!("⚠️ Moved {} unreadable rows to {}", rejected.rejected.len(), path.display());
+            *rejected = CsvLoad::default();
+        }
+        Ok(())
+    }
+}
+
+// Lenient load shared by the main catalog and libraries: each bad row is
+// logged and skipped, and an unreadable file leaves the catalog empty
+fn read_catalog(storage: &Storage, what: &str) -> (CsvLoad, StorageStatus) {
+    let result = storage.load_leniently();
+    let mut status = StorageStatus::new(OpStatus::from_result(&result));
+    let load = result.unwrap_or_else(|err| {
+        tracing::warn!("⚠️ Failed to load {} ({}). Starting with empty list.", what, err);
+        CsvLoad::default()
+    });
+    for error in &load.errors {
+        tracing::warn!("⚠️ Skipped a row of {}: {}", what, error);
+    }
+    status.row_errors = load.errors.clone();
+    (load, status)
+}
+
+// Catalog routes, served at the root for the main catalog and under
+// /libraries/{lib} for every library
+pub(crate) fn api_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(list_books))
+        .routes(routes!(add_book))
+        .routes(routes!(search_book))
+        .routes(routes!(duplicates::list_duplicates))
+        .routes(routes!(duplicates::merge_books))
+        .routes(routes!(get_book, update_book, delete_book))
+        .routes(routes!(tags::book_tags, tags::set_book_tags))
+        .routes(routes!(metadata::book_metadata, metadata::set_book_metadata))
+        .routes(routes!(similar::similar_books))
+        .routes(routes!(stats::get_stats))
+        .routes(routes!(audit::book_history))
+        .routes(routes!(audit::revert_book))
+        .routes(routes!(trash::list_trash))
+        .routes(routes!(trash::restore_book))
+        .routes(routes!(trash::purge_book))
+        .routes(routes!(changes::list_changes))
+        .routes(routes!(events::sse_events))
+        .routes(routes!(events::ws_events))
+        .routes(routes!(webhooks::create_subscription, webhooks::list_subscriptions))
+        .routes(routes!(webhooks::delete_subscription))
+        .routes(routes!(webhooks::list_deliveries))
+        .routes(routes!(webhooks::list_dead_letters))
+        .routes(routes!(webhooks::retry_dead_letter))
+        .routes(routes!(lending::add_member, lending::list_members))
+        .routes(routes!(lending::get_member))
+        .routes(routes!(lending::member_loans))
+        .routes(routes!(lending::checkout))
+        .routes(routes!(lending::return_loan))
+        .routes(routes!(lending::renew_loan))
+        .routes(routes!(lending::overdue_loans))
+        .routes(routes!(fines::member_ledger))
+        .routes(routes!(fines::member_balance))
+        .routes(routes!(fines::add_payment))
+        .routes(routes!(fines::add_waiver))
+        .routes(routes!(fines::loan_fine))
+        .routes(routes!(emails::set_email_preferences))
+        .routes(routes!(emails::list_emails))
+        .routes(routes!(reviews::add_review, reviews::book_reviews))
+        .routes(routes!(reviews::get_review, reviews::update_review, reviews::delete_review))
+        .routes(routes!(reviews::flag_review))
+        .routes(routes!(reviews::moderate_review))
+        .routes(routes!(holds::place_hold))
+        .routes(routes!(holds::get_hold, holds::cancel_hold))
+        .routes(routes!(holds::set_hold_priority))
+        .routes(routes!(holds::book_holds))
+        .routes(routes!(holds::member_holds))
+        .routes(routes!(copies::add_copy, copies::book_copies))
+        .routes(routes!(copies::get_copy, copies::update_copy, copies::delete_copy))
+        .routes(routes!(copies::print_labels))
+        .routes(routes!(health::live))
+        .routes(routes!(health::ready))
+}
+
+// Route Setup
+pub fn app(state: AppState, config: &Config) -> Router {
+    // Documented routes: the OpenAPI spec is generated from these definitions
+    let (router, api) = api_routes()
+        .routes(routes!(libraries::create_library, libraries::list_libraries))
+        .routes(routes!(libraries::delete_library))
+        .routes(routes!(libraries::rotate_library_key))
+        .routes(routes!(jobs::list_jobs))
+        .routes(routes!(jobs::list_runs))
+        .routes(routes!(jobs::run_job))
+        .split_for_parts();
+
+    // Libraries load on their own and are never read-only, so the guard
+    // goes on before their forwarding route
+    let router = router
+        .layer(middleware::from_fn_with_state(state.clone(), health::read_only_guard))
+        .route("/libraries/{lib}/{*rest}", any(libraries::forward))
+        .route("/ping", get(|| async {"📡 API is alive"}))
+        .merge(openapi::swagger_ui(api))
+        .with_state(state); // Sharing state with handlers
+
+    layers::http_layers(router, config)
+}
@@ -0,0 +1,483 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{Extensions, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Json, Response},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use hyper::upgrade::OnUpgrade;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tokio::sync::watch;
+use tower::ServiceExt;
+use utoipa::ToSchema;
+
+use crate::{storage::{atomic_write, Storage}, AppState, CatalogSettings};
+
+// What the registry file remembers about a library; keys are stored hashed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LibraryRecord {
+    name: String,
+    key_sha256: String,
+    created_at: DateTime<Utc>,
+}
+
+// A library is a complete catalog of its own: books, history, trash,
+// events and webhooks, served by its own router under /libraries/{lib}
+pub struct Library {
+    record: LibraryRecord,
+    pub state: AppState,
+    router: Router,
+    // Flipped to true when the library is deleted so its dispatcher stops
+    removed: Arc<watch::Sender<bool>>,
+}
+
+impl Library {
+    fn open(record: LibraryRecord, state: AppState) -> Self {
+        let (router, _) = crate::api_routes().split_for_parts();
+        let router = router.with_state(state.clone());
+        Library { record, state, router, removed: Arc::new(watch::Sender::new(false)) }
+    }
+
+    // Deliver the library's webhooks until shutdown or until it is deleted
+    fn spawn_dispatcher(&self) {
+        let shutting_down = self.state.shutting_down();
+        let mut removed = self.removed.subscribe();
+        let stop = async move {
+            tokio::select! {
+                _ = shutting_down => {}
+                _ = removed.wait_for(|removed| *removed) => {}
+            }
+        };
+        self.state.webhooks.clone().spawn_dispatcher(self.state.audit.clone(), stop);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.record.name
+    }
+
+    fn summary(&self) -> LibrarySummary {
+        let books = self.state.books.read().unwrap();
+        LibrarySummary {
+            name: self.record.name.clone(),
+            created_at: self.record.created_at,
+            catalog_size: books.iter().filter(|book| !book.is_deleted()).count(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LibrarySummary {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub catalog_size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LibraryKey {
+    pub name: String,
+    /// Send as X-Library-Key; it is only shown once
+    pub key: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateLibrary {
+    pub name: Option<String>,
+}
+
+pub enum LibraryError {
+    InvalidName,
+    Exists,
+    Storage(String),
+}
+
+// Registry of named libraries, persisted next to the main catalog
+pub struct Libraries {
+    path: Option<PathBuf>,
+    root: Storage,
+    libraries: RwLock<BTreeMap<String, Arc<Library>>>,
+    // Shared with every library's state, which checks it for moderation
+    admin_key: Arc<RwLock<Option<String>>>,
+    settings: RwLock<CatalogSettings>,
+    // Set once webhook dispatchers run, so new libraries get one too
+    dispatching: AtomicBool,
+    shutdown: Arc<watch::Sender<bool>>,
+}
+
+impl Libraries {
+    // Library states never have libraries of their own, but know the admin key
+    pub(crate) fn none(shutdown: Arc<watch::Sender<bool>>, admin_key: Arc<RwLock<Option<String>>>) -> Self {
+        Libraries {
+            path: None,
+            root: Storage::Memory,
+            libraries: RwLock::new(BTreeMap::new()),
+            admin_key,
+            settings: RwLock::new(CatalogSettings::default()),
+            dispatching: AtomicBool::new(false),
+            shutdown,
+        }
+    }
+
+    pub(crate) fn open(storage: &Storage, shutdown: Arc<watch::Sender<bool>>) -> Self {
+        let path = storage.sidecar("libraries.json");
+        let records: Vec<LibraryRecord> = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load libraries {}: {}", path.display(), err);
+                    Vec::new()
+                }),
+            _ => Vec::new(),
+        };
+
+        let admin_key = Arc::new(RwLock::new(None));
+        let libraries = Libraries { path, root: storage.clone(), ..Libraries::none(shutdown, admin_key) };
+        {
+            let mut map = libraries.libraries.write().unwrap();
+            for record in records {
+                let state = libraries.library_state(&record.name);
+                map.insert(record.name.clone(), Arc::new(Library::open(record, state)));
+            }
+        }
+        libraries
+    }
+
+    fn library_state(&self, name: &str) -> AppState {
+        let settings = self.settings.read().unwrap();
+        AppState::load_library(self.root.for_library(name), self.shutdown.clone(), self.admin_key.clone(), &settings)
+    }
+
+    fn save(&self, libraries: &BTreeMap<String, Arc<Library>>) -> Result<(), String> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let records: Vec<&LibraryRecord> = libraries.values().map(|library| &library.record).collect();
+        let text = serde_json::to_string_pretty(&records).map_err(|err| err.to_string())?;
+        atomic_write(path, text).map_err(|err| err.to_string())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Library>> {
+        self.libraries.read().unwrap().get(name).cloned()
+    }
+
+    pub fn all(&self) -> Vec<Arc<Library>> {
+        self.libraries.read().unwrap().values().cloned().collect()
+    }
+
+    pub(crate) fn set_admin_key(&self, key: Option<String>) {
+        *self.admin_key.write().unwrap() = key.filter(|key| !key.is_empty());
+    }
+
+    pub(crate) fn update_settings(&self, update: impl FnOnce(&mut CatalogSettings)) {
+        let mut settings = self.settings.write().unwrap();
+        update(&mut settings);
+        for library in self.all() {
+            library.state.apply_settings(&settings);
+        }
+    }
+
+    pub(crate) fn start_dispatchers(&self) {
+        self.dispatching.store(true, Ordering::SeqCst);
+        for library in self.all() {
+            library.spawn_dispatcher();
+        }
+    }
+
+    // Returns the new library's access key
+    pub fn create(&self, name: &str) -> Result<LibraryKey, LibraryError> {
+        if !valid_name(name) {
+            return Err(LibraryError::InvalidName);
+        }
+        let mut libraries = self.libraries.write().unwrap();
+        if libraries.contains_key(name) {
+            return Err(LibraryError::Exists);
+        }
+
+        let storage = self.root.for_library(name);
+        if let Storage::Csv(path) = &storage {
+            let dir = path.parent().expect("library files live in a directory");
+            fs::create_dir_all(dir).map_err(|err| LibraryError::Storage(err.to_string()))?;
+        }
+        storage.save(&[]).map_err(|err| LibraryError::Storage(err.to_string()))?;
+
+        let key = new_key();
+        let record = LibraryRecord { name: name.to_string(), key_sha256: hash_key(&key), created_at: Utc::now() };
+        let library = Arc::new(Library::open(record, self.library_state(name)));
+        libraries.insert(name.to_string(), library.clone());
+        if let Err(err) = self.save(&libraries) {
+            libraries.remove(name);
+            return Err(LibraryError::Storage(err));
+        }
+
+        if self.dispatching.load(Ordering::SeqCst) {
+            library.spawn_dispatcher();
+        }
+        tracing::info!("📚 Library '{}' created", name);
+        Ok(LibraryKey { name: name.to_string(), key })
+    }
+
+    // Removes the library and its files
+    pub fn remove(&self, name: &str) -> Result<bool, LibraryError> {
+        let mut libraries = self.libraries.write().unwrap();
+        let Some(library) = libraries.remove(name) else { return Ok(false) };
+        if let Err(err) = self.save(&libraries) {
+            libraries.insert(name.to_string(), library);
+            return Err(LibraryError::Storage(err));
+        }
+        library.removed.send_replace(true);
+
+        if let Storage::Csv(path) = self.root.for_library(name) {
+            if let Some(dir) = path.parent() {
+                if let Err(err) = fs::remove_dir_all(dir) {
+                    tracing::warn!("⚠️ Failed to remove files of library '{}': {}", name, err);
+                }
+            }
+        }
+        tracing::info!("🗑️ Library '{}' removed", name);
+        Ok(true)
+    }
+
+    pub fn rotate_key(&self, name: &str) -> Result<Option<LibraryKey>, LibraryError> {
+        let mut libraries = self.libraries.write().unwrap();
+        let Some(library) = libraries.get(name).cloned() else { return Ok(None) };
+
+        let key = new_key();
+        let record = LibraryRecord { key_sha256: hash_key(&key), ..library.record.clone() };
+        let rotated = Library {
+            record,
+            state: library.state.clone(),
+            router: library.router.clone(),
+            removed: library.removed.clone(),
+        };
+        libraries.insert(name.to_string(), Arc::new(rotated));
+        if let Err(err) = self.save(&libraries) {
+            libraries.insert(name.to_string(), library);
+            return Err(LibraryError::Storage(err));
+        }
+        Ok(Some(LibraryKey { name: name.to_string(), key }))
+    }
+
+    // Management needs the admin key; without one configured it stays closed
+    pub(crate) fn is_admin(&self, headers: &HeaderMap) -> bool {
+        match (self.admin_key.read().unwrap().as_deref(), header(headers, "x-admin-key")) {
+            (Some(admin_key), Some(given)) => same_hash(&hash_key(given), &hash_key(admin_key)),
+            _ => false,
+        }
+    }
+
+    // The library's own key, or the admin key
+    fn may_access(&self, library: &Library, headers: &HeaderMap) -> bool {
+        let key_matches = header(headers, "x-library-key")
+            .is_some_and(|key| same_hash(&hash_key(key), &library.record.key_sha256));
+        self.is_admin(headers) || key_matches
+    }
+
+    // Flush every library, reporting the first failure
+    pub(crate) fn flush(&self) -> Result<(), csv::Error> {
+        let mut result = Ok(());
+        for library in self.all() {
+            if let Err(err) = library.state.flush() {
+                tracing::error!("💥 Failed to flush library '{}': {}", library.record.name, err);
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+// Lowercase letters, digits, '-' and '_', up to 64 characters; safe as a directory name
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with(['-', '_'])
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn new_key() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// Hex digests are always the same length, so this takes the same time
+// however much of the key is right
+fn same_hash(given: &str, expected: &str) -> bool {
+    given.as_bytes().ct_eq(expected.as_bytes()).into()
+}
+
+fn library_error(err: LibraryError) -> Response {
+    match err {
+        LibraryError::InvalidName => (
+            StatusCode::BAD_REQUEST,
+            "🚫 Library Name Must Be 1-64 Lowercase Letters, Digits, '-' Or '_'",
+        )
+            .into_response(),
+        LibraryError::Exists => (StatusCode::CONFLICT, "🚫 Library Already Exists").into_response(),
+        LibraryError::Storage(err) => {
+            tracing::error!("💥 Library storage failed: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Save Library").into_response()
+        }
+    }
+}
+
+fn forbidden() -> Response {
+    (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response()
+}
+
+fn not_found() -> Response {
+    (StatusCode::NOT_FOUND, "❌ Library Not Found").into_response()
+}
+
+/// Create a library; the response holds its access key
+#[utoipa::path(post, path = "/libraries", tag = "libraries", request_body = CreateLibrary,
+    params(("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured")),
+    responses(
+        (status = 201, description = "Library created", body = LibraryKey),
+        (status = 400, description = "Missing or invalid name", body = String),
+        (status = 401, description = "Admin key required", body = String),
+        (status = 409, description = "Name already taken", body = String),
+        (status = 500, description = "Library could not be saved", body = String),
+    ))]
+pub async fn create_library(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(new_library): Json<CreateLibrary>,
+) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return forbidden();
+    }
+    let Some(name) = new_library.name else {
+        return library_error(LibraryError::InvalidName);
+    };
+    match state.libraries.create(&name) {
+        Ok(key) => (StatusCode::CREATED, Json(key)).into_response(),
+        Err(err) => library_error(err),
+    }
+}
+
+/// List libraries
+#[utoipa::path(get, path = "/libraries", tag = "libraries",
+    params(("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured")),
+    responses(
+        (status = 200, description = "Every library", body = Vec<LibrarySummary>),
+        (status = 401, description = "Admin key required", body = String),
+    ))]
+pub async fn list_libraries(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return forbidden();
+    }
+    let summaries: Vec<LibrarySummary> = state.libraries.all().iter().map(|library| library.summary()).collect();
+    Json(summaries).into_response()
+}
+
+/// Delete a library and all of its data
+#[utoipa::path(delete, path = "/libraries/{lib}", tag = "libraries",
+    params(
+        ("lib" = String, Path, description = "Library name"),
+        ("X-Library-Key" = Option<String>, Header, description = "The library's access key"),
+    ),
+    responses(
+        (status = 200, description = "Library deleted", body = String),
+        (status = 401, description = "Missing or invalid key", body = String),
+        (status = 404, description = "Library not found", body = String),
+        (status = 500, description = "Library could not be removed", body = String),
+    ))]
+pub async fn delete_library(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(library) = state.libraries.get(&name) else { return not_found() };
+    if !state.libraries.may_access(&library, &headers) {
+        return forbidden();
+    }
+    match state.libraries.remove(&name) {
+        Ok(true) => (StatusCode::OK, "🗑️ Library Deleted").into_response(),
+        Ok(false) => not_found(),
+        Err(err) => library_error(err),
+    }
+}
+
+/// Replace a library's access key
+#[utoipa::path(post, path = "/libraries/{lib}/rotate-key", tag = "libraries",
+    params(
+        ("lib" = String, Path, description = "Library name"),
+        ("X-Library-Key" = Option<String>, Header, description = "The library's current access key"),
+    ),
+    responses(
+        (status = 200, description = "New key; the old one stops working", body = LibraryKey),
+        (status = 401, description = "Missing or invalid key", body = String),
+        (status = 404, description = "Library not found", body = String),
+        (status = 500, description = "Library could not be saved", body = String),
+    ))]
+pub async fn rotate_library_key(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(library) = state.libraries.get(&name) else { return not_found() };
+    if !state.libraries.may_access(&library, &headers) {
+        return forbidden();
+    }
+    match state.libraries.rotate_key(&name) {
+        Ok(Some(key)) => Json(key).into_response(),
+        Ok(None) => not_found(),
+        Err(err) => library_error(err),
+    }
+}
+
+// Everything under /libraries/{lib}/ is served by that library's own router,
+// so /libraries/{lib}/books/1 behaves exactly like /books/1 does for the
+// main catalog.
+pub async fn forward(
+    Path((name, _rest)): Path<(String, String)>,
+    State(state): State<AppState>,
+    request: Request,
+) -> Response {
+    let Some(library) = state.libraries.get(&name) else { return not_found() };
+    if !state.libraries.may_access(&library, request.headers()) {
+        return forbidden();
+    }
+
+    // `rest` is already percent-decoded; the library router decodes again,
+    // so pass on the path exactly as the client sent it
+    let raw_rest = request.uri().path().splitn(4, '/').nth(3).unwrap_or_default();
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("/{}?{}", raw_rest, query),
+        None => format!("/{}", raw_rest),
+    };
+    let uri = match path_and_query.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return (StatusCode::BAD_REQUEST, "🚫 Invalid Path").into_response(),
+    };
+
+    // Start from clean extensions so the library router does not see {lib}
+    // and {rest} as extra path parameters; keep the upgrade for WebSockets.
+    let (mut parts, body) = request.into_parts();
+    let upgrade = parts.extensions.remove::<OnUpgrade>();
+    parts.extensions = Extensions::new();
+    if let Some(upgrade) = upgrade {
+        parts.extensions.insert(upgrade);
+    }
+    parts.uri = uri;
+
+    library.router.clone().oneshot(Request::from_parts(parts, body)).await.into_response()
+}
@@ -1,40 +1,88 @@
-use std::sync::{Arc, RwLock};
-use axum::{extract::State, routing::{get, post}, Router};
+use std::{path::Path, process::ExitCode, time::Duration};
+use clap::Parser;
 
-use crate::book::*;
-use handler::*;
+use apis_with_axum::{
+    app,
+    book::load_books_leniently,
+    config::{Cli, Command, Config},
+    shutdown::{serve_with_shutdown, shutdown_signal},
+    storage::Storage,
+    AppState,
+};
 
-mod handler;
-mod book;
+#[tokio::main]
+async fn main() -> ExitCode {
+    // Layered configuration: defaults, TOML file, environment, CLI flags
+    let cli = Cli::parse();
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("❌ {}", err);
+            return ExitCode::from(2);
+        }
+    };
 
-type AppState = State<Arc<RwLock<Vec<Book>>>>;
+    if cli.print_config {
+        print!("{}", config.to_toml());
+        return ExitCode::SUCCESS;
+    }
 
-#[tokio::main]
-async fn main() {
-    // Load books from CSV at server startup
-    let books = load_books_from_csv().unwrap_or_else(|_| {
-        eprintln!("⚠️ Failed to load books. Starting with empty list.");
-        Vec::new()
-    });
+    if let Some(Command::Validate { path }) = &cli.command {
+        return validate(path.as_deref().unwrap_or(&config.data_path));
+    }
+
+    let level: tracing::Level = config.log_level.parse().unwrap();
+    tracing_subscriber::fmt().with_max_level(level).init();
 
+    // Load books from storage at server startup
     // Shared state across routes using Arc + RwLock
-    let shared_books = Arc::new(RwLock::new(books));
-
-    // Route Setup
-    let app = Router::new()
-        .route("/books", get(list_books))
-        .route("/books/new", post(add_book))
-        .route("/books/search", get(search_book))
-        .route("/books/{id}", get(get_book)
-                            .put(update_book)
-                            .delete(delete_book))
-        .route("/ping", get(|| async {"📡 API is alive"}))
-        .with_state(shared_books); // Sharing state with handlers
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
+    let state = match AppState::open(Storage::from_config(&config), &config.loading) {
+        Ok(state) => state,
+        Err(err) => {
+            tracing::error!("❌ {}. Fix the data file (see the validate command) or raise loading.max_row_errors.", err);
+            return ExitCode::from(2);
+        }
+    };
+    let state = state
+        .with_webhook_settings(config.webhooks.clone())
+        .with_lending_settings(config.lending.clone())
+        .with_fine_settings(config.fines.clone())
+        .with_email_settings(config.email.clone())
+        .with_review_settings(config.reviews.clone())
+        .with_job_settings(config.jobs.clone(), config.trash.clone())
+        .with_library_admin_key(config.libraries.admin_key.clone());
+    state.spawn_webhook_dispatcher();
+    state.spawn_scheduler();
+    let app = app(state.clone(), &config);
+
+    let listener = tokio::net::TcpListener::bind(&config.bind)
     .await
-    .expect("❌ Failed to bind to port 3000");
+    .unwrap_or_else(|err| panic!("❌ Failed to bind to {}: {}", config.bind, err));
+
+    tracing::info!("🚀 Server running on http://{}", config.bind);
+    let drain_timeout = Duration::from_secs(config.limits.shutdown_timeout_secs);
+    let outcome = serve_with_shutdown(listener, app, state, shutdown_signal(), drain_timeout).await;
+    ExitCode::from(outcome.exit_code())
+}
 
-    println!("🚀 Server running on http://localhost:3000");
-    axum::serve(listener, app).await.unwrap();
-}
\ No newline at end of file
+// Report every row the server would skip; exits 1 if there are any, 2 if
+// the file cannot be read at all
+fn validate(path: &Path) -> ExitCode {
+    let load = match load_books_leniently(path) {
+        Ok(load) => load,
+        Err(err) => {
+            eprintln!("❌ {}: {}", path.display(), err);
+            return ExitCode::from(2);
+        }
+    };
+    for error in &load.errors {
+        println!("{}: {}", path.display(), error);
+    }
+    if load.errors.is_empty() {
+        println!("✅ {}: {} books, no errors", path.display(), load.books.len());
+        ExitCode::SUCCESS
+    } else {
+        println!("❌ {}: {} books, {} rows with errors", path.display(), load.books.len(), load.errors.len());
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,162 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::RwLock};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{copies::find_book, storage::{atomic_write, Storage}, AppState};
+
+// Bibliographic details the CSV catalog has no columns for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub struct BookMetadata {
+    /// ISBN-10 or ISBN-13, stored without hyphens or spaces
+    pub isbn: Option<String>,
+    /// Year of first publication
+    pub year: Option<i32>,
+}
+
+impl BookMetadata {
+    fn is_empty(&self) -> bool {
+        self.isbn.is_none() && self.year.is_none()
+    }
+}
+
+// Strips hyphens and spaces and checks the check digit; None when invalid
+pub fn normalize_isbn(isbn: &str) -> Option<String> {
+    let isbn: String = isbn.chars().filter(|c| *c != '-' && !c.is_whitespace()).collect::<String>().to_uppercase();
+    let digits: Vec<u32> = isbn
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'X' if isbn.len() == 10 && i == 9 => Some(10),
+            c => c.to_digit(10),
+        })
+        .collect::<Option<_>>()?;
+    let valid = match digits.len() {
+        10 => digits.iter().enumerate().map(|(i, d)| (10 - i as u32) * d).sum::<u32>() % 11 == 0,
+        13 => digits.iter().enumerate().map(|(i, d)| if i % 2 == 0 { *d } else { 3 * d }).sum::<u32>() % 10 == 0,
+        _ => false,
+    };
+    valid.then_some(isbn)
+}
+
+// ISBN and publication year per book, persisted next to the catalog as one JSON document
+pub struct Metadata {
+    path: Option<PathBuf>,
+    metadata: RwLock<BTreeMap<u32, BookMetadata>>,
+}
+
+impl Metadata {
+    pub fn open(storage: &Storage) -> Self {
+        let path = storage.sidecar("metadata.json");
+        let metadata = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load metadata {}: {}", path.display(), err);
+                    BTreeMap::new()
+                }),
+            _ => BTreeMap::new(),
+        };
+        Metadata { path, metadata: RwLock::new(metadata) }
+    }
+
+    pub fn read<T>(&self, view: impl FnOnce(&BTreeMap<u32, BookMetadata>) -> T) -> T {
+        view(&self.metadata.read().unwrap())
+    }
+
+    pub fn for_book(&self, book_id: u32) -> BookMetadata {
+        self.read(|metadata| metadata.get(&book_id).cloned().unwrap_or_default())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<u32, BookMetadata>)) -> Result<(), String> {
+        let mut metadata = self.metadata.write().unwrap();
+        let mut draft = metadata.clone();
+        change(&mut draft);
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&draft).map_err(|err| err.to_string())?;
+            atomic_write(path, text).map_err(|err| err.to_string())?;
+        }
+        *metadata = draft;
+        Ok(())
+    }
+
+    pub fn set(&self, book_id: u32, new_metadata: BookMetadata) -> Result<BookMetadata, String> {
+        self.update(|metadata| {
+            if new_metadata.is_empty() {
+                metadata.remove(&book_id);
+            } else {
+                metadata.insert(book_id, new_metadata.clone());
+            }
+        })?;
+        Ok(new_metadata)
+    }
+
+    // A book merged into another fills in what the surviving book lacks
+    pub fn merge(&self, from: u32, to: u32) -> Result<(), String> {
+        self.update(|metadata| {
+            if let Some(moved) = metadata.remove(&from) {
+                let kept = metadata.entry(to).or_default();
+                kept.isbn = kept.isbn.take().or(moved.isbn);
+                kept.year = kept.year.or(moved.year);
+            }
+        })
+    }
+}
+
+/// A book's ISBN and publication year
+#[utoipa::path(get, path = "/books/{id}/metadata", tag = "books",
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "The book's metadata; unknown fields are null", body = BookMetadata),
+        (status = 404, description = "Book not found", body = String),
+    ))]
+pub async fn book_metadata(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    if find_book(&state, id).is_none() {
+        return (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response();
+    }
+    Json(state.metadata.for_book(id)).into_response()
+}
+
+/// Replace a book's ISBN and publication year; a missing field is cleared
+#[utoipa::path(put, path = "/books/{id}/metadata", tag = "books", request_body = BookMetadata,
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "The book's metadata, ISBN normalized", body = BookMetadata),
+        (status = 400, description = "Invalid ISBN or year", body = String),
+        (status = 404, description = "Book not found", body = String),
+        (status = 500, description = "Metadata could not be saved", body = String),
+    ))]
+pub async fn set_book_metadata(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Json(request): Json<BookMetadata>,
+) -> Response {
+    let isbn = match request.isbn.as_deref().map(str::trim).filter(|isbn| !isbn.is_empty()) {
+        Some(isbn) => match normalize_isbn(isbn) {
+            Some(isbn) => Some(isbn),
+            None => return (StatusCode::BAD_REQUEST, "🚫 Invalid ISBN").into_response(),
+        },
+        None => None,
+    };
+    if request.year.is_some_and(|year| year < 1 || year > Utc::now().year() + 1) {
+        return (StatusCode::BAD_REQUEST, "🚫 Invalid Year").into_response();
+    }
+    if find_book(&state, id).is_none() {
+        return (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response();
+    }
+
+    match state.metadata.set(id, BookMetadata { isbn, year: request.year }) {
+        Ok(metadata) => Json(metadata).into_response(),
+        Err(err) => {
+            tracing::error!("💥 Failed to save metadata: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Save Metadata").into_response()
+        }
+    }
+}
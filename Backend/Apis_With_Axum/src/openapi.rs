@@ -0,0 +1,32 @@
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+// Top-level document; paths are collected from the routes in `app`
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "📚 Book API", description = "Book catalog backed by a CSV file"),
+    tags(
+        (name = "books", description = "Catalog management"),
+        (name = "history", description = "Audit trail and revert"),
+        (name = "trash", description = "Soft-deleted books, restore and purge"),
+        (name = "events", description = "Live change notifications"),
+        (name = "sync", description = "Incremental changes feed for offline clients"),
+        (name = "webhooks", description = "Signed change notifications to external URLs"),
+        (name = "lending", description = "Members, loans, due dates, returns and holds"),
+        (name = "fines", description = "Overdue fines, payments, waivers and balances"),
+        (name = "reviews", description = "Member ratings and reviews, flags and moderation"),
+        (name = "emails", description = "Due and overdue reminder emails, opt-outs and the sent log"),
+        (name = "stats", description = "Catalog statistics and data-quality reports"),
+        (name = "copies", description = "Physical copies, availability and barcode labels"),
+        (name = "jobs", description = "Scheduled backups, trash purges and reminders"),
+        (name = "libraries", description = "Named catalogs; every catalog route is also served under /libraries/{lib} with an X-Library-Key"),
+        (name = "health", description = "Liveness and readiness probes"),
+    )
+)]
+pub struct ApiDoc;
+
+// Swagger UI at /docs for the spec served at /openapi.json. Its scripts and
+// styles are built into the binary, so the page needs no outside CDN.
+pub fn swagger_ui(api: utoipa::openapi::OpenApi) -> SwaggerUi {
+    SwaggerUi::new("/docs").url("/openapi.json", api)
+}
@@ -0,0 +1,537 @@
+use std::{collections::HashMap, fs, path::PathBuf, sync::RwLock};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    book::Book,
+    config::ReviewsConfig,
+    copies::find_book,
+    storage::{atomic_write, Storage},
+    AppState,
+};
+
+// A member's report that a review breaks the rules
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReviewFlag {
+    /// Flags from before members were required have none
+    pub member_id: Option<u32>,
+    pub reason: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Review {
+    pub id: u32,
+    pub book_id: u32,
+    pub member_id: u32,
+    /// 1 to 5 stars
+    pub rating: u8,
+    pub text: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub flags: Vec<ReviewFlag>,
+    /// Hidden reviews are left out of listings and ratings
+    pub hidden: bool,
+}
+
+// Average and count over a book's visible reviews
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Rating {
+    pub average_rating: f64,
+    pub review_count: u32,
+}
+
+// A book as JSON, with its rating once it has visible reviews. Both are
+// flattened, so the book's own fields stay where plain-Book clients expect them.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RatedBook {
+    #[serde(flatten)]
+    pub book: Book,
+    #[serde(flatten)]
+    pub rating: Option<Rating>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateReview {
+    pub member_id: Option<u32>,
+    pub rating: Option<u32>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct UpdateReview {
+    /// The reviewer. Members have no credentials, so this only guards
+    /// against editing someone else's review by mistake.
+    pub member_id: Option<u32>,
+    pub rating: Option<u32>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateFlag {
+    /// The member reporting the review; each member flags a review once
+    pub member_id: Option<u32>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct Moderation {
+    pub hidden: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ReviewParams {
+    /// Include hidden reviews; needs X-Admin-Key when one is configured
+    pub include_hidden: Option<bool>,
+}
+
+#[derive(Debug)]
+pub enum ReviewError {
+    BookNotFound,
+    MemberNotFound,
+    ReviewNotFound,
+    InvalidRating,
+    AlreadyReviewed,
+    NotReviewer,
+    AlreadyFlagged,
+    Storage(String),
+}
+
+impl IntoResponse for ReviewError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ReviewError::BookNotFound => (StatusCode::NOT_FOUND, "❌ Book Not Found"),
+            ReviewError::MemberNotFound => (StatusCode::NOT_FOUND, "❌ Member Not Found"),
+            ReviewError::ReviewNotFound => (StatusCode::NOT_FOUND, "❌ Review Not Found"),
+            ReviewError::InvalidRating => (StatusCode::BAD_REQUEST, "🚫 Rating Must Be 1 To 5"),
+            ReviewError::AlreadyReviewed => (StatusCode::CONFLICT, "🚫 Member Already Reviewed This Book"),
+            ReviewError::NotReviewer => (StatusCode::FORBIDDEN, "🔒 Only The Reviewer May Do That"),
+            ReviewError::AlreadyFlagged => (StatusCode::CONFLICT, "🚫 Member Already Flagged This Review"),
+            ReviewError::Storage(err) => {
+                tracing::error!("💥 Failed to save reviews: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Save Reviews")
+            }
+        };
+        (status, message).into_response()
+    }
+}
+
+fn stars(rating: u32) -> Result<u8, ReviewError> {
+    u8::try_from(rating).ok().filter(|stars| (1..=5).contains(stars)).ok_or(ReviewError::InvalidRating)
+}
+
+// Empty text is no text
+fn clean_text(text: Option<String>) -> Option<String> {
+    text.map(|text| text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+// Member reviews, persisted next to the catalog as one JSON document
+pub struct Reviews {
+    path: Option<PathBuf>,
+    reviews: RwLock<Vec<Review>>,
+    settings: RwLock<ReviewsConfig>,
+}
+
+impl Reviews {
+    pub fn open(storage: &Storage) -> Self {
+        let path = storage.sidecar("reviews.json");
+        let reviews = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load reviews {}: {}", path.display(), err);
+                    Vec::new()
+                }),
+            _ => Vec::new(),
+        };
+        Reviews { path, reviews: RwLock::new(reviews), settings: RwLock::new(ReviewsConfig::default()) }
+    }
+
+    pub fn configure(&self, settings: ReviewsConfig) {
+        *self.settings.write().unwrap() = settings;
+    }
+
+    pub fn read<T>(&self, view: impl FnOnce(&[Review]) -> T) -> T {
+        view(&self.reviews.read().unwrap())
+    }
+
+    fn update<T>(&self, change: impl FnOnce(&mut Vec<Review>) -> Result<T, ReviewError>) -> Result<T, ReviewError> {
+        let mut reviews = self.reviews.write().unwrap();
+        let mut draft = reviews.clone();
+        let result = change(&mut draft)?;
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&draft).map_err(|err| ReviewError::Storage(err.to_string()))?;
+            atomic_write(path, text).map_err(|err| ReviewError::Storage(err.to_string()))?;
+        }
+        *reviews = draft;
+        Ok(result)
+    }
+
+    // Reviews of a book merged into another; returns how many moved. A member
+    // who reviewed both keeps their review of the surviving book.
+    pub fn reassign_book(&self, from: u32, to: u32) -> Result<u32, ReviewError> {
+        self.update(|reviews| {
+            let reviewers: Vec<u32> = reviews.iter().filter(|review| review.book_id == to).map(|review| review.member_id).collect();
+            reviews.retain(|review| review.book_id != from || !reviewers.contains(&review.member_id));
+            let mut moved = 0;
+            for review in reviews.iter_mut().filter(|review| review.book_id == from) {
+                review.book_id = to;
+                moved += 1;
+            }
+            Ok(moved)
+        })
+    }
+
+    pub fn for_book(&self, book_id: u32, include_hidden: bool) -> Vec<Review> {
+        self.read(|reviews| {
+            reviews.iter().filter(|review| review.book_id == book_id && (include_hidden || !review.hidden)).cloned().collect()
+        })
+    }
+
+    // Only reviews of `book_id`, so a review cannot be reached through another book
+    pub fn get(&self, book_id: u32, id: u32) -> Option<Review> {
+        self.read(|reviews| reviews.iter().find(|review| review.id == id && review.book_id == book_id).cloned())
+    }
+
+    // Ratings of every reviewed book, from visible reviews only
+    pub fn ratings(&self) -> HashMap<u32, Rating> {
+        let mut totals: HashMap<u32, (u32, u32)> = HashMap::new();
+        self.read(|reviews| {
+            for review in reviews.iter().filter(|review| !review.hidden) {
+                let (sum, count) = totals.entry(review.book_id).or_default();
+                *sum += u32::from(review.rating);
+                *count += 1;
+            }
+        });
+        totals
+            .into_iter()
+            .map(|(book_id, (sum, count))| {
+                let average_rating = (f64::from(sum) / f64::from(count) * 100.0).round() / 100.0;
+                (book_id, Rating { average_rating, review_count: count })
+            })
+            .collect()
+    }
+
+    pub fn rating(&self, book_id: u32) -> Option<Rating> {
+        self.ratings().remove(&book_id)
+    }
+
+    // The caller checks that the book and member exist
+    pub fn add(
+        &self,
+        book_id: u32,
+        member_id: u32,
+        rating: u32,
+        text: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<Review, ReviewError> {
+        let rating = stars(rating)?;
+        self.update(|reviews| {
+            if reviews.iter().any(|review| review.book_id == book_id && review.member_id == member_id) {
+                return Err(ReviewError::AlreadyReviewed);
+            }
+            let id = reviews.iter().map(|review| review.id).max().unwrap_or(0) + 1;
+            let review = Review {
+                id,
+                book_id,
+                member_id,
+                rating,
+                text: clean_text(text),
+                created_at: now,
+                updated_at: None,
+                flags: Vec::new(),
+                hidden: false,
+            };
+            reviews.push(review.clone());
+            Ok(review)
+        })
+    }
+
+    fn change(
+        &self,
+        book_id: u32,
+        id: u32,
+        change: impl FnOnce(&mut Review) -> Result<(), ReviewError>,
+    ) -> Result<Review, ReviewError> {
+        self.update(|reviews| {
+            let review = reviews
+                .iter_mut()
+                .find(|review| review.id == id && review.book_id == book_id)
+                .ok_or(ReviewError::ReviewNotFound)?;
+            change(review)?;
+            Ok(review.clone())
+        })
+    }
+
+    // Only `member_id`'s own review may be edited. The caller names the
+    // member, so this is advisory rather than access control.
+    pub fn edit(
+        &self,
+        book_id: u32,
+        id: u32,
+        member_id: u32,
+        changes: UpdateReview,
+        now: DateTime<Utc>,
+    ) -> Result<Review, ReviewError> {
+        let rating = changes.rating.map(stars).transpose()?;
+        self.change(book_id, id, |review| {
+            if review.member_id != member_id {
+                return Err(ReviewError::NotReviewer);
+            }
+            if let Some(rating) = rating {
+                review.rating = rating;
+            }
+            if let Some(text) = changes.text {
+                review.text = clean_text(Some(text));
+            }
+            review.updated_at = Some(now);
+            Ok(())
+        })
+    }
+
+    // With a member, only their own review; without one, any review
+    pub fn remove(&self, book_id: u32, id: u32) -> Result<Review, ReviewError> {
+        self.update(|reviews| {
+            let index = reviews
+                .iter()
+                .position(|review| review.id == id && review.book_id == book_id)
+                .ok_or(ReviewError::ReviewNotFound)?;
+            Ok(reviews.remove(index))
+        })
+    }
+
+    // Enough flags hide a review until a moderator looks at it. The caller
+    // checks that the member exists.
+    pub fn flag(&self, book_id: u32, id: u32, flag: ReviewFlag) -> Result<Review, ReviewError> {
+        let hide_at = self.settings.read().unwrap().auto_hide_flags;
+        self.change(book_id, id, |review| {
+            if review.flags.iter().any(|earlier| earlier.member_id == flag.member_id) {
+                return Err(ReviewError::AlreadyFlagged);
+            }
+            review.flags.push(flag);
+            if hide_at > 0 && review.flags.len() >= hide_at as usize {
+                review.hidden = true;
+            }
+            Ok(())
+        })
+    }
+
+    // Unhiding clears the flags, so the same reports do not hide it again
+    pub fn moderate(&self, book_id: u32, id: u32, hidden: bool) -> Result<Review, ReviewError> {
+        self.change(book_id, id, |review| {
+            review.hidden = hidden;
+            if !hidden {
+                review.flags.clear();
+            }
+            Ok(())
+        })
+    }
+}
+
+// Books as JSON with their ratings
+pub fn rated_books(state: &AppState, books: Vec<Book>) -> Vec<RatedBook> {
+    let ratings = state.reviews.ratings();
+    books
+        .into_iter()
+        .map(|book| {
+            let rating = ratings.get(&book.id).copied();
+            RatedBook { book, rating }
+        })
+        .collect()
+}
+
+fn check_book(state: &AppState, book_id: u32) -> Result<(), ReviewError> {
+    find_book(state, book_id).map(|_| ()).ok_or(ReviewError::BookNotFound)
+}
+
+/// Review a book; each member reviews a book once
+#[utoipa::path(post, path = "/books/{id}/reviews", tag = "reviews", request_body = CreateReview,
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 201, description = "Review added", body = Review),
+        (status = 400, description = "Member or rating missing, or rating not 1 to 5", body = String),
+        (status = 404, description = "Book or member not found", body = String),
+        (status = 409, description = "The member already reviewed this book", body = String),
+        (status = 500, description = "Review could not be saved", body = String),
+    ))]
+pub async fn add_review(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Json(new_review): Json<CreateReview>,
+) -> Response {
+    let (Some(member_id), Some(rating)) = (new_review.member_id, new_review.rating) else {
+        return (StatusCode::BAD_REQUEST, "🚫 Member & Rating Required").into_response();
+    };
+    if let Err(err) = check_book(&state, id) {
+        return err.into_response();
+    }
+    if state.lending.read(|data| data.member(member_id).is_none()) {
+        return ReviewError::MemberNotFound.into_response();
+    }
+    match state.reviews.add(id, member_id, rating, new_review.text, Utc::now()) {
+        Ok(review) => (StatusCode::CREATED, Json(review)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Reviews of a book, oldest first
+#[utoipa::path(get, path = "/books/{id}/reviews", tag = "reviews",
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ReviewParams,
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key, required for include_hidden"),
+    ),
+    responses(
+        (status = 200, description = "Visible reviews, or all of them with include_hidden", body = Vec<Review>),
+        (status = 401, description = "Hidden reviews need the admin key", body = String),
+        (status = 404, description = "Book not found", body = String),
+    ))]
+pub async fn book_reviews(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Query(params): Query<ReviewParams>,
+    headers: HeaderMap,
+) -> Response {
+    let include_hidden = params.include_hidden.unwrap_or(false);
+    if include_hidden && !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    match check_book(&state, id) {
+        Ok(()) => Json(state.reviews.for_book(id, include_hidden)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Get a review by ID; hidden reviews are not found
+#[utoipa::path(get, path = "/books/{id}/reviews/{review_id}", tag = "reviews",
+    params(("id" = u32, Path, description = "Book ID"), ("review_id" = u32, Path, description = "Review ID")),
+    responses(
+        (status = 200, description = "The review", body = Review),
+        (status = 404, description = "Review not found", body = String),
+    ))]
+pub async fn get_review(Path((id, review_id)): Path<(u32, u32)>, State(state): State<AppState>) -> Response {
+    match state.reviews.get(id, review_id).filter(|review| !review.hidden) {
+        Some(review) => Json(review).into_response(),
+        None => ReviewError::ReviewNotFound.into_response(),
+    }
+}
+
+/// Change a review's rating or text, naming the member who wrote it. Members
+/// have no credentials, so the check only catches honest mistakes.
+#[utoipa::path(put, path = "/books/{id}/reviews/{review_id}", tag = "reviews", request_body = UpdateReview,
+    params(("id" = u32, Path, description = "Book ID"), ("review_id" = u32, Path, description = "Review ID")),
+    responses(
+        (status = 200, description = "Review updated", body = Review),
+        (status = 400, description = "Member missing, or rating not 1 to 5", body = String),
+        (status = 403, description = "The review is another member's", body = String),
+        (status = 404, description = "Review not found", body = String),
+        (status = 500, description = "Review could not be saved", body = String),
+    ))]
+pub async fn update_review(
+    Path((id, review_id)): Path<(u32, u32)>,
+    State(state): State<AppState>,
+    Json(changes): Json<UpdateReview>,
+) -> Response {
+    let Some(member_id) = changes.member_id else {
+        return (StatusCode::BAD_REQUEST, "🚫 Member Required").into_response();
+    };
+    match state.reviews.edit(id, review_id, member_id, changes, Utc::now()) {
+        Ok(review) => Json(review).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Delete a review; a moderator's job, so it needs the admin key
+#[utoipa::path(delete, path = "/books/{id}/reviews/{review_id}", tag = "reviews",
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ("review_id" = u32, Path, description = "Review ID"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 200, description = "Review deleted", body = Review),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Review not found", body = String),
+        (status = 500, description = "Reviews could not be saved", body = String),
+    ))]
+pub async fn delete_review(
+    Path((id, review_id)): Path<(u32, u32)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    match state.reviews.remove(id, review_id) {
+        Ok(review) => Json(review).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Report a review to the moderators; each member flags a review once
+#[utoipa::path(post, path = "/books/{id}/reviews/{review_id}/flags", tag = "reviews", request_body = CreateFlag,
+    params(("id" = u32, Path, description = "Book ID"), ("review_id" = u32, Path, description = "Review ID")),
+    responses(
+        (status = 201, description = "Flag recorded; enough flags hide the review", body = Review),
+        (status = 400, description = "Member missing", body = String),
+        (status = 404, description = "Review or member not found", body = String),
+        (status = 409, description = "The member already flagged this review", body = String),
+        (status = 500, description = "Review could not be saved", body = String),
+    ))]
+pub async fn flag_review(
+    Path((id, review_id)): Path<(u32, u32)>,
+    State(state): State<AppState>,
+    Json(flag): Json<CreateFlag>,
+) -> Response {
+    let Some(member_id) = flag.member_id else {
+        return (StatusCode::BAD_REQUEST, "🚫 Member Required").into_response();
+    };
+    if state.lending.read(|data| data.member(member_id).is_none()) {
+        return ReviewError::MemberNotFound.into_response();
+    }
+    let flag = ReviewFlag { member_id: Some(member_id), reason: clean_text(flag.reason), at: Utc::now() };
+    match state.reviews.flag(id, review_id, flag) {
+        Ok(review) => (StatusCode::CREATED, Json(review)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
+/// Hide or restore a review
+#[utoipa::path(put, path = "/books/{id}/reviews/{review_id}/moderation", tag = "reviews", request_body = Moderation,
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ("review_id" = u32, Path, description = "Review ID"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 200, description = "Review hidden or restored", body = Review),
+        (status = 400, description = "Hidden setting missing", body = String),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Review not found", body = String),
+        (status = 500, description = "Review could not be saved", body = String),
+    ))]
+pub async fn moderate_review(
+    Path((id, review_id)): Path<(u32, u32)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(moderation): Json<Moderation>,
+) -> Response {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let Some(hidden) = moderation.hidden else {
+        return (StatusCode::BAD_REQUEST, "🚫 Hidden Setting Required").into_response();
+    };
+    match state.reviews.moderate(id, review_id, hidden) {
+        Ok(review) => Json(review).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
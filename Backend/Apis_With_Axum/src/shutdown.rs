@@ -0,0 +1,118 @@
+use std::{future::Future, time::Duration};
+
+use axum::Router;
+use tokio::{net::TcpListener, sync::oneshot};
+
+use crate::AppState;
+
+// How the server stopped, mapped to the process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    Clean,
+    ServerError,
+    DrainTimedOut,
+    FlushFailed,
+}
+
+impl ShutdownOutcome {
+    // 2 is reserved for configuration errors in main
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ShutdownOutcome::Clean => 0,
+            ShutdownOutcome::ServerError => 1,
+            ShutdownOutcome::DrainTimedOut => 3,
+            ShutdownOutcome::FlushFailed => 4,
+        }
+    }
+}
+
+// Resolves on Ctrl+C or SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("❌ Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("❌ Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+// Serve until `signal` resolves, then stop accepting connections, drain
+// in-flight requests for at most `drain_timeout` and flush the catalog.
+pub async fn serve_with_shutdown(
+    listener: TcpListener,
+    app: Router,
+    state: AppState,
+    signal: impl Future<Output = ()> + Send + 'static,
+    drain_timeout: Duration,
+) -> ShutdownOutcome {
+    let (signalled_tx, signalled_rx) = oneshot::channel();
+    let stopping = state.shutdown.clone();
+
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        signal.await;
+        tracing::info!("🛑 Shutdown requested, draining in-flight requests");
+        // Ends event streams, which would otherwise never finish draining
+        stopping.send_replace(true);
+        let _ = signalled_tx.send(());
+    });
+    let mut server = tokio::spawn(async move { server.await });
+
+    let mut outcome = tokio::select! {
+        result = &mut server => server_outcome(result),
+        _ = signalled_rx => match tokio::time::timeout(drain_timeout, &mut server).await {
+            Ok(result) => server_outcome(result),
+            Err(_) => {
+                tracing::warn!("⏱️ Connections still open after {:?}, closing them", drain_timeout);
+                server.abort();
+                ShutdownOutcome::DrainTimedOut
+            }
+        },
+    };
+
+    // Persist whatever made it into memory before the process exits
+    let flush_state = state.clone();
+    match tokio::task::spawn_blocking(move || flush_state.flush()).await {
+        Ok(Ok(())) => tracing::info!("💾 Catalog flushed"),
+        Ok(Err(err)) => {
+            tracing::error!("💥 Failed to flush catalog: {}", err);
+            outcome = ShutdownOutcome::FlushFailed;
+        }
+        Err(err) => {
+            tracing::error!("💥 Flush task failed: {}", err);
+            outcome = ShutdownOutcome::FlushFailed;
+        }
+    }
+
+    outcome
+}
+
+fn server_outcome(
+    result: Result<std::io::Result<()>, tokio::task::JoinError>,
+) -> ShutdownOutcome {
+    match result {
+        Ok(Ok(())) => ShutdownOutcome::Clean,
+        Ok(Err(err)) => {
+            tracing::error!("💥 Server error: {}", err);
+            ShutdownOutcome::ServerError
+        }
+        Err(err) => {
+            tracing::error!("💥 Server task failed: {}", err);
+            ShutdownOutcome::ServerError
+        }
+    }
+}
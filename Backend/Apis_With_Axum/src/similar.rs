@@ -0,0 +1,254 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Mutex,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{book::Book, AppState};
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+// What each kind of overlap is worth
+const AUTHOR_WEIGHT: u32 = 3;
+const TAG_WEIGHT: u32 = 1;
+const BORROWER_WEIGHT: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Suggestion {
+    #[serde(flatten)]
+    pub book: Book,
+    /// Higher is more similar
+    pub score: u32,
+    pub shared_authors: Vec<String>,
+    pub shared_tags: Vec<String>,
+    /// Members who borrowed both books
+    pub co_borrowers: u32,
+    /// Why the book was suggested, in words
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SimilarParams {
+    /// At most this many suggestions (default 10, at most 50)
+    pub limit: Option<usize>,
+}
+
+// "Steve Klabnik and Carol Nichols" is two authors
+pub fn split_authors(author: &str) -> Vec<String> {
+    author
+        .split(" and ")
+        .flat_map(|part| part.split(['&', ';']))
+        .map(|name| name.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn author_key(name: &str) -> String {
+    name.to_lowercase()
+}
+
+// Lookup tables over the live catalog, its tags and its loans. They are
+// built once and then caught up from the audit log, new loans and tag
+// changes, so a query never rescans the whole catalog.
+#[derive(Default)]
+struct Index {
+    built: bool,
+    audit_seq: u64,
+    last_loan_id: u32,
+    tags_revision: u64,
+    books: BTreeMap<u32, Book>,
+    by_author: HashMap<String, BTreeSet<u32>>,
+    tags: BTreeMap<u32, BTreeSet<String>>,
+    by_tag: HashMap<String, BTreeSet<u32>>,
+    borrowers: HashMap<u32, BTreeSet<u32>>,
+    borrowed: HashMap<u32, BTreeSet<u32>>,
+}
+
+impl Index {
+    fn add_book(&mut self, book: Book) {
+        for name in split_authors(&book.author) {
+            self.by_author.entry(author_key(&name)).or_default().insert(book.id);
+        }
+        self.books.insert(book.id, book);
+    }
+
+    fn remove_book(&mut self, book_id: u32) {
+        let Some(book) = self.books.remove(&book_id) else {
+            return;
+        };
+        for name in split_authors(&book.author) {
+            let key = author_key(&name);
+            if let Some(ids) = self.by_author.get_mut(&key) {
+                ids.remove(&book_id);
+                if ids.is_empty() {
+                    self.by_author.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn set_tags(&mut self, tags: BTreeMap<u32, BTreeSet<String>>) {
+        self.by_tag.clear();
+        for (book_id, book_tags) in &tags {
+            for tag in book_tags {
+                self.by_tag.entry(tag.clone()).or_default().insert(*book_id);
+            }
+        }
+        self.tags = tags;
+    }
+
+    fn add_loan(&mut self, book_id: u32, member_id: u32) {
+        self.borrowers.entry(book_id).or_default().insert(member_id);
+        self.borrowed.entry(member_id).or_default().insert(book_id);
+    }
+
+    // Loans of a book merged into another now count for that book
+    fn move_loans(&mut self, from: u32, to: u32) {
+        for member_id in self.borrowers.remove(&from).unwrap_or_default() {
+            let borrowed = self.borrowed.entry(member_id).or_default();
+            borrowed.remove(&from);
+            borrowed.insert(to);
+            self.borrowers.entry(to).or_default().insert(member_id);
+        }
+    }
+
+    fn refresh(&mut self, state: &AppState) {
+        // Read before the catalog, so a change in between is replayed, not lost
+        let last_seq = state.audit.last_seq();
+        if !self.built {
+            let books: Vec<Book> = state.books.read().unwrap().iter().filter(|book| !book.is_deleted()).cloned().collect();
+            for book in books {
+                self.add_book(book);
+            }
+            self.audit_seq = last_seq;
+            self.tags_revision = state.tags.revision();
+            self.set_tags(state.tags.read(Clone::clone));
+            self.built = true;
+        }
+
+        // Each entry holds the book as it is after the change, if it is still live
+        for entry in state.audit.page(self.audit_seq, usize::MAX) {
+            self.remove_book(entry.book_id);
+            if let Some(book) = entry.after.filter(|book| !book.is_deleted()) {
+                self.add_book(book);
+            }
+            if let Some(into) = entry.merged_into {
+                self.move_loans(entry.book_id, into);
+            }
+            self.audit_seq = entry.seq;
+        }
+
+        // Loans are only ever appended, in ID order
+        let last_loan_id = self.last_loan_id;
+        let new_loans: Vec<(u32, u32, u32)> = state.lending.read(|data| {
+            let start = data.loans.partition_point(|loan| loan.id <= last_loan_id);
+            data.loans[start..].iter().map(|loan| (loan.id, loan.book_id, loan.member_id)).collect()
+        });
+        for (loan_id, book_id, member_id) in new_loans {
+            self.add_loan(book_id, member_id);
+            self.last_loan_id = loan_id;
+        }
+
+        let revision = state.tags.revision();
+        if revision != self.tags_revision {
+            self.set_tags(state.tags.read(Clone::clone));
+            self.tags_revision = revision;
+        }
+    }
+
+    fn suggest(&self, book_id: u32, limit: usize) -> Option<Vec<Suggestion>> {
+        let target = self.books.get(&book_id)?;
+        let mut authors: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+        let mut tags: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+        let mut co_borrowers: BTreeMap<u32, u32> = BTreeMap::new();
+
+        for name in split_authors(&target.author) {
+            for id in self.by_author.get(&author_key(&name)).into_iter().flatten() {
+                authors.entry(*id).or_default().push(name.clone());
+            }
+        }
+        for tag in self.tags.get(&book_id).into_iter().flatten() {
+            for id in self.by_tag.get(tag).into_iter().flatten() {
+                tags.entry(*id).or_default().push(tag.clone());
+            }
+        }
+        for member_id in self.borrowers.get(&book_id).into_iter().flatten() {
+            for id in self.borrowed.get(member_id).into_iter().flatten() {
+                *co_borrowers.entry(*id).or_default() += 1;
+            }
+        }
+
+        let candidates: BTreeSet<u32> = authors.keys().chain(tags.keys()).chain(co_borrowers.keys()).copied().collect();
+        let mut suggestions: Vec<Suggestion> = candidates
+            .into_iter()
+            .filter(|id| *id != book_id)
+            .filter_map(|id| {
+                let book = self.books.get(&id)?.clone();
+                let shared_authors = authors.remove(&id).unwrap_or_default();
+                let shared_tags = tags.remove(&id).unwrap_or_default();
+                let co_borrowers = co_borrowers.get(&id).copied().unwrap_or(0);
+
+                let mut reasons = Vec::new();
+                if !shared_authors.is_empty() {
+                    reasons.push(format!("Also by {}", shared_authors.join(" and ")));
+                }
+                if !shared_tags.is_empty() {
+                    reasons.push(format!("Also tagged {}", shared_tags.join(", ")));
+                }
+                if co_borrowers > 0 {
+                    reasons.push(format!("Borrowed by {} member(s) who also borrowed this book", co_borrowers));
+                }
+                let score = AUTHOR_WEIGHT * shared_authors.len() as u32
+                    + TAG_WEIGHT * shared_tags.len() as u32
+                    + BORROWER_WEIGHT * co_borrowers;
+                Some(Suggestion { book, score, shared_authors, shared_tags, co_borrowers, reasons })
+            })
+            .collect();
+
+        suggestions.sort_by(|a, b| b.score.cmp(&a.score).then(a.book.id.cmp(&b.book.id)));
+        suggestions.truncate(limit);
+        Some(suggestions)
+    }
+}
+
+// Recommendations for one catalog, kept up to date as it changes
+#[derive(Default)]
+pub struct Similarity {
+    index: Mutex<Index>,
+}
+
+impl Similarity {
+    // None when the book is not in the catalog
+    pub fn suggest(&self, state: &AppState, book_id: u32, limit: usize) -> Option<Vec<Suggestion>> {
+        let mut index = self.index.lock().unwrap();
+        index.refresh(state);
+        index.suggest(book_id, limit)
+    }
+}
+
+/// Books like this one, by shared authors, tags and borrowers
+#[utoipa::path(get, path = "/books/{id}/similar", tag = "books",
+    params(("id" = u32, Path, description = "Book ID"), SimilarParams),
+    responses(
+        (status = 200, description = "Suggestions, most similar first, each with its reasons", body = Vec<Suggestion>),
+        (status = 404, description = "Book not found", body = String),
+    ))]
+pub async fn similar_books(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Query(params): Query<SimilarParams>,
+) -> Response {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    match state.similar.suggest(&state, id, limit) {
+        Some(suggestions) => Json(suggestions).into_response(),
+        None => (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response(),
+    }
+}
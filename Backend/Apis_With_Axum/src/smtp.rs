@@ -0,0 +1,81 @@
+use std::{fmt, time::Duration};
+
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::{authentication::Credentials, extension::ClientId},
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+
+use crate::config::{EmailConfig, SmtpSecurity};
+
+// A plain-text email
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpError {
+    pub message: String,
+    // The server refused for good (5xx), so trying again will not help
+    pub permanent: bool,
+}
+
+impl SmtpError {
+    fn permanent(message: impl fmt::Display) -> Self {
+        SmtpError { message: message.to_string(), permanent: true }
+    }
+}
+
+impl From<lettre::transport::smtp::Error> for SmtpError {
+    fn from(err: lettre::transport::smtp::Error) -> Self {
+        SmtpError { message: err.to_string(), permanent: err.is_permanent() }
+    }
+}
+
+impl fmt::Display for SmtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+fn helo_name(from: &str) -> String {
+    from.rsplit_once('@').map(|(_, domain)| domain).unwrap_or("localhost").to_string()
+}
+
+fn transport(settings: &EmailConfig, from: &str) -> Result<AsyncSmtpTransport<Tokio1Executor>, SmtpError> {
+    let host = settings.smtp_host.as_str();
+    let builder = match settings.security {
+        SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+    };
+    let mut builder = builder
+        .port(settings.smtp_port)
+        .timeout(Some(Duration::from_secs(settings.timeout_secs)))
+        .hello_name(ClientId::Domain(helo_name(from)));
+    if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+        builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+    Ok(builder.build())
+}
+
+// Hand one message to the configured server
+pub async fn send(settings: &EmailConfig, message: &Message) -> Result<(), SmtpError> {
+    let mailbox = |address: &str| {
+        address.parse::<Mailbox>().map_err(|_| SmtpError::permanent(format!("'{}' is not an email address", address)))
+    };
+    let email = lettre::Message::builder()
+        .from(mailbox(&message.from)?)
+        .to(mailbox(&message.to)?)
+        .subject(&message.subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.body.clone())
+        .map_err(SmtpError::permanent)?;
+
+    transport(settings, &message.from)?.send(email).await?;
+    Ok(())
+}
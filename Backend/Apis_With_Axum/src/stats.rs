@@ -0,0 +1,249 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    audit::AuditAction,
+    book::Book,
+    format::{Format, Negotiated},
+    similar::split_authors,
+    AppState,
+};
+
+const DEFAULT_TOP: usize = 10;
+const MAX_TOP: usize = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StatsParams {
+    /// Length of the most-borrowed and newest lists (default 10, at most 100)
+    pub top: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Count {
+    pub name: String,
+    pub books: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BorrowedBook {
+    pub id: u32,
+    pub title: String,
+    pub author: String,
+    /// Loans ever made, returned or not
+    pub loans: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NewBook {
+    pub id: u32,
+    pub title: String,
+    pub author: String,
+    /// Null for books loaded from the data file rather than added through the API
+    pub added_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DuplicateTitle {
+    pub title: String,
+    pub ids: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DataQuality {
+    /// IDs of books without an ISBN in their metadata
+    pub missing_isbn: Vec<u32>,
+    /// IDs of books without a publication year, left out of `by_decade`
+    pub missing_year: Vec<u32>,
+    /// Titles shared by more than one book, ignoring case and punctuation
+    pub duplicate_titles: Vec<DuplicateTitle>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CatalogStats {
+    pub total_books: u32,
+    /// Co-authored books count once for each author
+    pub by_author: Vec<Count>,
+    pub by_tag: Vec<Count>,
+    /// Decades such as "1960s", oldest first
+    pub by_decade: Vec<Count>,
+    pub most_borrowed: Vec<BorrowedBook>,
+    pub newest: Vec<NewBook>,
+    pub quality: DataQuality,
+}
+
+// Lowercase words without punctuation, so "Dune!" and "dune" compare equal
+pub fn normalize_title(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Most books first, then by name
+fn counts(tally: BTreeMap<String, (String, u32)>) -> Vec<Count> {
+    let mut counts: Vec<Count> = tally.into_values().map(|(name, books)| Count { name, books }).collect();
+    counts.sort_by(|a, b| b.books.cmp(&a.books).then_with(|| a.name.cmp(&b.name)));
+    counts
+}
+
+pub fn catalog_stats(state: &AppState, top: usize) -> CatalogStats {
+    let books: Vec<Book> = state.books.read().unwrap().iter().filter(|book| !book.is_deleted()).cloned().collect();
+    let tags = state.tags.read(Clone::clone);
+    let metadata = state.metadata.read(Clone::clone);
+
+    // Keyed case-insensitively, shown as first spelled
+    let mut by_author: BTreeMap<String, (String, u32)> = BTreeMap::new();
+    let mut by_tag: BTreeMap<String, (String, u32)> = BTreeMap::new();
+    let mut by_decade: BTreeMap<i32, u32> = BTreeMap::new();
+    let mut titles: BTreeMap<String, Vec<&Book>> = BTreeMap::new();
+    let mut quality = DataQuality { missing_isbn: Vec::new(), missing_year: Vec::new(), duplicate_titles: Vec::new() };
+
+    for book in &books {
+        for name in split_authors(&book.author) {
+            by_author.entry(name.to_lowercase()).or_insert((name, 0)).1 += 1;
+        }
+        for tag in tags.get(&book.id).into_iter().flatten() {
+            by_tag.entry(tag.clone()).or_insert((tag.clone(), 0)).1 += 1;
+        }
+        let details = metadata.get(&book.id);
+        match details.and_then(|details| details.year) {
+            Some(year) => *by_decade.entry(year.div_euclid(10) * 10).or_default() += 1,
+            None => quality.missing_year.push(book.id),
+        }
+        if details.and_then(|details| details.isbn.as_ref()).is_none() {
+            quality.missing_isbn.push(book.id);
+        }
+        titles.entry(normalize_title(&book.title)).or_default().push(book);
+    }
+    quality.duplicate_titles = titles
+        .into_values()
+        .filter(|books| books.len() > 1)
+        .map(|books| DuplicateTitle { title: books[0].title.clone(), ids: books.iter().map(|book| book.id).collect() })
+        .collect();
+
+    let loans: HashMap<u32, u32> = state.lending.read(|data| {
+        let mut loans = HashMap::new();
+        for loan in &data.loans {
+            *loans.entry(loan.book_id).or_default() += 1;
+        }
+        loans
+    });
+    let mut most_borrowed: Vec<BorrowedBook> = books
+        .iter()
+        .filter_map(|book| {
+            let loans = loans.get(&book.id).copied()?;
+            Some(BorrowedBook { id: book.id, title: book.title.clone(), author: book.author.clone(), loans })
+        })
+        .collect();
+    most_borrowed.sort_by(|a, b| b.loans.cmp(&a.loans).then(a.id.cmp(&b.id)));
+    most_borrowed.truncate(top);
+
+    let added: HashMap<u32, DateTime<Utc>> = state
+        .audit
+        .page(0, usize::MAX)
+        .into_iter()
+        .filter(|entry| entry.action == AuditAction::Created)
+        .map(|entry| (entry.book_id, entry.at))
+        .collect();
+    // IDs only grow, so the highest are the newest
+    let mut newest: Vec<&Book> = books.iter().collect();
+    newest.sort_by_key(|book| Reverse(book.id));
+    let newest: Vec<NewBook> = newest
+        .into_iter()
+        .take(top)
+        .map(|book| NewBook {
+            id: book.id,
+            title: book.title.clone(),
+            author: book.author.clone(),
+            added_at: added.get(&book.id).copied(),
+        })
+        .collect();
+
+    CatalogStats {
+        total_books: books.len() as u32,
+        by_author: counts(by_author),
+        by_tag: counts(by_tag),
+        by_decade: by_decade.into_iter().map(|(decade, books)| Count { name: format!("{}s", decade), books }).collect(),
+        most_borrowed,
+        newest,
+        quality,
+    }
+}
+
+// One row per figure: section, book ID where there is one, name and value
+fn write_csv(stats: &CatalogStats) -> Result<String, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["section", "id", "name", "value"])?;
+    writer.write_record(["total", "", "books", &stats.total_books.to_string()])?;
+    for (section, counts) in [("author", &stats.by_author), ("tag", &stats.by_tag), ("decade", &stats.by_decade)] {
+        for count in counts {
+            writer.write_record([section, "", &count.name, &count.books.to_string()])?;
+        }
+    }
+    for book in &stats.most_borrowed {
+        writer.write_record(["most_borrowed", &book.id.to_string(), &book.title, &book.loans.to_string()])?;
+    }
+    for book in &stats.newest {
+        let added_at = book.added_at.map(|at| at.to_rfc3339()).unwrap_or_default();
+        writer.write_record(["newest", &book.id.to_string(), &book.title, &added_at])?;
+    }
+    for (section, ids) in [("missing_isbn", &stats.quality.missing_isbn), ("missing_year", &stats.quality.missing_year)] {
+        for id in ids {
+            writer.write_record([section, &id.to_string(), "", ""])?;
+        }
+    }
+    for duplicate in &stats.quality.duplicate_titles {
+        let ids = duplicate.ids.iter().map(u32::to_string).collect::<Vec<_>>().join(" ");
+        writer.write_record(["duplicate_title", "", &duplicate.title, &ids])?;
+    }
+    let bytes = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(String::from_utf8(bytes).expect("csv output is utf-8"))
+}
+
+/// Catalog statistics and data-quality report, as JSON or CSV
+#[utoipa::path(get, path = "/stats", tag = "stats", params(StatsParams),
+    responses(
+        (status = 200, description = "Counts per author, tag and decade, most borrowed, newest and data quality", content(
+            (CatalogStats = "application/json"), (String = "text/csv"))),
+        (status = 406, description = "Neither JSON nor CSV is acceptable", body = String),
+    ))]
+pub async fn get_stats(
+    State(state): State<AppState>,
+    Query(params): Query<StatsParams>,
+    Negotiated(format): Negotiated,
+) -> Response {
+    let top = params.top.unwrap_or(DEFAULT_TOP).clamp(1, MAX_TOP);
+    let stats = catalog_stats(&state, top);
+
+    match format {
+        Format::Json => Json(stats).into_response(),
+        Format::Csv => match write_csv(&stats) {
+            Ok(body) => (
+                [
+                    (header::CONTENT_TYPE, Format::Csv.content_type()),
+                    (header::CONTENT_DISPOSITION, "attachment; filename=\"catalog-stats.csv\""),
+                ],
+                body,
+            )
+                .into_response(),
+            Err(err) => {
+                tracing::error!("💥 Failed to encode response: {}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Encode Response").into_response()
+            }
+        },
+        Format::Xml => (StatusCode::NOT_ACCEPTABLE, "🚫 Not Acceptable, Use application/json or text/csv").into_response(),
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    book::*,
+    config::{Config, StorageBackend},
+};
+
+// Where the catalog is persisted
+#[derive(Debug, Clone)]
+pub enum Storage {
+    Csv(PathBuf),
+    Memory,
+}
+
+impl Storage {
+    pub fn from_config(config: &Config) -> Self {
+        match config.storage {
+            StorageBackend::Csv => Storage::Csv(config.data_path.clone()),
+            StorageBackend::Memory => Storage::Memory,
+        }
+    }
+
+    pub fn load(&self) -> Result<Vec<Book>, csv::Error> {
+        match self {
+            Storage::Csv(path) => load_books_from_csv(path),
+            Storage::Memory => Ok(Vec::new()),
+        }
+    }
+
+    // Rows that do not parse are skipped and reported instead of failing the load
+    pub fn load_leniently(&self) -> Result<CsvLoad, csv::Error> {
+        match self {
+            Storage::Csv(path) => load_books_leniently(path),
+            Storage::Memory => Ok(CsvLoad::default()),
+        }
+    }
+
+    // Append skipped rows to e.g. books.rejected.csv, since the next save
+    // rewrites the data file without them. Returns the file written to.
+    pub fn set_aside(&self, load: &CsvLoad) -> Result<Option<PathBuf>, csv::Error> {
+        let Some(path) = self.sidecar("rejected.csv").filter(|_| !load.rejected.is_empty()) else {
+            return Ok(None);
+        };
+        let is_new = !path.exists();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(file);
+        if is_new {
+            writer.write_byte_record(&load.headers)?;
+        }
+        for row in &load.rejected {
+            writer.write_byte_record(row)?;
+        }
+        writer.flush()?;
+        Ok(Some(path))
+    }
+
+    pub fn save(&self, books: &[Book]) -> Result<(), csv::Error> {
+        match self {
+            Storage::Csv(path) => save_books_to_csv(path, books),
+            Storage::Memory => Ok(()),
+        }
+    }
+
+    // Each library keeps its catalog in its own directory next to the main one,
+    // e.g. assets/books.csv -> assets/libraries/<name>/books.csv
+    pub fn for_library(&self, name: &str) -> Storage {
+        match self {
+            Storage::Csv(path) => {
+                let dir = path.parent().unwrap_or(Path::new("")).join("libraries").join(name);
+                Storage::Csv(dir.join("books.csv"))
+            }
+            Storage::Memory => Storage::Memory,
+        }
+    }
+
+    // Sidecar file stored next to the CSV, e.g. books.csv -> books.audit.jsonl
+    pub fn sidecar(&self, suffix: &str) -> Option<PathBuf> {
+        match self {
+            Storage::Csv(path) => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                Some(path.with_file_name(format!("{}.{}", stem, suffix)))
+            }
+            Storage::Memory => None,
+        }
+    }
+}
+
+// One JSON document per line; a missing file is an empty log. Lines that
+// do not parse, e.g. one torn by a crash mid-append, are skipped with a
+// warning so they cannot take the rest of the log with them.
+pub fn load_jsonl<T: DeserializeOwned>(path: &Path) -> io::Result<Vec<T>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut items = Vec::new();
+    for (index, line) in BufReader::new(file).split(b'\n').enumerate() {
+        let line = line?;
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        match serde_json::from_slice(&line) {
+            Ok(item) => items.push(item),
+            Err(err) => tracing::warn!("⚠️ Skipping line {} of {}: {}", index + 1, path.display(), err),
+        }
+    }
+    Ok(items)
+}
+
+// Replace `path` in one step: write a temporary file next to it, flush it to
+// disk and rename it over the old one, so a crash mid-save leaves either the
+// old contents or the new, never a torn file
+pub fn atomic_write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.tmp", name));
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(contents.as_ref())?;
+        file.sync_all()
+    });
+    if let Err(err) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(err);
+    }
+    // Make the rename itself durable; not every platform can open a directory
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+pub fn append_jsonl<T: Serialize>(path: &Path, item: &T) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(item).map_err(io::Error::other)?;
+    writeln!(file, "{}", line)
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{copies::find_book, storage::{atomic_write, Storage}, AppState};
+
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 40;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct SetTags {
+    /// Replaces every tag; an empty list clears them
+    pub tags: Option<Vec<String>>,
+}
+
+// Lowercase, single-spaced, so "Sci-Fi " and "sci-fi" are the same tag
+pub fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Subject tags per book, persisted next to the catalog as one JSON document
+pub struct Tags {
+    path: Option<PathBuf>,
+    tags: RwLock<BTreeMap<u32, BTreeSet<String>>>,
+    // Bumped on every change so indexes know when to catch up
+    revision: AtomicU64,
+}
+
+impl Tags {
+    pub fn open(storage: &Storage) -> Self {
+        let path = storage.sidecar("tags.json");
+        let tags = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load tags {}: {}", path.display(), err);
+                    BTreeMap::new()
+                }),
+            _ => BTreeMap::new(),
+        };
+        Tags { path, tags: RwLock::new(tags), revision: AtomicU64::new(0) }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    pub fn read<T>(&self, view: impl FnOnce(&BTreeMap<u32, BTreeSet<String>>) -> T) -> T {
+        view(&self.tags.read().unwrap())
+    }
+
+    pub fn for_book(&self, book_id: u32) -> BTreeSet<String> {
+        self.read(|tags| tags.get(&book_id).cloned().unwrap_or_default())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<u32, BTreeSet<String>>)) -> Result<(), String> {
+        let mut tags = self.tags.write().unwrap();
+        let mut draft = tags.clone();
+        change(&mut draft);
+        if let Some(path) = &self.path {
+            let text = serde_json::to_string_pretty(&draft).map_err(|err| err.to_string())?;
+            atomic_write(path, text).map_err(|err| err.to_string())?;
+        }
+        *tags = draft;
+        self.revision.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn set(&self, book_id: u32, new_tags: BTreeSet<String>) -> Result<BTreeSet<String>, String> {
+        self.update(|tags| {
+            if new_tags.is_empty() {
+                tags.remove(&book_id);
+            } else {
+                tags.insert(book_id, new_tags.clone());
+            }
+        })?;
+        Ok(new_tags)
+    }
+
+    // A book merged into another brings its tags along
+    pub fn merge(&self, from: u32, to: u32) -> Result<(), String> {
+        self.update(|tags| {
+            if let Some(moved) = tags.remove(&from) {
+                tags.entry(to).or_default().extend(moved);
+            }
+        })
+    }
+}
+
+/// A book's tags, alphabetically
+#[utoipa::path(get, path = "/books/{id}/tags", tag = "books",
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "The book's tags", body = Vec<String>),
+        (status = 404, description = "Book not found", body = String),
+    ))]
+pub async fn book_tags(Path(id): Path<u32>, State(state): State<AppState>) -> Response {
+    if find_book(&state, id).is_none() {
+        return (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response();
+    }
+    Json(state.tags.for_book(id)).into_response()
+}
+
+/// Replace a book's tags
+#[utoipa::path(put, path = "/books/{id}/tags", tag = "books", request_body = SetTags,
+    params(("id" = u32, Path, description = "Book ID")),
+    responses(
+        (status = 200, description = "The book's tags, normalized", body = Vec<String>),
+        (status = 400, description = "Tags missing, too many or too long", body = String),
+        (status = 404, description = "Book not found", body = String),
+        (status = 500, description = "Tags could not be saved", body = String),
+    ))]
+pub async fn set_book_tags(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Json(request): Json<SetTags>,
+) -> Response {
+    let Some(tags) = request.tags else {
+        return (StatusCode::BAD_REQUEST, "🚫 Tags Required").into_response();
+    };
+    let tags: BTreeSet<String> = tags.iter().map(|tag| normalize_tag(tag)).filter(|tag| !tag.is_empty()).collect();
+    if tags.len() > MAX_TAGS || tags.iter().any(|tag| tag.chars().count() > MAX_TAG_LENGTH) {
+        let message = format!("🚫 At Most {} Tags Of {} Characters", MAX_TAGS, MAX_TAG_LENGTH);
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    if find_book(&state, id).is_none() {
+        return (StatusCode::NOT_FOUND, "❌ Book Not Found").into_response();
+    }
+
+    match state.tags.set(id, tags) {
+        Ok(tags) => Json(tags).into_response(),
+        Err(err) => {
+            tracing::error!("💥 Failed to save tags: {}", err);
+            (StatusCode::INTERNAL_SERVER_ERROR, "💥 Failed To Save Tags").into_response()
+        }
+    }
+}
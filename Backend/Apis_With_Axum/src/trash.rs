@@ -0,0 +1,113 @@
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    audit::{Actor, AuditAction},
+    book::Book,
+    handler::storage_error,
+    AppState,
+};
+
+/// List books in the trash, most recently deleted first
+#[utoipa::path(get, path = "/books/trash", tag = "trash",
+    responses((status = 200, description = "Deleted books awaiting purge", body = Vec<Book>)))]
+pub async fn list_trash(State(state): State<AppState>) -> Json<Vec<Book>> {
+    let books_reader = state.books.read().unwrap();
+    let mut trashed: Vec<Book> = books_reader.iter().filter(|book| book.is_deleted()).cloned().collect();
+    trashed.sort_by_key(|book| std::cmp::Reverse(book.deleted_at));
+    Json(trashed)
+}
+
+/// Take a book back out of the trash
+#[utoipa::path(post, path = "/books/{id}/restore", tag = "trash",
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ("X-Actor" = Option<String>, Header, description = "Who is making the change"),
+    ),
+    responses(
+        (status = 200, description = "Book restored", body = Book),
+        (status = 404, description = "Book is not in the trash", body = String),
+        (status = 500, description = "Book could not be saved", body = String),
+    ))]
+pub async fn restore_book(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+) -> impl IntoResponse {
+    let mut books_writer = state.books.write().unwrap();
+    let mut draft = books_writer.clone();
+    let Some(book) = draft.iter_mut().find(|book| book.id == id && book.is_deleted()) else {
+        return (StatusCode::NOT_FOUND, "❌ Book Not In Trash").into_response();
+    };
+
+    book.deleted_at = None;
+    let restored = book.clone();
+    if state.persist(&draft).is_err() {
+        return storage_error();
+    }
+    *books_writer = draft;
+    state.audit.record(AuditAction::Restored, &actor, None, Some(restored.clone()));
+    Json(restored).into_response()
+}
+
+/// Permanently remove a book that is already in the trash
+#[utoipa::path(delete, path = "/books/{id}/purge", tag = "trash",
+    params(
+        ("id" = u32, Path, description = "Book ID"),
+        ("X-Actor" = Option<String>, Header, description = "Who is making the change"),
+    ),
+    responses(
+        (status = 200, description = "Book purged", body = String),
+        (status = 404, description = "Book is not in the trash", body = String),
+        (status = 500, description = "Book could not be saved", body = String),
+    ))]
+pub async fn purge_book(
+    Path(id): Path<u32>,
+    State(state): State<AppState>,
+    Actor(actor): Actor,
+) -> impl IntoResponse {
+    let mut books_writer = state.books.write().unwrap();
+    let Some(position) = books_writer.iter().position(|book| book.id == id && book.is_deleted()) else {
+        return (StatusCode::NOT_FOUND, "❌ Book Not In Trash").into_response();
+    };
+
+    let mut draft = books_writer.clone();
+    let purged = draft.remove(position);
+    if state.persist(&draft).is_err() {
+        return storage_error();
+    }
+    *books_writer = draft;
+    state.audit.record(AuditAction::Purged, &actor, Some(purged), None);
+    (StatusCode::OK, "🔥 Book Purged").into_response()
+}
+
+// Remove every book that has been in the trash longer than `retention`.
+// Returns the purged books.
+pub fn purge_expired(state: &AppState, retention: chrono::Duration, now: DateTime<Utc>) -> Vec<Book> {
+    let mut books_writer = state.books.write().unwrap();
+    let cutoff = now - retention;
+
+    let (expired, kept): (Vec<Book>, Vec<Book>) = books_writer
+        .iter()
+        .cloned()
+        .partition(|book| book.deleted_at.is_some_and(|at| at <= cutoff));
+
+    if expired.is_empty() {
+        return expired;
+    }
+    if state.persist(&kept).is_err() {
+        // Keep them in the trash and try again next time
+        return Vec::new();
+    }
+    *books_writer = kept;
+    for book in &expired {
+        state.audit.record(AuditAction::Purged, "trash-purger", Some(book.clone()), None);
+    }
+    tracing::info!("🔥 Purged {} book(s) from the trash", expired.len());
+    expired
+}
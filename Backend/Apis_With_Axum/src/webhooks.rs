@@ -0,0 +1,592 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json},
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::{
+    sync::{broadcast::error::RecvError, Notify},
+    task::JoinHandle,
+};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    audit::{AuditEntry, AuditLog},
+    config::WebhookConfig,
+    events::{ChangeEvent, ChangeKind},
+    storage::{append_jsonl, atomic_write, Storage},
+    AppState,
+};
+
+// How many delivery attempts are kept in memory for GET /webhooks/deliveries
+const DELIVERY_LOG_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Subscription {
+    pub id: u32,
+    pub url: String,
+    /// HMAC-SHA256 key; only returned when the subscription is created
+    pub secret: String,
+    /// Change kinds to deliver; empty means all
+    pub events: Vec<ChangeKind>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Subscription {
+    fn wants(&self, kind: ChangeKind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+
+    fn summary(&self) -> SubscriptionSummary {
+        SubscriptionSummary {
+            id: self.id,
+            url: self.url.clone(),
+            events: self.events.clone(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SubscriptionSummary {
+    pub id: u32,
+    pub url: String,
+    pub events: Vec<ChangeKind>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct CreateSubscription {
+    pub url: Option<String>,
+    /// Generated when omitted
+    pub secret: Option<String>,
+    pub events: Option<Vec<ChangeKind>>,
+}
+
+// One HTTP attempt
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeliveryRecord {
+    pub delivery_id: String,
+    pub subscription_id: u32,
+    pub event_id: u64,
+    pub attempt: u32,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub succeeded: bool,
+    pub at: DateTime<Utc>,
+}
+
+// An event that could not be delivered after every retry
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetter {
+    pub id: u32,
+    pub subscription_id: u32,
+    pub event: ChangeEvent,
+    pub attempts: u32,
+    pub last_error: String,
+    pub failed_at: DateTime<Utc>,
+}
+
+// Everything that survives a restart, stored as one JSON document
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WebhookStore {
+    subscriptions: Vec<Subscription>,
+    dead_letters: Vec<DeadLetter>,
+    // Where subscriptions without a cursor of their own start; older stores
+    // kept this one cursor for all of them
+    cursor: u64,
+    // Last audit sequence number each subscription is done with
+    #[serde(default)]
+    cursors: BTreeMap<u32, u64>,
+}
+
+pub struct Webhooks {
+    path: Option<PathBuf>,
+    log_path: Option<PathBuf>,
+    store: RwLock<WebhookStore>,
+    deliveries: RwLock<VecDeque<DeliveryRecord>>,
+    settings: RwLock<WebhookConfig>,
+    client: RwLock<reqwest::Client>,
+    // Woken when subscriptions are added or removed, so the dispatcher can
+    // start or stop their workers
+    changed: Notify,
+}
+
+impl Webhooks {
+    pub fn open(storage: &Storage, settings: WebhookConfig) -> Self {
+        let path = storage.sidecar("webhooks.json");
+        let store = match &path {
+            Some(path) if path.exists() => fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| serde_json::from_str(&text).map_err(|err| err.to_string()))
+                .unwrap_or_else(|err| {
+                    tracing::warn!("⚠️ Failed to load webhooks {}: {}", path.display(), err);
+                    WebhookStore::default()
+                }),
+            _ => WebhookStore::default(),
+        };
+
+        Webhooks {
+            path,
+            log_path: storage.sidecar("deliveries.jsonl"),
+            store: RwLock::new(store),
+            deliveries: RwLock::new(VecDeque::new()),
+            client: RwLock::new(client(&settings)),
+            settings: RwLock::new(settings),
+            changed: Notify::new(),
+        }
+    }
+
+    // Retry and address settings for deliveries started from now on
+    pub fn configure(&self, settings: WebhookConfig) {
+        *self.client.write().unwrap() = client(&settings);
+        *self.settings.write().unwrap() = settings;
+    }
+
+    fn save(&self, store: &WebhookStore) {
+        let Some(path) = &self.path else { return };
+        let result = serde_json::to_string_pretty(store)
+            .map_err(std::io::Error::other)
+            .and_then(|text| atomic_write(path, text));
+        if let Err(err) = result {
+            tracing::error!("💥 Failed to save webhooks: {}", err);
+        }
+    }
+
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.store.read().unwrap().subscriptions.clone()
+    }
+
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.store.read().unwrap().dead_letters.clone()
+    }
+
+    pub fn deliveries(&self) -> Vec<DeliveryRecord> {
+        self.deliveries.read().unwrap().iter().cloned().collect()
+    }
+
+    // The subscription gets changes after audit sequence number `since`
+    fn add(&self, url: String, secret: String, events: Vec<ChangeKind>, since: u64) -> Subscription {
+        let mut store = self.store.write().unwrap();
+        let id = store.subscriptions.iter().map(|sub| sub.id).max().unwrap_or(0) + 1;
+        let subscription = Subscription { id, url, secret, events, created_at: Utc::now() };
+        store.subscriptions.push(subscription.clone());
+        store.cursors.insert(id, since);
+        self.save(&store);
+        self.changed.notify_one();
+        subscription
+    }
+
+    fn remove(&self, id: u32) -> bool {
+        let mut store = self.store.write().unwrap();
+        let len_before = store.subscriptions.len();
+        store.subscriptions.retain(|sub| sub.id != id);
+        let removed = store.subscriptions.len() < len_before;
+        if removed {
+            store.cursors.remove(&id);
+            self.save(&store);
+            self.changed.notify_one();
+        }
+        removed
+    }
+
+    fn take_dead_letter(&self, id: u32) -> Option<DeadLetter> {
+        let mut store = self.store.write().unwrap();
+        let position = store.dead_letters.iter().position(|letter| letter.id == id)?;
+        let letter = store.dead_letters.remove(position);
+        self.save(&store);
+        Some(letter)
+    }
+
+    fn push_dead_letter(&self, subscription_id: u32, event: ChangeEvent, attempts: u32, last_error: String) {
+        let mut store = self.store.write().unwrap();
+        let id = store.dead_letters.iter().map(|letter| letter.id).max().unwrap_or(0) + 1;
+        tracing::warn!("☠️ Webhook {} gave up on event {}: {}", subscription_id, event.id, last_error);
+        store.dead_letters.push(DeadLetter {
+            id,
+            subscription_id,
+            event,
+            attempts,
+            last_error,
+            failed_at: Utc::now(),
+        });
+        self.save(&store);
+    }
+
+    fn log_delivery(&self, record: DeliveryRecord) {
+        if let Some(path) = &self.log_path {
+            if let Err(err) = append_jsonl(path, &record) {
+                tracing::error!("💥 Failed to write delivery log: {}", err);
+            }
+        }
+        let mut deliveries = self.deliveries.write().unwrap();
+        if deliveries.len() == DELIVERY_LOG_SIZE {
+            deliveries.pop_front();
+        }
+        deliveries.push_back(record);
+    }
+
+    // None once the subscription is gone
+    fn cursor(&self, id: u32) -> Option<u64> {
+        let store = self.store.read().unwrap();
+        store.subscriptions.iter().any(|sub| sub.id == id).then(|| *store.cursors.get(&id).unwrap_or(&store.cursor))
+    }
+
+    fn advance_cursor(&self, id: u32, seq: u64) {
+        let mut store = self.store.write().unwrap();
+        let current = *store.cursors.get(&id).unwrap_or(&store.cursor);
+        if seq > current && store.subscriptions.iter().any(|sub| sub.id == id) {
+            store.cursors.insert(id, seq);
+            self.save(&store);
+        }
+    }
+
+    // Deliver this change if the subscription wants it, and wait until it has
+    // succeeded or been dead-lettered. Only then does the subscription's
+    // cursor move, so a restart part way redelivers the change instead of
+    // losing it, and changes arrive in order. False once the subscription is gone.
+    async fn dispatch(self: &Arc<Self>, id: u32, entry: &AuditEntry) -> bool {
+        let Some(subscription) = self.subscriptions().into_iter().find(|sub| sub.id == id) else {
+            return false;
+        };
+        match ChangeEvent::from_audit(entry) {
+            Some(event) if subscription.wants(event.kind) => self.clone().deliver(subscription, event).await,
+            _ => {}
+        }
+        self.advance_cursor(id, entry.seq);
+        true
+    }
+
+    // Follow the audit log for one subscription from its own cursor, so a
+    // slow or failing receiver only ever holds up itself
+    async fn follow(self: Arc<Self>, audit: Arc<AuditLog>, id: u32) {
+        while let Some(cursor) = self.cursor(id) {
+            let (backlog, mut receiver) = audit.subscribe(Some(cursor));
+            for entry in &backlog {
+                if !self.dispatch(id, entry).await {
+                    return;
+                }
+            }
+            loop {
+                match receiver.recv().await {
+                    Ok(entry) if self.dispatch(id, &entry).await => {}
+                    Ok(_) => return,
+                    // Fell behind while delivering: catch up from the cursor again
+                    Err(RecvError::Lagged(_)) => break,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        }
+    }
+
+    // POST the signed event, retrying with exponential backoff
+    async fn deliver(self: Arc<Self>, subscription: Subscription, event: ChangeEvent) {
+        let body = serde_json::to_vec(&event).expect("change events always serialize");
+        let signature = sign(&subscription.secret, &body);
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let settings = self.settings.read().unwrap().clone();
+        let client = self.client.read().unwrap().clone();
+        let mut backoff = Duration::from_millis(settings.initial_backoff_ms);
+
+        // Subscriptions saved before the address was refused never get a request
+        if let Err(reason) = check_target(&subscription.url, settings.allow_private_targets) {
+            self.push_dead_letter(subscription.id, event, 0, reason.to_string());
+            return;
+        }
+
+        let mut last_error = String::new();
+        for attempt in 1..=settings.max_attempts {
+            let result = client
+                .post(&subscription.url)
+                .timeout(Duration::from_secs(settings.timeout_secs))
+                .header("content-type", "application/json")
+                .header("x-webhook-delivery", &delivery_id)
+                .header("x-webhook-event", event.kind.as_str())
+                .header("x-webhook-attempt", attempt.to_string())
+                .header("x-signature-256", format!("sha256={}", signature))
+                .body(body.clone())
+                .send()
+                .await;
+
+            let (status, error) = match result {
+                Ok(response) if response.status().is_success() => (Some(response.status().as_u16()), None),
+                Ok(response) => (
+                    Some(response.status().as_u16()),
+                    Some(format!("receiver answered {}", response.status())),
+                ),
+                Err(err) => (None, Some(err.to_string())),
+            };
+
+            self.log_delivery(DeliveryRecord {
+                delivery_id: delivery_id.clone(),
+                subscription_id: subscription.id,
+                event_id: event.id,
+                attempt,
+                status,
+                error: error.clone(),
+                succeeded: error.is_none(),
+                at: Utc::now(),
+            });
+
+            match error {
+                None => return,
+                Some(error) => last_error = error,
+            }
+            if attempt < settings.max_attempts {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+
+        self.push_dead_letter(subscription.id, event, settings.max_attempts, last_error);
+    }
+
+    // Run a worker per subscription, each following the audit log from its
+    // saved cursor, so changes made while the server was down are still
+    // delivered after a restart.
+    pub fn spawn_dispatcher(
+        self: Arc<Self>,
+        audit: Arc<AuditLog>,
+        shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+    ) {
+        tokio::spawn(async move {
+            tokio::pin!(shutdown);
+            let mut workers: HashMap<u32, JoinHandle<()>> = HashMap::new();
+            loop {
+                let ids: Vec<u32> = self.subscriptions().iter().map(|sub| sub.id).collect();
+                workers.retain(|id, worker| {
+                    let wanted = ids.contains(id);
+                    if !wanted {
+                        worker.abort();
+                    }
+                    wanted
+                });
+                for id in ids {
+                    workers.entry(id).or_insert_with(|| tokio::spawn(self.clone().follow(audit.clone(), id)));
+                }
+
+                tokio::select! {
+                    _ = self.changed.notified() => {}
+                    // Unfinished deliveries are made again after a restart
+                    _ = &mut shutdown => {
+                        workers.values().for_each(JoinHandle::abort);
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+// Redirects are not followed, since they could lead anywhere, and unless
+// private targets are allowed host names must resolve to public addresses
+fn client(settings: &WebhookConfig) -> reqwest::Client {
+    let builder = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    let builder = if settings.allow_private_targets { builder } else { builder.dns_resolver(Arc::new(PublicOnly)) };
+    builder.build().expect("webhook client builds")
+}
+
+// Resolves host names, leaving out every address that is not public
+struct PublicOnly;
+
+impl Resolve for PublicOnly {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.filter(|addr| is_public(addr.ip())).collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            let shared = first == 100 && (64..128).contains(&second);
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || first == 0
+                || shared)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()),
+        },
+    }
+}
+
+// Only http(s) URLs, and unless allowed none naming a private address.
+// Host names are checked again when they are resolved.
+fn check_target(url: &str, allow_private: bool) -> Result<(), &'static str> {
+    let url = reqwest::Url::parse(url).map_err(|_| "🚫 A http(s) URL Is Required")?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("🚫 A http(s) URL Is Required");
+    }
+    let host = url.host_str().ok_or("🚫 A http(s) URL Is Required")?;
+    if allow_private {
+        return Ok(());
+    }
+    let private = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => !is_public(ip),
+        Err(_) => host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost"),
+    };
+    if private {
+        return Err("🚫 Webhook URL Points At A Private Address");
+    }
+    Ok(())
+}
+
+// Receivers recompute this over the raw body with their copy of the secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Register a webhook subscription; it receives every change, so it needs the admin key
+#[utoipa::path(post, path = "/webhooks", tag = "webhooks", request_body = CreateSubscription,
+    params(
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 201, description = "Subscription created, including its secret", body = Subscription),
+        (status = 400, description = "Missing or invalid URL, or a private address that is not allowed", body = String),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+    ))]
+pub async fn create_subscription(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(new_subscription): Json<CreateSubscription>,
+) -> impl IntoResponse {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let Some(url) = new_subscription.url else {
+        return (StatusCode::BAD_REQUEST, "🚫 A http(s) URL Is Required").into_response();
+    };
+    let allow_private = state.webhooks.settings.read().unwrap().allow_private_targets;
+    if let Err(reason) = check_target(&url, allow_private) {
+        return (StatusCode::BAD_REQUEST, reason).into_response();
+    }
+    let secret = new_subscription
+        .secret
+        .filter(|secret| !secret.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+    let since = state.audit.last_seq();
+    let subscription = state.webhooks.add(url, secret, new_subscription.events.unwrap_or_default(), since);
+    (StatusCode::CREATED, Json(subscription)).into_response()
+}
+
+/// List webhook subscriptions (secrets are not shown)
+#[utoipa::path(get, path = "/webhooks", tag = "webhooks",
+    responses((status = 200, description = "Registered subscriptions", body = Vec<SubscriptionSummary>)))]
+pub async fn list_subscriptions(State(state): State<AppState>) -> Json<Vec<SubscriptionSummary>> {
+    Json(state.webhooks.subscriptions().iter().map(Subscription::summary).collect())
+}
+
+/// Remove a webhook subscription; needs the admin key
+#[utoipa::path(delete, path = "/webhooks/{id}", tag = "webhooks",
+    params(
+        ("id" = u32, Path, description = "Subscription ID"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 200, description = "Subscription removed", body = String),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Subscription not found", body = String),
+    ))]
+pub async fn delete_subscription(Path(id): Path<u32>, State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    if state.webhooks.remove(id) {
+        (StatusCode::OK, "🗑️ Webhook Removed").into_response()
+    } else {
+        (StatusCode::NOT_FOUND, "❌ Webhook Not Found").into_response()
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeliveryParams {
+    /// Only show attempts for this subscription
+    pub subscription_id: Option<u32>,
+}
+
+/// Recent delivery attempts, oldest first
+#[utoipa::path(get, path = "/webhooks/deliveries", tag = "webhooks", params(DeliveryParams),
+    responses((status = 200, description = "Delivery log", body = Vec<DeliveryRecord>)))]
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Query(params): Query<DeliveryParams>,
+) -> Json<Vec<DeliveryRecord>> {
+    let deliveries = state.webhooks.deliveries();
+    Json(
+        deliveries
+            .into_iter()
+            .filter(|record| params.subscription_id.is_none_or(|id| record.subscription_id == id))
+            .collect(),
+    )
+}
+
+/// Events that exhausted their retries
+#[utoipa::path(get, path = "/webhooks/dead-letters", tag = "webhooks",
+    responses((status = 200, description = "Undelivered events", body = Vec<DeadLetter>)))]
+pub async fn list_dead_letters(State(state): State<AppState>) -> Json<Vec<DeadLetter>> {
+    Json(state.webhooks.dead_letters())
+}
+
+/// Try delivering a dead letter again; needs the admin key
+#[utoipa::path(post, path = "/webhooks/dead-letters/{id}/retry", tag = "webhooks",
+    params(
+        ("id" = u32, Path, description = "Dead letter ID"),
+        ("X-Admin-Key" = Option<String>, Header, description = "The configured admin key; refused when none is configured"),
+    ),
+    responses(
+        (status = 202, description = "Delivery restarted", body = String),
+        (status = 401, description = "Admin key missing or wrong", body = String),
+        (status = 404, description = "Dead letter or its subscription not found", body = String),
+    ))]
+pub async fn retry_dead_letter(Path(id): Path<u32>, State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if !state.libraries.is_admin(&headers) {
+        return (StatusCode::UNAUTHORIZED, "🔒 Missing Or Invalid Key").into_response();
+    }
+    let subscription_id = match state.webhooks.dead_letters().iter().find(|letter| letter.id == id) {
+        Some(letter) => letter.subscription_id,
+        None => return (StatusCode::NOT_FOUND, "❌ Dead Letter Not Found").into_response(),
+    };
+    let Some(subscription) = state.webhooks.subscriptions().into_iter().find(|sub| sub.id == subscription_id) else {
+        return (StatusCode::NOT_FOUND, "❌ Webhook Not Found").into_response();
+    };
+    let Some(letter) = state.webhooks.take_dead_letter(id) else {
+        return (StatusCode::NOT_FOUND, "❌ Dead Letter Not Found").into_response();
+    };
+
+    tokio::spawn(state.webhooks.clone().deliver(subscription, letter.event));
+    (StatusCode::ACCEPTED, "🔁 Delivery Restarted").into_response()
+}
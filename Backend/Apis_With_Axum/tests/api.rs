@@ -0,0 +1,350 @@
+mod common;
+
+use std::{collections::BTreeSet, fs};
+
+use apis_with_axum::{app, book::load_books_from_csv, config::Config, storage::Storage, AppState};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use common::TestApp;
+use serde_json::Value;
+use tower::ServiceExt;
+
+const DUNE: &str = r#"{"title":"Dune","author":"Frank Herbert"}"#;
+
+// ---------- Reading ----------
+
+#[tokio::test]
+async fn list_books_returns_the_whole_catalog() {
+    let app = TestApp::new();
+    let (status, books) = app.get_json("/books").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(books.as_array().unwrap().len(), 3);
+    assert_eq!(books[1]["title"], "Clean Code");
+}
+
+#[tokio::test]
+async fn get_book_by_id() {
+    let app = TestApp::new();
+    let (status, book) = app.get_json("/books/2").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(book["id"], 2);
+    assert_eq!(book["author"], "Robert C. Martin");
+}
+
+#[tokio::test]
+async fn get_missing_book_is_not_found() {
+    let app = TestApp::new();
+    let (status, body) = app.get("/books/99").await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(body.contains("Book Not Found"));
+}
+
+#[tokio::test]
+async fn non_numeric_id_is_rejected() {
+    let app = TestApp::new();
+    let (status, _) = app.get("/books/abc").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn search_is_case_insensitive() {
+    let app = TestApp::new();
+    let (status, books) = app.get_json("/books/search?title=RUST").await;
+
+    assert_eq!(status, StatusCode::OK);
+    let ids: Vec<u64> = books.as_array().unwrap().iter().map(|b| b["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![1, 3]);
+}
+
+#[tokio::test]
+async fn search_without_title_returns_everything() {
+    let app = TestApp::new();
+    let (_, books) = app.get_json("/books/search").await;
+    assert_eq!(books.as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn search_with_no_match_is_empty() {
+    let app = TestApp::new();
+    let (status, books) = app.get_json("/books/search?title=cobol").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(books, Value::Array(vec![]));
+}
+
+// ---------- Writing ----------
+
+#[tokio::test]
+async fn add_book_assigns_next_id_and_saves() {
+    let app = TestApp::new();
+    let (status, body) = app.send(Method::POST, "/books/new", Some(DUNE)).await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    let book: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(book["id"], 4);
+    assert!(app.saved_csv().contains("4,Dune,Frank Herbert"));
+}
+
+#[tokio::test]
+async fn add_book_requires_title_and_author() {
+    let app = TestApp::new();
+    let (status, body) = app.send(Method::POST, "/books/new", Some(r#"{"title":"Dune"}"#)).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Title & Author Required"));
+    assert_eq!(app.saved_csv(), common::SEED_CSV);
+}
+
+#[tokio::test]
+async fn add_book_rejects_malformed_json() {
+    let app = TestApp::new();
+    let (status, _) = app.send(Method::POST, "/books/new", Some("{not json")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app.send(Method::POST, "/books/new", Some(r#"{"title":42}"#)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn add_book_requires_json_content_type() {
+    let app = TestApp::new();
+    let request = Request::post("/books/new").body(Body::from(DUNE)).unwrap();
+    let (status, _) = app.request(request).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn add_book_into_empty_catalog_starts_at_one() {
+    let app = TestApp::with_csv("id,title,author\n");
+    let (status, body) = app.send(Method::POST, "/books/new", Some(DUNE)).await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["id"], 1);
+}
+
+#[tokio::test]
+async fn update_book_changes_title_and_author() {
+    let app = TestApp::new();
+    let (status, _) = app.send(Method::PUT, "/books/2", Some(DUNE)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, book) = app.get_json("/books/2").await;
+    assert_eq!(book["title"], "Dune");
+    assert!(app.saved_csv().contains("2,Dune,Frank Herbert"));
+}
+
+#[tokio::test]
+async fn update_missing_book_is_not_found() {
+    let app = TestApp::new();
+    let (status, _) = app.send(Method::PUT, "/books/99", Some(DUNE)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn update_book_requires_title_and_author() {
+    let app = TestApp::new();
+    let (status, _) = app.send(Method::PUT, "/books/2", Some(r#"{"author":"Nobody"}"#)).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.saved_csv(), common::SEED_CSV);
+}
+
+#[tokio::test]
+async fn delete_book_hides_it() {
+    let app = TestApp::new();
+    let (status, _) = app.send(Method::DELETE, "/books/2", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.get("/books/2").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Soft delete: the row stays on disk with a deletion timestamp
+    let saved = load_books_from_csv(&app.data_path).unwrap();
+    assert!(saved.iter().find(|book| book.id == 2).unwrap().is_deleted());
+}
+
+#[tokio::test]
+async fn delete_missing_book_is_not_found() {
+    let app = TestApp::new();
+    let (status, _) = app.send(Method::DELETE, "/books/99", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn failed_save_is_reported_as_server_error() {
+    let app = TestApp::new();
+    // A directory in place of the data file makes every save fail
+    fs::remove_file(&app.data_path).unwrap();
+    fs::create_dir(&app.data_path).unwrap();
+
+    let (status, _) = app.send(Method::POST, "/books/new", Some(DUNE)).await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (_, report) = app.get_json("/health/ready").await;
+    assert_eq!(report["storage"]["last_save"]["ok"], false);
+
+    // Nothing that failed to save is served either
+    assert_eq!(app.send(Method::PUT, "/books/1", Some(DUNE)).await.0, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(app.send(Method::DELETE, "/books/2", None).await.0, StatusCode::INTERNAL_SERVER_ERROR);
+    let (_, books) = app.get_json("/books").await;
+    assert_eq!(books.as_array().unwrap().len(), 3);
+    assert_eq!(books[0]["title"], "The Rust Programming Language");
+    assert!(app.get_json("/books/changes").await.1["changes"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn unknown_route_and_method_are_rejected() {
+    let app = TestApp::new();
+    let (status, _) = app.get("/authors").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = app.send(Method::PATCH, "/books/1", Some(DUNE)).await;
+    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn memory_storage_never_touches_disk() {
+    let state = AppState::load(Storage::Memory);
+    let router = app(state.clone(), &Config::default());
+    let request = Request::post("/books/new")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(DUNE))
+        .unwrap();
+
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(state.books.read().unwrap().len(), 1);
+}
+
+// ---------- Concurrency ----------
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_writers_get_unique_ids() {
+    let app = TestApp::new();
+
+    let mut tasks = Vec::new();
+    for n in 0..50 {
+        let router = app.router.clone();
+        tasks.push(tokio::spawn(async move {
+            let body = format!(r#"{{"title":"Book {n}","author":"Author {n}"}}"#);
+            let request = Request::post("/books/new")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            router.oneshot(request).await.unwrap().status()
+        }));
+    }
+    for task in tasks {
+        assert_eq!(task.await.unwrap(), StatusCode::CREATED);
+    }
+
+    let saved = load_books_from_csv(&app.data_path).unwrap();
+    let ids: BTreeSet<u32> = saved.iter().map(|book| book.id).collect();
+    assert_eq!(saved.len(), 53);
+    assert_eq!(ids, (1..=53).collect());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_updates_and_deletes_stay_consistent() {
+    let app = TestApp::new();
+
+    let mut tasks = Vec::new();
+    for n in 0..20 {
+        let router = app.router.clone();
+        tasks.push(tokio::spawn(async move {
+            let request = if n % 2 == 0 {
+                Request::put("/books/1")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(format!(r#"{{"title":"Edition {n}","author":"Steve"}}"#)))
+                    .unwrap()
+            } else {
+                Request::delete("/books/3").body(Body::empty()).unwrap()
+            };
+            router.oneshot(request).await.unwrap().status()
+        }));
+    }
+    let mut ok = 0;
+    for task in tasks {
+        if task.await.unwrap() == StatusCode::OK {
+            ok += 1;
+        }
+    }
+
+    // Every update succeeds, exactly one delete wins
+    assert_eq!(ok, 10 + 1);
+
+    let memory = app.state.books.read().unwrap().clone();
+    let saved = load_books_from_csv(&app.data_path).unwrap();
+    assert_eq!(saved.len(), memory.len());
+    assert_eq!(saved.iter().filter(|book| !book.is_deleted()).count(), 2);
+    assert!(saved[0].title.starts_with("Edition"));
+}
+
+// ---------- Health ----------
+
+#[tokio::test]
+async fn ping_is_alive() {
+    let app = TestApp::new();
+    let (status, body) = app.get("/ping").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("API is alive"));
+}
+
+#[tokio::test]
+async fn liveness_reports_version() {
+    let app = TestApp::new();
+    let (status, body) = app.get_json("/health/live").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "alive");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+}
+
+#[tokio::test]
+async fn readiness_reports_catalog_and_storage() {
+    let app = TestApp::new();
+    let (status, body) = app.get_json("/health/ready").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "ready");
+    assert_eq!(body["catalog_size"], 3);
+    assert_eq!(body["storage"]["backend"], "csv");
+    assert_eq!(body["storage"]["reachable"], true);
+    assert_eq!(body["storage"]["last_load"]["ok"], true);
+}
+
+#[tokio::test]
+async fn readiness_fails_when_data_file_could_not_be_loaded() {
+    let app = TestApp::without_data_file();
+    let (status, body) = app.get_json("/health/ready").await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["status"], "not_ready");
+    assert_eq!(body["storage"]["last_load"]["ok"], false);
+    assert!(body["storage"]["last_load"]["error"].is_string());
+
+    // Liveness is unaffected
+    let (status, _) = app.get("/health/live").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn saves_replace_the_data_file_whole() {
+    let app = TestApp::new();
+    // A directory where the temporary file would go stops the save before the data file is touched
+    let temp = app.dir.path().join(".books.csv.tmp");
+    fs::create_dir(&temp).unwrap();
+    assert_eq!(app.send(Method::POST, "/books/new", Some(DUNE)).await.0, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(load_books_from_csv(&app.data_path).unwrap().len(), 3);
+
+    fs::remove_dir(&temp).unwrap();
+    assert_eq!(app.send(Method::POST, "/books/new", Some(DUNE)).await.0, StatusCode::CREATED);
+    assert_eq!(load_books_from_csv(&app.data_path).unwrap().len(), 4);
+    assert!(!temp.exists());
+}
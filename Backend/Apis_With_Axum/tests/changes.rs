@@ -0,0 +1,111 @@
+mod common;
+
+use std::collections::BTreeMap;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::Value;
+
+const DUNE: &str = r#"{"title":"Dune","author":"Frank Herbert"}"#;
+
+#[tokio::test]
+async fn empty_log_returns_the_same_cursor() {
+    let app = TestApp::new();
+
+    let (status, page) = app.get_json("/books/changes").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page, serde_json::json!({ "changes": [], "cursor": 0, "has_more": false }));
+}
+
+#[tokio::test]
+async fn changes_are_ordered_with_tombstones_for_deletes() {
+    let app = TestApp::new();
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+    app.send(Method::PUT, "/books/4", Some(r#"{"title":"Dune Messiah","author":"Frank Herbert"}"#)).await;
+    app.send(Method::DELETE, "/books/2", None).await;
+
+    let (_, page) = app.get_json("/books/changes?since=0").await;
+    let changes = page["changes"].as_array().unwrap();
+    let kinds: Vec<&str> = changes.iter().map(|change| change["kind"].as_str().unwrap()).collect();
+    assert_eq!(kinds, vec!["created", "updated", "deleted"]);
+    assert_eq!(changes[1]["book"]["title"], "Dune Messiah");
+    assert_eq!(changes[2]["id"], 2);
+    assert!(changes[2].get("book").is_none(), "tombstones carry only the id");
+    assert_eq!(page["cursor"], 3);
+
+    // Nothing new since the cursor
+    let (_, page) = app.get_json("/books/changes?since=3").await;
+    assert_eq!(page["changes"], serde_json::json!([]));
+    assert_eq!(page["cursor"], 3);
+}
+
+#[tokio::test]
+async fn pages_follow_the_cursor() {
+    let app = TestApp::new();
+    for id in 1..=3 {
+        app.send(Method::DELETE, &format!("/books/{id}"), None).await;
+    }
+
+    let (_, first) = app.get_json("/books/changes?limit=2").await;
+    assert_eq!(first["changes"].as_array().unwrap().len(), 2);
+    assert_eq!(first["has_more"], true);
+
+    let (_, second) = app.get_json(&format!("/books/changes?since={}&limit=2", first["cursor"])).await;
+    assert_eq!(second["changes"][0]["id"], 3);
+    assert_eq!(second["has_more"], false);
+}
+
+#[tokio::test]
+async fn purges_advance_the_cursor_without_a_change() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::DELETE, "/books/1/purge", None).await;
+
+    let (_, page) = app.get_json("/books/changes?since=1").await;
+    assert_eq!(page["changes"], serde_json::json!([]));
+    assert_eq!(page["cursor"], 2);
+}
+
+#[tokio::test]
+async fn unknown_cursor_requires_a_resync() {
+    let app = TestApp::new();
+
+    let (status, _) = app.get("/books/changes?since=42").await;
+    assert_eq!(status, StatusCode::GONE);
+}
+
+// A snapshot then the feed after it reproduce the live catalog, including
+// books that were in the data file before the log began
+#[tokio::test]
+async fn replaying_the_feed_rebuilds_the_catalog() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/3", None).await;
+    let (_, page) = app.get_json("/books/changes?snapshot=true").await;
+    assert_eq!((&page["snapshot"], &page["cursor"], &page["has_more"]), (&Value::Bool(true), &Value::from(1), &Value::Bool(false)));
+    let mut local: BTreeMap<u64, Value> = page["changes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|change| (change["id"].as_u64().unwrap(), change["book"].clone()))
+        .collect();
+    assert_eq!(local.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+    let cursor = page["cursor"].clone();
+
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::PUT, "/books/2", Some(r#"{"title":"Clean Code 2","author":"Robert C. Martin"}"#)).await;
+    app.send(Method::POST, "/books/1/restore", None).await;
+
+    let (_, page) = app.get_json(&format!("/books/changes?since={cursor}")).await;
+    for change in page["changes"].as_array().unwrap() {
+        let id = change["id"].as_u64().unwrap();
+        match &change["book"] {
+            Value::Null => local.remove(&id),
+            book => local.insert(id, book.clone()),
+        };
+    }
+
+    let (_, books) = app.get_json("/books").await;
+    let local: Vec<Value> = local.into_values().collect();
+    assert_eq!(Value::Array(local), books);
+}
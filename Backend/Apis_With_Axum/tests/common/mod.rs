@@ -0,0 +1,153 @@
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+
+use apis_with_axum::{app, copies::CreateCopy, config::{Config, LoadingConfig}, storage::Storage, AppState};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+pub const SEED_CSV: &str = "id,title,author\n\
+1,The Rust Programming Language,Steve Klabnik and Carol Nichols\n\
+2,Clean Code,Robert C. Martin\n\
+3,Programming Rust,Jim Blandy\n";
+
+// Every test app is configured with this X-Admin-Key
+pub const ADMIN_KEY: &str = "sesame";
+
+// For driving Lending directly: every book has a single copy, ID 1
+pub const ONE_COPY: &[u32] = &[1];
+
+// A router wired to its own temp data file, never the real assets/books.csv
+pub struct TestApp {
+    pub dir: TempDir,
+    pub data_path: PathBuf,
+    pub state: AppState,
+    pub router: Router,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        Self::with_csv(SEED_CSV)
+    }
+
+    pub fn with_csv(contents: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let data_path = dir.path().join("books.csv");
+        fs::write(&data_path, contents).unwrap();
+        Self::from_storage(dir, data_path)
+    }
+
+    // The data file does not exist, so loading fails
+    pub fn without_data_file() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let data_path = dir.path().join("missing.csv");
+        Self::from_storage(dir, data_path)
+    }
+
+    // Seeded app whose state is adjusted before the router is built
+    pub fn customized(customize: impl FnOnce(AppState) -> AppState) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let data_path = dir.path().join("books.csv");
+        fs::write(&data_path, SEED_CSV).unwrap();
+        Self::build(dir, data_path, &Config::default(), customize)
+    }
+
+    // Seeded app behind the layers built from `config`
+    pub fn with_config(config: Config) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let data_path = dir.path().join("books.csv");
+        fs::write(&data_path, SEED_CSV).unwrap();
+        Self::build(dir, data_path, &config, |state| state)
+    }
+
+    // Started the way the server starts, with the bad-row threshold applied
+    pub fn opened(contents: &str, loading: &LoadingConfig) -> Result<Self, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let data_path = dir.path().join("books.csv");
+        fs::write(&data_path, contents).unwrap();
+        let state = AppState::open(Storage::Csv(data_path.clone()), loading)?;
+        let router = app(state.clone(), &Config::default());
+        Ok(TestApp { dir, data_path, state, router })
+    }
+
+    fn from_storage(dir: TempDir, data_path: PathBuf) -> Self {
+        Self::build(dir, data_path, &Config::default(), |state| state)
+    }
+
+    fn build(
+        dir: TempDir,
+        data_path: PathBuf,
+        config: &Config,
+        customize: impl FnOnce(AppState) -> AppState,
+    ) -> Self {
+        let state = AppState::load(Storage::Csv(data_path.clone())).with_library_admin_key(Some(ADMIN_KEY.to_string()));
+        let state = customize(state);
+        let router = app(state.clone(), config);
+        TestApp { dir, data_path, state, router }
+    }
+
+    // Registers `count` copies of a book so it can be lent
+    pub fn stock(&self, book_id: u32, count: usize) {
+        let today = chrono::Utc::now().date_naive();
+        for _ in 0..count {
+            let copy = CreateCopy { barcode: None, condition: None, location: None, acquired_on: None };
+            self.state.copies.add(book_id, copy, today).unwrap();
+        }
+    }
+
+    pub async fn request(&self, request: Request<Body>) -> (StatusCode, String) {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    pub async fn send(&self, method: Method, uri: &str, json: Option<&str>) -> (StatusCode, String) {
+        self.request(json_request(method, uri, json)).await
+    }
+
+    // Like `send`, with the admin key
+    pub async fn admin(&self, method: Method, uri: &str, json: Option<&str>) -> (StatusCode, String) {
+        let mut request = json_request(method, uri, json);
+        request.headers_mut().insert("x-admin-key", ADMIN_KEY.parse().unwrap());
+        self.request(request).await
+    }
+
+    // For streaming endpoints: the body is handed back unread
+    pub async fn response(&self, request: Request<Body>) -> Response {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    pub async fn get(&self, uri: &str) -> (StatusCode, String) {
+        self.send(Method::GET, uri, None).await
+    }
+
+    pub async fn get_json(&self, uri: &str) -> (StatusCode, Value) {
+        let (status, body) = self.get(uri).await;
+        (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+
+    pub fn saved_csv(&self) -> String {
+        fs::read_to_string(&self.data_path).unwrap()
+    }
+}
+
+pub fn json_request(method: Method, uri: &str, json: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder().method(method).uri(uri);
+    let body = match json {
+        Some(json) => {
+            builder = builder.header(header::CONTENT_TYPE, "application/json");
+            Body::from(json.to_string())
+        }
+        None => Body::empty(),
+    };
+    builder.body(body).unwrap()
+}
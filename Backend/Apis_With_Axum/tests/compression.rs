@@ -0,0 +1,147 @@
+mod common;
+
+use std::{io::{Read, Write}, time::Duration};
+
+use apis_with_axum::{config::Config, layers::http_layers};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    routing::get,
+    Router,
+};
+use common::TestApp;
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+// Big enough to cross the default 1 KiB compression threshold
+fn large_catalog() -> TestApp {
+    let mut csv = String::from("id,title,author\n");
+    for id in 1..=60 {
+        csv.push_str(&format!("{id},Book number {id},Author {id}\n"));
+    }
+    TestApp::with_csv(&csv)
+}
+
+async fn fetch_encoded(app: &TestApp, uri: &str, encoding: &str) -> (Option<String>, Vec<u8>) {
+    let request = Request::get(uri).header(header::ACCEPT_ENCODING, encoding).body(Body::empty()).unwrap();
+    let response = app.response(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_encoding = response
+        .headers()
+        .get(header::CONTENT_ENCODING)
+        .map(|value| value.to_str().unwrap().to_string());
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (content_encoding, bytes.to_vec())
+}
+
+async fn plain_books(app: &TestApp) -> Vec<u8> {
+    app.get("/books").await.1.into_bytes()
+}
+
+#[tokio::test]
+async fn gzip_response() {
+    let app = large_catalog();
+    let (encoding, body) = fetch_encoded(&app, "/books", "gzip").await;
+    assert_eq!(encoding.as_deref(), Some("gzip"));
+
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(&body[..]).read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, plain_books(&app).await);
+    assert!(body.len() < decoded.len());
+}
+
+#[tokio::test]
+async fn brotli_response() {
+    let app = large_catalog();
+    let (encoding, body) = fetch_encoded(&app, "/books", "br").await;
+    assert_eq!(encoding.as_deref(), Some("br"));
+
+    let mut decoded = Vec::new();
+    brotli::Decompressor::new(&body[..], 4096).read_to_end(&mut decoded).unwrap();
+    assert_eq!(decoded, plain_books(&app).await);
+}
+
+#[tokio::test]
+async fn zstd_response() {
+    let app = large_catalog();
+    let (encoding, body) = fetch_encoded(&app, "/books", "zstd").await;
+    assert_eq!(encoding.as_deref(), Some("zstd"));
+
+    let decoded = zstd::decode_all(&body[..]).unwrap();
+    assert_eq!(decoded, plain_books(&app).await);
+}
+
+#[tokio::test]
+async fn small_or_unrequested_responses_are_not_compressed() {
+    let app = large_catalog();
+    let (encoding, _) = fetch_encoded(&app, "/books/1", "gzip").await;
+    assert_eq!(encoding, None);
+
+    let (encoding, _) = fetch_encoded(&app, "/books", "identity").await;
+    assert_eq!(encoding, None);
+}
+
+#[tokio::test]
+async fn gzipped_request_bodies_are_accepted() {
+    let app = TestApp::new();
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(br#"{"title":"Dune","author":"Frank Herbert"}"#).unwrap();
+    let request = Request::post("/books/new")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_ENCODING, "gzip")
+        .body(Body::from(encoder.finish().unwrap()))
+        .unwrap();
+
+    let (status, body) = app.request(request).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let (_, book) = app.get_json("/books/4").await;
+    assert_eq!(book["title"], "Dune");
+}
+
+#[tokio::test]
+async fn oversized_bodies_are_rejected() {
+    let app = TestApp::new();
+    let title = "x".repeat(Config::default().limits.body_limit + 1);
+    let json = format!(r#"{{"title":"{title}","author":"Frank Herbert"}}"#);
+
+    let (status, _) = app.send(axum::http::Method::POST, "/books/new", Some(&json)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(app.saved_csv(), common::SEED_CSV);
+}
+
+// A small gzip body that inflates past the limit is still refused
+#[tokio::test]
+async fn decompressed_size_is_limited_too() {
+    let app = TestApp::new();
+    let title = "x".repeat(Config::default().limits.body_limit + 1);
+    let json = format!(r#"{{"title":"{title}","author":"Frank Herbert"}}"#);
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(json.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert!(compressed.len() < 10_000);
+
+    let request = Request::post("/books/new")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_ENCODING, "gzip")
+        .body(Body::from(compressed))
+        .unwrap();
+    let (status, _) = app.request(request).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn slow_requests_time_out() {
+    let mut config = Config::default();
+    config.limits.request_timeout_secs = 1;
+    let router = Router::new().route(
+        "/slow",
+        get(|| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            "done"
+        }),
+    );
+    let router = http_layers(router, &config);
+
+    let response = router.oneshot(Request::get("/slow").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+}
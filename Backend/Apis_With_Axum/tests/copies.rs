@@ -0,0 +1,193 @@
+mod common;
+
+use apis_with_axum::labels::{self, Label};
+use axum::http::{header, Method, StatusCode};
+use common::TestApp;
+use serde_json::Value;
+
+async fn add_copy(app: &TestApp, book_id: u32, json: &str) -> (StatusCode, Value) {
+    let (status, body) = app.send(Method::POST, &format!("/books/{book_id}/copies"), Some(json)).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+#[test]
+fn code128_checksums_and_code_sets() {
+    // Start B, then (104 + 48·1 + 42·2 + 42·3 + 17·4 + 18·5 + 19·6 + 35·7) mod 103 = 55
+    assert_eq!(labels::code128_symbols("PJJ123C").unwrap(), vec![104, 48, 42, 42, 17, 18, 19, 35, 55]);
+    // Even-length digit strings are packed in pairs with code set C: (105 + 12 + 68 + 168) mod 103 = 44
+    assert_eq!(labels::code128_symbols("123456").unwrap(), vec![105, 12, 34, 56, 44]);
+    assert!(labels::code128_symbols("café").is_none());
+    assert!(labels::code128_symbols("").is_none());
+
+    // Each symbol is 11 modules wide, the stop symbol 13, and it ends on a bar
+    let modules = labels::code128("PJJ123C").unwrap();
+    assert_eq!(modules.len(), 9 * 11 + 13);
+    assert!(modules[0] && modules[modules.len() - 1]);
+}
+
+#[test]
+fn label_sheets_are_svg_and_pdf() {
+    let batch: Vec<Label> = (0..31)
+        .map(|n| Label { barcode: format!("C-{n}"), caption: "Fish & <Chips>".to_string() })
+        .collect();
+
+    let svg = labels::svg_sheet(&batch);
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("Fish &amp; &lt;Chips&gt;"));
+    assert_eq!(svg.matches("<g ").count(), 31);
+
+    let pdf = labels::pdf_sheet(&batch);
+    assert!(pdf.starts_with(b"%PDF-"));
+    let text = String::from_utf8_lossy(&pdf);
+    assert!(text.contains("/Count 2"), "31 labels take two pages");
+    assert!(text.contains("(C-30)"));
+}
+
+#[test]
+fn barcodes_keep_their_quiet_zone() {
+    // Long enough that the label width, not the 1.5pt cap, sets the module,
+    // yet short enough that a fixed 9pt margin would be under ten modules
+    let svg = labels::svg_sheet(&[Label { barcode: "ABCDEFGH".to_string(), caption: String::new() }]);
+    let attribute = |rect: &str, name: &str| -> f32 {
+        let start = rect.find(&format!("{name}=\"")).unwrap() + name.len() + 2;
+        rect[start..].split('"').next().unwrap().parse().unwrap()
+    };
+    let bars: Vec<(f32, f32)> =
+        svg.lines().filter(|line| line.starts_with("<rect")).map(|rect| (attribute(rect, "x"), attribute(rect, "width"))).collect();
+    let module = bars.iter().map(|bar| bar.1).fold(f32::MAX, f32::min);
+    let (first, last) = (bars[0], bars[bars.len() - 1]);
+    assert!(module < 1.5);
+    assert!(first.0 >= 10.0 * module, "left quiet zone {} for module {module}", first.0);
+    assert!(189.0 - (last.0 + last.1) >= 10.0 * module, "right quiet zone for module {module}");
+}
+
+#[test]
+fn pdf_text_is_win_ansi() {
+    assert_eq!(labels::win_ansi("Café €5"), b"Caf\xe9 \x805");
+    assert_eq!(labels::win_ansi("‘Dune’ — “Herbert”…"), b"\x91Dune\x92 \x97 \x93Herbert\x94\x85");
+    assert_eq!(labels::win_ansi("\u{80}汉"), b"??");
+}
+
+#[tokio::test]
+async fn copies_are_tracked_per_book() {
+    let app = TestApp::new();
+
+    let (status, copy) =
+        add_copy(&app, 1, r#"{"barcode":"RPL-0001","location":"Stacks A","acquired_on":"2021-03-04"}"#).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(copy["condition"], "good");
+    assert_eq!(copy["acquired_on"], "2021-03-04");
+
+    // Barcodes are generated when not given
+    let (_, generated) = add_copy(&app, 1, r#"{"condition":"worn"}"#).await;
+    assert_eq!(generated["barcode"], "0000000002");
+
+    assert_eq!(add_copy(&app, 2, r#"{"barcode":"RPL-0001"}"#).await.0, StatusCode::CONFLICT);
+    assert_eq!(add_copy(&app, 2, r#"{"barcode":"ünïcode"}"#).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(add_copy(&app, 99, "{}").await.0, StatusCode::NOT_FOUND);
+
+    let (_, copies) = app.get_json("/books/1/copies").await;
+    assert_eq!(copies.as_array().unwrap().len(), 2);
+
+    let (status, body) = app.send(Method::PUT, "/copies/2", Some(r#"{"location":"Repair desk"}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    let updated: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((updated["location"].as_str(), updated["condition"].as_str()), (Some("Repair desk"), Some("worn")));
+
+    assert_eq!(app.send(Method::DELETE, "/copies/2", None).await.0, StatusCode::OK);
+    assert_eq!(app.get("/copies/2").await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn get_book_reports_availability() {
+    let app = TestApp::new();
+    let (_, book) = app.get_json("/books/1").await;
+    assert_eq!(book["title"], "The Rust Programming Language");
+    assert_eq!(book["availability"]["copies"], 0);
+
+    add_copy(&app, 1, "{}").await;
+    add_copy(&app, 1, "{}").await;
+    add_copy(&app, 1, r#"{"condition":"lost"}"#).await;
+    app.send(Method::POST, "/members", Some(r#"{"name":"Ada"}"#)).await;
+    app.send(Method::POST, "/members", Some(r#"{"name":"Grace"}"#)).await;
+    app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":1}"#)).await;
+
+    let (_, book) = app.get_json("/books/1").await;
+    assert_eq!(book["availability"], serde_json::json!({"copies":3,"circulating":2,"on_loan":1,"available":1}));
+    // Both circulating copies can be out at once; the lost one never
+    let (status, _) = app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":2}"#)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (_, book) = app.get_json("/books/1").await;
+    assert_eq!(book["availability"], serde_json::json!({"copies":3,"circulating":2,"on_loan":2,"available":0}));
+}
+
+#[tokio::test]
+async fn copies_on_loan_stay_in_the_inventory() {
+    let app = TestApp::new();
+    add_copy(&app, 1, "{}").await;
+    app.send(Method::POST, "/members", Some(r#"{"name":"Ada"}"#)).await;
+    app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":1}"#)).await;
+
+    assert_eq!(app.send(Method::DELETE, "/copies/1", None).await.0, StatusCode::CONFLICT);
+    let withdraw = Some(r#"{"condition":"withdrawn"}"#);
+    assert_eq!(app.send(Method::PUT, "/copies/1", withdraw).await.0, StatusCode::CONFLICT);
+    // Other changes still go through
+    assert_eq!(app.send(Method::PUT, "/copies/1", Some(r#"{"condition":"worn"}"#)).await.0, StatusCode::OK);
+
+    app.send(Method::POST, "/loans/1/return", None).await;
+    assert_eq!(app.send(Method::PUT, "/copies/1", withdraw).await.0, StatusCode::OK);
+    assert_eq!(app.send(Method::DELETE, "/copies/1", None).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn new_copies_go_to_the_first_waiting_hold() {
+    let app = TestApp::new();
+    add_copy(&app, 1, "{}").await;
+    for name in ["Ada", "Grace", "Linus"] {
+        app.send(Method::POST, "/members", Some(&format!(r#"{{"name":"{name}"}}"#))).await;
+    }
+    app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":1}"#)).await;
+    app.send(Method::POST, "/holds", Some(r#"{"book_id":1,"member_id":2}"#)).await;
+    app.send(Method::POST, "/holds", Some(r#"{"book_id":1,"member_id":3}"#)).await;
+
+    // A lost copy is not one anyone can collect
+    add_copy(&app, 1, r#"{"condition":"lost"}"#).await;
+    let (_, hold) = app.get_json("/holds/1").await;
+    assert_eq!(hold["status"], "waiting");
+
+    add_copy(&app, 1, "{}").await;
+    let (_, hold) = app.get_json("/holds/1").await;
+    assert_eq!(hold["status"], "ready");
+    let (status, _) = app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":3}"#)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Finding the lost copy sets it aside for the next in line
+    app.send(Method::PUT, "/copies/2", Some(r#"{"condition":"good"}"#)).await;
+    let (_, hold) = app.get_json("/holds/2").await;
+    assert_eq!(hold["status"], "ready");
+}
+
+#[tokio::test]
+async fn labels_render_for_a_batch() {
+    let app = TestApp::new();
+    add_copy(&app, 2, r#"{"barcode":"CC-1"}"#).await;
+    add_copy(&app, 3, r#"{"barcode":"PR-1"}"#).await;
+
+    let response = app
+        .response(common::json_request(Method::POST, "/copies/labels", Some(r#"{"copy_ids":[1,2]}"#)))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/svg+xml");
+
+    let (status, svg) = app.send(Method::POST, "/copies/labels", Some(r#"{"copy_ids":[1,2]}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(svg.contains("Clean Code") && svg.contains("PR-1"));
+
+    let response = app
+        .response(common::json_request(Method::POST, "/copies/labels", Some(r#"{"copy_ids":[2],"format":"pdf"}"#)))
+        .await;
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/pdf");
+
+    assert_eq!(app.send(Method::POST, "/copies/labels", Some(r#"{"copy_ids":[]}"#)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.send(Method::POST, "/copies/labels", Some(r#"{"copy_ids":[9]}"#)).await.0, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use apis_with_axum::config::{Config, CorsConfig};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    response::Response,
+};
+use common::TestApp;
+
+const WEB_APP: &str = "https://app.example.com";
+
+fn app_allowing(cors: CorsConfig) -> TestApp {
+    let config = Config { cors, ..Config::default() };
+    config.validate().unwrap();
+    TestApp::with_config(config)
+}
+
+fn web_app_cors() -> CorsConfig {
+    CorsConfig { allowed_origins: vec![WEB_APP.to_string()], ..CorsConfig::default() }
+}
+
+async fn preflight(app: &TestApp, origin: &str, method: &str, headers: &str) -> Response {
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/books/1")
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, headers)
+        .body(Body::empty())
+        .unwrap();
+    app.response(request).await
+}
+
+fn header_value(response: &Response, name: header::HeaderName) -> Option<&str> {
+    response.headers().get(name).map(|value| value.to_str().unwrap())
+}
+
+#[tokio::test]
+async fn preflight_for_put_and_delete_on_a_book() {
+    let app = app_allowing(web_app_cors());
+
+    for method in ["PUT", "DELETE"] {
+        let response = preflight(&app, WEB_APP, method, "content-type,x-actor").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), Some(WEB_APP));
+        let methods = header_value(&response, header::ACCESS_CONTROL_ALLOW_METHODS).unwrap();
+        assert!(methods.contains(method), "{methods}");
+        let headers = header_value(&response, header::ACCESS_CONTROL_ALLOW_HEADERS).unwrap();
+        assert!(headers.contains("x-actor"), "{headers}");
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_MAX_AGE), Some("600"));
+    }
+}
+
+#[tokio::test]
+async fn actual_requests_carry_the_allowed_origin() {
+    let app = app_allowing(web_app_cors());
+    let request = Request::delete("/books/1").header(header::ORIGIN, WEB_APP).body(Body::empty()).unwrap();
+
+    let response = app.response(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), Some(WEB_APP));
+}
+
+#[tokio::test]
+async fn unknown_origins_get_no_cors_headers() {
+    let app = app_allowing(web_app_cors());
+
+    let response = preflight(&app, "https://evil.example.com", "DELETE", "content-type").await;
+    assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), None);
+}
+
+#[tokio::test]
+async fn nothing_is_allowed_by_default() {
+    let app = TestApp::new();
+
+    let response = preflight(&app, WEB_APP, "PUT", "content-type").await;
+    assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), None);
+}
+
+#[tokio::test]
+async fn configured_methods_headers_and_credentials() {
+    let app = app_allowing(CorsConfig {
+        allowed_methods: vec!["GET".to_string()],
+        allowed_headers: vec!["authorization".to_string()],
+        allow_credentials: true,
+        max_age_secs: 60,
+        ..web_app_cors()
+    });
+
+    let response = preflight(&app, WEB_APP, "GET", "authorization").await;
+    assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_METHODS), Some("GET"));
+    assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_HEADERS), Some("authorization"));
+    assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_CREDENTIALS), Some("true"));
+    assert_eq!(header_value(&response, header::ACCESS_CONTROL_MAX_AGE), Some("60"));
+}
+
+#[test]
+fn credentials_cannot_use_wildcards() {
+    let config = Config {
+        cors: CorsConfig {
+            allowed_origins: vec!["*".to_string()],
+            allow_credentials: true,
+            ..CorsConfig::default()
+        },
+        ..Config::default()
+    };
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("cors.allow_credentials"), "{err}");
+}
+
+#[test]
+fn cors_settings_come_from_the_environment() {
+    let mut config = Config::default();
+    config
+        .apply_env(|var| match var {
+            "BOOKS_CORS_ALLOWED_ORIGINS" => Some(format!("{WEB_APP}, http://localhost:5173")),
+            "BOOKS_CORS_ALLOWED_METHODS" => Some("GET,PUT".to_string()),
+            "BOOKS_CORS_ALLOW_CREDENTIALS" => Some("true".to_string()),
+            "BOOKS_CORS_MAX_AGE_SECS" => Some("120".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(config.cors.allowed_origins, vec![WEB_APP, "http://localhost:5173"]);
+    assert_eq!(config.cors.allowed_methods, vec!["GET", "PUT"]);
+    assert!(config.cors.allow_credentials);
+    assert_eq!(config.cors.max_age_secs, 120);
+    config.validate().unwrap();
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use apis_with_axum::{
+    duplicates::{similarity, Merges},
+    storage::Storage,
+};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::{TestApp, ONE_COPY};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+fn day(n: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap() + Duration::days(n)
+}
+
+async fn add_book(app: &TestApp, title: &str, author: &str) {
+    let json = format!(r#"{{"title":"{title}","author":"{author}"}}"#);
+    assert_eq!(app.send(Method::POST, "/books/new", Some(&json)).await.0, StatusCode::CREATED);
+}
+
+async fn merge(app: &TestApp, keep: u32, merge: u32) -> (StatusCode, Value) {
+    let json = format!(r#"{{"keep":{keep},"merge":{merge}}}"#);
+    let (status, body) = app.admin(Method::POST, "/books/merge", Some(&json)).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+fn pairs(duplicates: &Value) -> Vec<(u64, u64, u64)> {
+    duplicates
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|pair| (pair["first"]["id"].as_u64().unwrap(), pair["second"]["id"].as_u64().unwrap(), pair["score"].as_u64().unwrap()))
+        .collect()
+}
+
+#[test]
+fn similarity_counts_edits() {
+    assert_eq!(similarity("dune", "dune"), 1.0);
+    assert_eq!(similarity("", ""), 1.0);
+    assert!((similarity("kitten", "sitting") - 4.0 / 7.0).abs() < 1e-9);
+    assert_eq!(similarity("abc", "xyz"), 0.0);
+}
+
+#[tokio::test]
+async fn near_duplicates_are_scored_and_explained() {
+    let app = TestApp::new();
+    add_book(&app, "Clean Code.", "Martin, Robert C.").await;
+    add_book(&app, "Clean Coder", "Robert Martin").await;
+
+    let (status, duplicates) = app.get_json("/books/duplicates").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(pairs(&duplicates), vec![(2, 4, 100), (2, 5, 89), (4, 5, 89)]);
+    assert_eq!(duplicates[0]["reasons"], json!(["Titles are 100% alike", "Authors are 100% alike"]));
+    assert!(duplicates[0]["same_isbn"].is_null());
+
+    let (_, duplicates) = app.get_json("/books/duplicates?min_score=95").await;
+    assert_eq!(pairs(&duplicates).len(), 1);
+    let (_, duplicates) = app.get_json("/books/duplicates?limit=2").await;
+    assert_eq!(pairs(&duplicates).len(), 2);
+    // The two Rust books share a word but little else
+    let (_, duplicates) = app.get_json("/books/duplicates?min_score=0").await;
+    assert!(pairs(&duplicates).iter().any(|(a, b, _)| (*a, *b) == (1, 3)));
+}
+
+#[tokio::test]
+async fn isbns_settle_or_split_a_pair() {
+    let app = TestApp::new();
+    add_book(&app, "The Rust Book", "Klabnik").await;
+    add_book(&app, "Clean Code", "Robert C. Martin").await;
+    app.send(Method::PUT, "/books/1/metadata", Some(r#"{"isbn":"9781593278281"}"#)).await;
+    app.send(Method::PUT, "/books/4/metadata", Some(r#"{"isbn":"978-1-59327-828-1"}"#)).await;
+    app.send(Method::PUT, "/books/2/metadata", Some(r#"{"isbn":"0132350882"}"#)).await;
+    app.send(Method::PUT, "/books/5/metadata", Some(r#"{"isbn":"9780132350884"}"#)).await;
+
+    let (_, duplicates) = app.get_json("/books/duplicates?min_score=40").await;
+    assert_eq!(pairs(&duplicates), vec![(1, 4, 100), (2, 5, 50)]);
+    assert_eq!(duplicates[0]["same_isbn"], true);
+    assert_eq!(duplicates[0]["reasons"][0], "Same ISBN 9781593278281");
+    assert_eq!(duplicates[1]["reasons"][2], "Different ISBNs");
+}
+
+#[tokio::test]
+async fn merging_moves_everything_and_redirects_the_old_id() {
+    let app = TestApp::new();
+    add_book(&app, "Clean Code.", "Martin, Robert C.").await;
+    let lending = &app.state.lending;
+    let ada = lending.add_member("Ada".to_string(), None, day(0)).unwrap().id;
+    let grace = lending.add_member("Grace".to_string(), None, day(0)).unwrap().id;
+    lending.checkout(4, ada, ONE_COPY, None, day(0)).unwrap();
+    lending.place_hold(4, grace, 0, ONE_COPY, day(1)).unwrap();
+    app.send(Method::POST, "/books/4/copies", Some("{}")).await;
+    app.send(Method::POST, "/books/4/reviews", Some(&format!(r#"{{"member_id":{ada},"rating":5}}"#))).await;
+    app.send(Method::PUT, "/books/2/tags", Some(r#"{"tags":["craft"]}"#)).await;
+    app.send(Method::PUT, "/books/4/tags", Some(r#"{"tags":["classic"]}"#)).await;
+    app.send(Method::PUT, "/books/4/metadata", Some(r#"{"isbn":"0132350882","year":2008}"#)).await;
+
+    let (status, outcome) = merge(&app, 2, 4).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(outcome["book"]["title"], "Clean Code");
+    assert_eq!(
+        (&outcome["merged"], &outcome["loans"], &outcome["holds"], &outcome["copies"], &outcome["reviews"]),
+        (&json!(4), &json!(1), &json!(1), &json!(1), &json!(1))
+    );
+
+    let (status, _) = app.get("/books/4").await;
+    assert_eq!(status, StatusCode::PERMANENT_REDIRECT);
+    let response = app.response(common::json_request(Method::GET, "/books/4", None)).await;
+    assert_eq!(response.headers()[header::LOCATION], "2");
+    let (_, books) = app.get_json("/books").await;
+    assert!(books.as_array().unwrap().iter().all(|book| book["id"] != 4));
+
+    let (_, book) = app.get_json("/books/2").await;
+    assert_eq!((&book["availability"]["copies"], &book["availability"]["on_loan"]), (&json!(1), &json!(1)));
+    assert_eq!(book["review_count"], 1);
+    let (_, tags) = app.get_json("/books/2/tags").await;
+    assert_eq!(tags, json!(["classic", "craft"]));
+    let (_, metadata) = app.get_json("/books/2/metadata").await;
+    assert_eq!(metadata, json!({"isbn": "0132350882", "year": 2008}));
+    assert_eq!(lending.read(|data| data.hold_queue(2).len()), 1);
+
+    // Both books' changes show up under the kept one
+    let (_, history) = app.get_json("/books/2/history").await;
+    let merged = history.as_array().unwrap().iter().find(|entry| entry["action"] == "merged").unwrap();
+    assert_eq!((&merged["book_id"], &merged["merged_into"]), (&json!(4), &json!(2)));
+    assert!(history.as_array().unwrap().iter().any(|entry| entry["action"] == "created"));
+    // The merged book cannot come back
+    assert_eq!(app.send(Method::POST, "/books/4/revert/1", None).await.0, StatusCode::CONFLICT);
+    let (_, changes) = app.get_json("/books/changes").await;
+    assert_eq!(changes["changes"].as_array().unwrap().last().unwrap()["kind"], "deleted");
+
+    // Merging the survivor again keeps the old ID pointing at the newest home
+    add_book(&app, "Clean Code", "R. C. Martin").await;
+    assert_eq!(merge(&app, 5, 2).await.0, StatusCode::OK);
+    let response = app.response(common::json_request(Method::GET, "/books/4", None)).await;
+    assert_eq!(response.headers()[header::LOCATION], "5");
+}
+
+#[tokio::test]
+async fn merges_are_checked_first() {
+    let app = TestApp::new();
+    assert_eq!(merge(&app, 2, 2).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.admin(Method::POST, "/books/merge", Some(r#"{"keep":2}"#)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(merge(&app, 2, 99).await.0, StatusCode::NOT_FOUND);
+    app.send(Method::DELETE, "/books/3", None).await;
+    assert_eq!(merge(&app, 2, 3).await.0, StatusCode::NOT_FOUND);
+}
+
+fn merge_request(json: &str) -> Request<Body> {
+    let mut request = common::json_request(Method::POST, "/books/merge", Some(json));
+    request.headers_mut().insert("x-admin-key", common::ADMIN_KEY.parse().unwrap());
+    request
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn opposite_merges_at_once_keep_one_book() {
+    let app = TestApp::new();
+    let (first, second) = tokio::join!(
+        tokio::spawn({
+            let app = app.router.clone();
+            async move { app.oneshot(merge_request(r#"{"keep":1,"merge":3}"#)).await }
+        }),
+        tokio::spawn({
+            let app = app.router.clone();
+            async move { app.oneshot(merge_request(r#"{"keep":3,"merge":1}"#)).await }
+        }),
+    );
+    let mut statuses = [first.unwrap().unwrap().status(), second.unwrap().unwrap().status()];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::OK, StatusCode::NOT_FOUND]);
+
+    // Exactly one survives, and the other leads to it
+    let (_, books) = app.get_json("/books").await;
+    let ids: Vec<u64> = books.as_array().unwrap().iter().map(|book| book["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids.len(), 2);
+    let (kept, gone) = if ids.contains(&1) { (1, 3) } else { (3, 1) };
+    assert_eq!(app.state.merges.resolve(gone), Some(kept));
+    assert_eq!(app.state.merges.resolve(kept), None);
+}
+
+#[test]
+fn redirects_never_loop() {
+    let dir = tempfile::tempdir().unwrap();
+    let merges = Merges::open(&Storage::Csv(dir.path().join("books.csv")));
+    merges.record(2, 1).unwrap();
+    assert!(merges.record(1, 2).is_err());
+    assert!(merges.record(3, 2).is_err());
+    assert!(merges.record(3, 3).is_err());
+    assert_eq!(merges.resolve(1), None);
+}
+
+#[tokio::test]
+async fn merging_needs_the_admin_key() {
+    let app = TestApp::new();
+    let json = r#"{"keep":1,"merge":3}"#;
+    assert_eq!(app.send(Method::POST, "/books/merge", Some(json)).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/books/3").await.0, StatusCode::OK);
+
+    let app = TestApp::customized(|state| state.with_library_admin_key(None));
+    assert_eq!(app.admin(Method::POST, "/books/merge", Some(json)).await.0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn a_merge_that_cannot_be_saved_leaves_both_books() {
+    let app = TestApp::new();
+    std::fs::remove_file(&app.data_path).unwrap();
+    std::fs::create_dir(&app.data_path).unwrap();
+
+    assert_eq!(merge(&app, 1, 3).await.0, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(app.get("/books/3").await.0, StatusCode::OK);
+    assert_eq!(app.get_json("/books").await.1.as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn books_both_on_loan_merge_with_their_copies() {
+    let app = TestApp::new();
+    app.stock(1, 1);
+    app.stock(2, 1);
+    let ada = app.state.lending.add_member("Ada".to_string(), None, day(0)).unwrap().id;
+    for book in [1, 2] {
+        let json = format!(r#"{{"book_id":{book},"member_id":{ada}}}"#);
+        assert_eq!(app.send(Method::POST, "/loans", Some(&json)).await.0, StatusCode::CREATED);
+    }
+
+    assert_eq!(merge(&app, 1, 2).await.0, StatusCode::OK);
+    let (_, book) = app.get_json("/books/1").await;
+    assert_eq!(book["availability"], json!({"copies":2,"circulating":2,"on_loan":2,"available":0}));
+}
@@ -0,0 +1,281 @@
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use apis_with_axum::{
+    config::{Config, EmailConfig, LendingConfig, SmtpSecurity},
+    emails::{self, EmailStatus, ReminderKind, ReminderSummary},
+};
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::{TestApp, ONE_COPY};
+
+fn day(n: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap() + Duration::days(n)
+}
+
+#[derive(Debug, Clone)]
+struct Received {
+    from: String,
+    to: String,
+    data: String,
+}
+
+// A stand-in SMTP server on localhost that keeps what it is sent. The first
+// `busy` recipients are told to try later, and bounce@ addresses are refused.
+struct SmtpSink {
+    addr: SocketAddr,
+    received: Arc<Mutex<Vec<Received>>>,
+    busy: Arc<AtomicUsize>,
+}
+
+impl SmtpSink {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sink = SmtpSink {
+            addr: listener.local_addr().unwrap(),
+            received: Arc::default(),
+            busy: Arc::default(),
+        };
+        let (received, busy) = (sink.received.clone(), sink.busy.clone());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = serve(stream, &received, &busy);
+            }
+        });
+        sink
+    }
+
+    fn settings(&self) -> EmailConfig {
+        EmailConfig {
+            smtp_host: self.addr.ip().to_string(),
+            smtp_port: self.addr.port(),
+            security: SmtpSecurity::None,
+            from: "desk@library.test".to_string(),
+            initial_backoff_ms: 1,
+            ..EmailConfig::default()
+        }
+    }
+
+    fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+fn serve(stream: TcpStream, received: &Mutex<Vec<Received>>, busy: &AtomicUsize) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut envelope = (String::new(), String::new());
+    writer.write_all(b"220 sink ready\r\n")?;
+
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        let command = line.trim_end().to_string();
+        line.clear();
+        let reply: &[u8] = if command.starts_with("EHLO") {
+            b"250-sink\r\n250 8BITMIME\r\n"
+        } else if let Some(from) = command.strip_prefix("MAIL FROM:") {
+            envelope.0 = from.trim_matches(['<', '>']).to_string();
+            b"250 ok\r\n"
+        } else if let Some(to) = command.strip_prefix("RCPT TO:") {
+            envelope.1 = to.trim_matches(['<', '>']).to_string();
+            if envelope.1.starts_with("bounce@") {
+                b"550 no such mailbox\r\n"
+            } else if busy.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+                b"451 try again later\r\n"
+            } else {
+                b"250 ok\r\n"
+            }
+        } else if command == "DATA" {
+            writer.write_all(b"354 go ahead\r\n")?;
+            let mut data = String::new();
+            loop {
+                reader.read_line(&mut line)?;
+                if line == ".\r\n" {
+                    break;
+                }
+                data.push_str(line.strip_prefix('.').unwrap_or(&line));
+                line.clear();
+            }
+            line.clear();
+            // Undo quoted-printable soft line breaks
+            let data = data.replace("=\r\n", "");
+            let (from, to) = envelope.clone();
+            received.lock().unwrap().push(Received { from, to, data });
+            b"250 queued\r\n"
+        } else if command == "QUIT" {
+            writer.write_all(b"221 bye\r\n")?;
+            return Ok(());
+        } else {
+            b"500 unknown command\r\n"
+        };
+        writer.write_all(reply)?;
+    }
+    Ok(())
+}
+
+// Ada borrows book 2 on day 0, due on day 7
+fn desk(sink: &SmtpSink, email: &str) -> (TestApp, u32, u32) {
+    let settings = sink.settings();
+    let app = TestApp::customized(|state| {
+        state
+            .with_email_settings(settings)
+            .with_lending_settings(LendingConfig { loan_days: 7, ..LendingConfig::default() })
+    });
+    let lending = &app.state.lending;
+    let member = lending.add_member("Ada".to_string(), Some(email.to_string()), day(0)).unwrap().id;
+    let loan = lending.checkout(2, member, ONE_COPY, None, day(0)).unwrap().id;
+    (app, member, loan)
+}
+
+#[tokio::test]
+async fn reminders_go_out_a_day_before_and_once_overdue() {
+    let sink = SmtpSink::start();
+    let (app, member, loan) = desk(&sink, "ada@example.com");
+    let summary = |sent, failed, skipped| ReminderSummary { sent, failed, skipped };
+
+    assert_eq!(emails::send_reminders(&app.state, day(5)).await, summary(0, 0, 0));
+    assert_eq!(emails::send_reminders(&app.state, day(6) + Duration::hours(1)).await, summary(1, 0, 0));
+    // Already reminded about this due date
+    assert_eq!(emails::send_reminders(&app.state, day(6) + Duration::hours(2)).await, summary(0, 0, 0));
+    assert_eq!(emails::send_reminders(&app.state, day(10)).await, summary(1, 0, 0));
+    assert_eq!(emails::send_reminders(&app.state, day(11)).await, summary(0, 0, 0));
+
+    let received = sink.received();
+    assert_eq!(received.len(), 2);
+    assert_eq!((received[0].from.as_str(), received[0].to.as_str()), ("desk@library.test", "ada@example.com"));
+    assert!(received[0].data.contains("Subject: Reminder: \"Clean Code\" is due 2025-01-08\r\n"));
+    assert!(received[0].data.contains("Hello Ada,\r\n"));
+    assert!(received[1].data.contains("Subject: Overdue: \"Clean Code\"\r\n"));
+    // Three days late with one grace day at 25 cents a day
+    assert!(received[1].data.contains("is 3 day(s) late. The fine so far is 0.50."), "{}", received[1].data);
+
+    let log = app.state.emails.log();
+    assert_eq!(log.iter().map(|email| email.kind).collect::<Vec<_>>(), vec![ReminderKind::DueSoon, ReminderKind::Overdue]);
+    assert!(log.iter().all(|email| email.loan_id == loan && email.status == EmailStatus::Sent && email.attempts == 1));
+
+    let (status, listed) = app.get_json(&format!("/emails?member_id={}", member)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed.as_array().unwrap().len(), 2);
+    assert_eq!(listed[1]["kind"], "overdue");
+    let (_, listed) = app.get_json("/emails?member_id=99").await;
+    assert_eq!(listed.as_array().unwrap().len(), 0);
+
+    // The log survives a restart
+    let reopened = apis_with_axum::emails::Emails::open(&app.state.storage);
+    assert_eq!(reopened.log().len(), 2);
+}
+
+#[tokio::test]
+async fn a_renewal_earns_a_fresh_reminder() {
+    let sink = SmtpSink::start();
+    let (app, _, loan) = desk(&sink, "ada@example.com");
+
+    assert_eq!(emails::send_reminders(&app.state, day(6) + Duration::hours(1)).await.sent, 1);
+    app.state.lending.renew(loan, day(6) + Duration::hours(2)).unwrap();
+    assert_eq!(emails::send_reminders(&app.state, day(13) + Duration::hours(1)).await.sent, 1);
+    assert!(sink.received()[1].data.contains("is due 2025-01-15"));
+}
+
+#[tokio::test]
+async fn opted_out_members_are_skipped() {
+    let sink = SmtpSink::start();
+    let (app, member, _) = desk(&sink, "ada@example.com");
+
+    let path = format!("/members/{}/email-preferences", member);
+    let (status, body) = app.send(Method::PUT, &path, Some(r#"{"reminders":false}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#""email_opt_out":true"#));
+    assert_eq!(app.send(Method::PUT, &path, Some("{}")).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.send(Method::PUT, "/members/99/email-preferences", Some(r#"{"reminders":true}"#)).await.0, StatusCode::NOT_FOUND);
+
+    assert_eq!(emails::send_reminders(&app.state, day(10)).await.skipped, 1);
+    assert!(sink.received().is_empty());
+
+    app.send(Method::PUT, &path, Some(r#"{"reminders":true}"#)).await;
+    assert_eq!(emails::send_reminders(&app.state, day(10)).await.sent, 1);
+}
+
+#[tokio::test]
+async fn busy_servers_are_retried_and_refusals_are_not() {
+    let sink = SmtpSink::start();
+    let (app, _, _) = desk(&sink, "ada@example.com");
+
+    sink.busy.store(1, Ordering::SeqCst);
+    assert_eq!(emails::send_reminders(&app.state, day(10)).await.sent, 1);
+    assert_eq!(app.state.emails.log()[0].attempts, 2);
+
+    // Every attempt fails, so the reminder is logged as failed and tried again next run
+    let (app, _, _) = desk(&sink, "grace@example.com");
+    sink.busy.store(3, Ordering::SeqCst);
+    assert_eq!(emails::send_reminders(&app.state, day(10)).await.failed, 1);
+    let failed = app.state.emails.log()[0].clone();
+    assert_eq!((failed.status, failed.attempts), (EmailStatus::Failed, 3));
+    assert!(failed.error.unwrap().contains("451"));
+    assert_eq!(emails::send_reminders(&app.state, day(10)).await.sent, 1);
+
+    let (app, _, _) = desk(&sink, "bounce@example.com");
+    assert_eq!(emails::send_reminders(&app.state, day(10)).await.failed, 1);
+    assert_eq!(app.state.emails.log()[0].attempts, 1);
+}
+
+#[tokio::test]
+async fn the_reminder_job_fails_when_email_does() {
+    let sink = SmtpSink::start();
+    let settings = sink.settings();
+    let app = TestApp::customized(|state| state.with_email_settings(settings));
+    let lending = &app.state.lending;
+    let member = lending.add_member("Ada".to_string(), Some("bounce@example.com".to_string()), day(0)).unwrap().id;
+    lending.checkout(2, member, ONE_COPY, None, day(0)).unwrap();
+
+    let (status, body) = app.admin(Method::POST, "/jobs/overdue_reminders/run", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains(r#""outcome":"failed""#), "{body}");
+    assert!(body.contains("1 overdue loan(s) for 1 member(s); 0 reminder(s) sent, 1 failed"));
+}
+
+#[test]
+fn templates_only_take_known_placeholders() {
+    assert_eq!(
+        emails::render("{member}: {title} ({title})", &[("member", "Ada".to_string()), ("title", "Dune".to_string())]),
+        "Ada: Dune (Dune)"
+    );
+    // Values are never read as placeholders themselves
+    assert_eq!(
+        emails::render("{member} on {title}", &[("member", "{title}".to_string()), ("title", "Dune".to_string())]),
+        "{title} on Dune"
+    );
+    assert!(emails::check_template("Due {due_date}, fine {fine}").is_ok());
+    assert!(emails::check_template("Hi {name}").unwrap_err().contains("{name}"));
+    assert!(emails::check_template("Hi {member").is_err());
+
+    let mut config = Config::default();
+    config.email.overdue_body = "Late by {days}".to_string();
+    config.email.smtp_host = "smtp.example.com".to_string();
+    config.email.from = "nobody".to_string();
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("email.overdue_body: unknown placeholder '{days}'"), "{err}");
+    assert!(err.contains("email.from: 'nobody' is not an email address"), "{err}");
+}
+
+#[test]
+fn printed_config_hides_secrets() {
+    let mut config = Config::default();
+    config.email.username = Some("desk".to_string());
+    config.email.password = Some("hunter2".to_string());
+    config.libraries.admin_key = Some("sesame".to_string());
+
+    let printed = config.to_toml();
+    assert!(!printed.contains("hunter2") && !printed.contains("sesame"), "{printed}");
+    assert!(printed.contains(r#"password = "<redacted>""#) && printed.contains(r#"admin_key = "<redacted>""#));
+    assert!(printed.contains(r#"username = "desk""#));
+    // Unset secrets stay unset
+    assert!(!Config::default().to_toml().contains("<redacted>"));
+}
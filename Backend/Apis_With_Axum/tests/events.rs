@@ -0,0 +1,141 @@
+mod common;
+
+use std::time::Duration;
+
+use apis_with_axum::events::ChangeEvent;
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use common::TestApp;
+use futures_util::{SinkExt, StreamExt};
+use http_body_util::BodyExt;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const DUNE: &str = r#"{"title":"Dune","author":"Frank Herbert"}"#;
+
+// Reads SSE frames until `count` events have arrived
+async fn read_sse_events(body: &mut Body, count: usize) -> Vec<(String, String, ChangeEvent)> {
+    let mut buffer = String::new();
+    let mut events = Vec::new();
+
+    while events.len() < count {
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.frame())
+            .await
+            .expect("timed out waiting for an event")
+            .unwrap()
+            .unwrap();
+        if let Ok(data) = frame.into_data() {
+            buffer.push_str(std::str::from_utf8(&data).unwrap());
+        }
+
+        while let Some(end) = buffer.find("\n\n") {
+            let block: String = buffer.drain(..end + 2).collect();
+            let field = |name: &str| {
+                block
+                    .lines()
+                    .find_map(|line| line.strip_prefix(&format!("{name}:")))
+                    .map(|value| value.trim().to_string())
+            };
+            if let (Some(id), Some(kind), Some(data)) = (field("id"), field("event"), field("data")) {
+                events.push((id, kind, serde_json::from_str(&data).unwrap()));
+            }
+        }
+    }
+    events
+}
+
+async fn open_sse(app: &TestApp, last_event_id: Option<&str>) -> Body {
+    let mut request = Request::get("/books/events");
+    if let Some(id) = last_event_id {
+        request = request.header("last-event-id", id);
+    }
+    let response = app.response(request.body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/event-stream");
+    response.into_body()
+}
+
+#[tokio::test]
+async fn sse_pushes_created_updated_and_deleted() {
+    let app = TestApp::new();
+    let mut body = open_sse(&app, None).await;
+
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+    app.send(Method::PUT, "/books/4", Some(r#"{"title":"Dune Messiah","author":"Frank Herbert"}"#)).await;
+    app.send(Method::DELETE, "/books/4", None).await;
+
+    let events = read_sse_events(&mut body, 3).await;
+    let kinds: Vec<&str> = events.iter().map(|(_, kind, _)| kind.as_str()).collect();
+    assert_eq!(kinds, vec!["created", "updated", "deleted"]);
+    assert_eq!(events[1].2.book.title, "Dune Messiah");
+    assert_eq!(events[0].0, events[0].2.id.to_string());
+}
+
+#[tokio::test]
+async fn sse_resumes_after_last_event_id() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::DELETE, "/books/2", None).await;
+    app.send(Method::DELETE, "/books/3", None).await;
+
+    // The client saw event 1 before disconnecting
+    let mut body = open_sse(&app, Some("1")).await;
+    app.send(Method::POST, "/books/2/restore", None).await;
+
+    let events = read_sse_events(&mut body, 3).await;
+    let ids: Vec<u64> = events.iter().map(|(_, _, event)| event.id).collect();
+    assert_eq!(ids, vec![2, 3, 4]);
+    assert_eq!(events[2].1, "created");
+    assert_eq!(events[2].2.book.id, 2);
+}
+
+#[tokio::test]
+async fn purges_are_not_broadcast() {
+    let app = TestApp::new();
+    let mut body = open_sse(&app, None).await;
+
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::DELETE, "/books/1/purge", None).await;
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+
+    let events = read_sse_events(&mut body, 2).await;
+    let kinds: Vec<&str> = events.iter().map(|(_, kind, _)| kind.as_str()).collect();
+    assert_eq!(kinds, vec!["deleted", "created"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn websocket_replays_and_streams_changes() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/3", None).await;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let router = app.router.clone();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/books/ws?since=0"))
+        .await
+        .unwrap();
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+
+    let mut events = Vec::new();
+    while events.len() < 2 {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("timed out waiting for a message")
+            .unwrap()
+            .unwrap();
+        if let Message::Text(text) = message {
+            events.push(serde_json::from_str::<ChangeEvent>(&text).unwrap());
+        }
+    }
+
+    assert_eq!(events[0].id, 1);
+    assert_eq!(events[0].book.id, 3);
+    assert_eq!(events[1].id, 2);
+    assert_eq!(events[1].book.title, "Dune");
+
+    socket.send(Message::Close(None)).await.unwrap();
+}
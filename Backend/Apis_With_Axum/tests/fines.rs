@@ -0,0 +1,173 @@
+mod common;
+
+use apis_with_axum::{
+    config::{FinesConfig, LendingConfig},
+    fines::{self, CreateWaiver, EntryKind},
+    lending::{Lending, LendingError, Loan},
+    storage::Storage,
+};
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::{TestApp, ONE_COPY};
+use serde_json::Value;
+
+fn day(n: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap() + Duration::days(n)
+}
+
+const RATES: FinesConfig = FinesConfig { daily_rate_cents: 25, grace_days: 2, max_per_item_cents: 500 };
+
+fn loan_due(due: DateTime<Utc>, returned_at: Option<DateTime<Utc>>) -> Loan {
+    Loan {
+        id: 7,
+        book_id: 1,
+        copy_id: Some(1),
+        member_id: 1,
+        checked_out_at: due - Duration::days(14),
+        due_at: due,
+        renewals: 0,
+        returned_at,
+    }
+}
+
+// Book 1 is due on day 7
+fn desk(dir: &tempfile::TempDir) -> (Lending, u32, u32) {
+    let lending = Lending::open(&Storage::Csv(dir.path().join("books.csv")));
+    lending.configure(LendingConfig { loan_days: 7, ..LendingConfig::default() });
+    lending.configure_fines(RATES);
+    let member = lending.add_member("Ada".to_string(), None, day(0)).unwrap().id;
+    let loan = lending.checkout(1, member, ONE_COPY, None, day(0)).unwrap().id;
+    (lending, member, loan)
+}
+
+#[test]
+fn fines_follow_the_rate_grace_and_cap() {
+    let due = day(0);
+    let fine = |now| fines::assess(&loan_due(due, None), now, &RATES);
+
+    assert_eq!(fine(due).amount_cents, 0);
+    // Within the grace period nothing is charged
+    assert_eq!((fine(day(2)).days_late, fine(day(2)).amount_cents), (2, 0));
+    // After it, every late day is counted beyond the grace days
+    assert_eq!((fine(day(5)).charged_days, fine(day(5)).amount_cents), (3, 75));
+    // A started day counts as a whole day
+    assert_eq!(fine(day(5) + Duration::minutes(1)).days_late, 6);
+    let long = fine(day(100));
+    assert_eq!((long.amount_cents, long.capped), (500, true));
+
+    let uncapped = FinesConfig { max_per_item_cents: 0, ..RATES };
+    assert_eq!(fines::assess(&loan_due(due, None), day(100), &uncapped).amount_cents, 98 * 25);
+}
+
+#[test]
+fn returned_loans_stop_accruing() {
+    let returned = loan_due(day(0), Some(day(4)));
+    let at_return = fines::assess(&returned, day(4), &RATES);
+    assert_eq!(fines::assess(&returned, day(40), &RATES), at_return);
+    assert!(at_return.settled);
+    assert_eq!(at_return.amount_cents, 50);
+}
+
+#[test]
+fn late_returns_are_charged_to_the_ledger() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, member, loan) = desk(&dir);
+
+    let balance = lending.read(|data| data.balance(member, day(12), &RATES));
+    assert_eq!((balance.owed_cents, balance.accruing_cents), (0, 75));
+
+    lending.return_loan(loan, day(12)).unwrap();
+    let ledger = lending.read(|data| data.ledger_for(member));
+    assert_eq!(ledger.len(), 1);
+    assert_eq!((ledger[0].kind, ledger[0].amount_cents, ledger[0].loan_id), (EntryKind::Charge, 75, Some(loan)));
+
+    let balance = lending.read(|data| data.balance(member, day(30), &RATES));
+    assert_eq!((balance.owed_cents, balance.accruing_cents), (75, 0));
+
+    // An on-time return charges nothing
+    let on_time = lending.checkout(2, member, ONE_COPY, None, day(12)).unwrap();
+    lending.return_loan(on_time.id, day(19)).unwrap();
+    assert_eq!(lending.read(|data| data.ledger_for(member)).len(), 1);
+}
+
+#[test]
+fn payments_and_waivers_reduce_the_balance() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, member, loan) = desk(&dir);
+    lending.return_loan(loan, day(17)).unwrap(); // 10 days late, 8 charged: 200
+
+    lending.pay(member, 50, Some("cash".to_string()), day(18)).unwrap();
+    assert!(matches!(lending.pay(member, 500, None, day(18)), Err(LendingError::ExceedsBalance)));
+
+    let waiver = CreateWaiver { amount_cents: Some(100), loan_id: Some(loan), note: None };
+    lending.waive(member, waiver, day(18)).unwrap();
+    let balance = lending.read(|data| data.balance(member, day(18), &RATES));
+    assert_eq!(
+        (balance.charges_cents, balance.payments_cents, balance.waivers_cents, balance.owed_cents),
+        (200, 50, 100, 50)
+    );
+
+    // With no amount, the rest is forgiven
+    let rest = lending.waive(member, CreateWaiver { amount_cents: None, loan_id: None, note: None }, day(18)).unwrap();
+    assert_eq!(rest.amount_cents, 50);
+    assert_eq!(lending.read(|data| data.balance(member, day(18), &RATES)).owed_cents, 0);
+    let nothing_left = CreateWaiver { amount_cents: None, loan_id: None, note: None };
+    assert!(matches!(lending.waive(member, nothing_left, day(18)), Err(LendingError::ExceedsBalance)));
+}
+
+#[test]
+fn loan_waivers_stop_at_that_loans_charges() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, member, first) = desk(&dir);
+    let second = lending.checkout(2, member, ONE_COPY, None, day(0)).unwrap().id;
+    lending.return_loan(first, day(17)).unwrap(); // 200
+    lending.return_loan(second, day(12)).unwrap(); // 5 days late, 3 charged: 75
+
+    let waive = |amount_cents, loan_id| lending.waive(member, CreateWaiver { amount_cents, loan_id, note: None }, day(18));
+    // The member owes 275, but only 75 of it is for the second loan
+    assert!(matches!(waive(Some(100), Some(second)), Err(LendingError::ExceedsBalance)));
+    assert_eq!(waive(Some(50), Some(second)).unwrap().amount_cents, 50);
+    assert_eq!(waive(None, Some(second)).unwrap().amount_cents, 25);
+    assert!(matches!(waive(None, Some(second)), Err(LendingError::ExceedsBalance)));
+    assert_eq!(lending.read(|data| data.balance(member, day(18), &RATES)).owed_cents, 200);
+}
+
+#[tokio::test]
+async fn payments_and_waivers_need_the_admin_key() {
+    let app = TestApp::new();
+    app.send(Method::POST, "/members", Some(r#"{"name":"Ada"}"#)).await;
+    for path in ["/members/1/payments", "/members/1/waivers"] {
+        assert_eq!(app.send(Method::POST, path, Some(r#"{"amount_cents":5}"#)).await.0, StatusCode::UNAUTHORIZED);
+    }
+    assert!(app.state.lending.read(|data| data.ledger_for(1)).is_empty());
+
+    let app = TestApp::customized(|state| state.with_library_admin_key(None));
+    assert_eq!(app.admin(Method::POST, "/members/1/waivers", Some("{}")).await.0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn fines_over_http() {
+    let app = TestApp::new();
+    app.stock(1, 1);
+    app.send(Method::POST, "/members", Some(r#"{"name":"Ada"}"#)).await;
+    let (_, body) = app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":1}"#)).await;
+    let loan: Value = serde_json::from_str(&body).unwrap();
+
+    let (status, fine) = app.get_json(&format!("/loans/{}/fine", loan["id"])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((fine["amount_cents"].as_u64(), fine["settled"].as_bool()), (Some(0), Some(false)));
+
+    let (_, balance) = app.get_json("/members/1/balance").await;
+    assert_eq!(balance["owed_cents"], 0);
+    let (_, ledger) = app.get_json("/members/1/ledger").await;
+    assert!(ledger.as_array().unwrap().is_empty());
+
+    assert_eq!(app.admin(Method::POST, "/members/1/payments", Some("{}")).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        app.admin(Method::POST, "/members/1/payments", Some(r#"{"amount_cents":5}"#)).await.0,
+        StatusCode::CONFLICT
+    );
+    assert_eq!(app.admin(Method::POST, "/members/1/waivers", Some(r#"{"loan_id":99}"#)).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/members/9/balance").await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/loans/9/fine").await.0, StatusCode::NOT_FOUND);
+}
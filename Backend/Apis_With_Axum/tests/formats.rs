@@ -0,0 +1,154 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use common::TestApp;
+use http_body_util::BodyExt;
+
+async fn get_as(app: &TestApp, uri: &str, accept: &str) -> (StatusCode, String, String) {
+    let request = Request::get(uri).header(header::ACCEPT, accept).body(Body::empty()).unwrap();
+    let response = app.response(request).await;
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, content_type, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+async fn send_as(app: &TestApp, method: Method, uri: &str, content_type: &str, body: &str) -> (StatusCode, String) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    app.request(request).await
+}
+
+#[tokio::test]
+async fn json_is_the_default() {
+    let app = TestApp::new();
+
+    for accept in ["*/*", "application/json", ""] {
+        let (status, content_type, body) = get_as(&app, "/books", accept).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "application/json");
+        assert!(body.starts_with('['), "{accept:?} gave {body}");
+    }
+}
+
+#[tokio::test]
+async fn list_books_as_csv() {
+    let app = TestApp::new();
+
+    let (status, content_type, body) = get_as(&app, "/books", "text/csv").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/csv"));
+    let mut lines = body.lines();
+    assert_eq!(lines.next(), Some("id,title,author,deleted_at"));
+    assert_eq!(lines.next(), Some("1,The Rust Programming Language,Steve Klabnik and Carol Nichols,"));
+    assert_eq!(lines.count(), 2);
+}
+
+#[tokio::test]
+async fn get_and_search_as_xml() {
+    let app = TestApp::new();
+
+    let (status, content_type, body) = get_as(&app, "/books/2", "application/xml").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type, "application/xml");
+    assert!(body.starts_with("<book><id>2</id><title>Clean Code</title>"), "{body}");
+
+    let (_, _, body) = get_as(&app, "/books/search?title=rust", "text/xml").await;
+    assert!(body.starts_with("<books><book><id>1</id>"), "{body}");
+    assert_eq!(body.matches("<book>").count(), 2);
+}
+
+#[tokio::test]
+async fn quality_values_pick_the_preferred_format() {
+    let app = TestApp::new();
+
+    let (_, content_type, _) = get_as(&app, "/books", "application/json;q=0.5, text/csv").await;
+    assert!(content_type.starts_with("text/csv"));
+
+    let (_, content_type, _) = get_as(&app, "/books", "text/csv;q=0, application/xml;q=0.9").await;
+    assert_eq!(content_type, "application/xml");
+}
+
+#[tokio::test]
+async fn unsupported_accept_is_406() {
+    let app = TestApp::new();
+
+    let (status, _, _) = get_as(&app, "/books", "application/pdf").await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+    let (status, _, _) = get_as(&app, "/books/1", "text/csv;q=0").await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+    // Other text types are not CSV
+    for accept in ["text/html", "text/plain", "text/*"] {
+        let (status, _, _) = get_as(&app, "/books", accept).await;
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE, "{accept}");
+    }
+    let (_, content_type, _) = get_as(&app, "/books", "text/html, */*;q=0.1").await;
+    assert_eq!(content_type, "application/json");
+}
+
+#[tokio::test]
+async fn add_book_from_csv_body() {
+    let app = TestApp::new();
+
+    let (status, body) = send_as(&app, Method::POST, "/books/new", "text/csv", "title,author\nDune,Frank Herbert\n").await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let (_, book) = app.get_json("/books/4").await;
+    assert_eq!(book["title"], "Dune");
+    assert_eq!(book["author"], "Frank Herbert");
+}
+
+#[tokio::test]
+async fn update_book_from_xml_body() {
+    let app = TestApp::new();
+
+    let xml = "<book><title>Clean Architecture</title><author>Robert C. Martin</author></book>";
+    let (status, body) = send_as(&app, Method::PUT, "/books/2", "application/xml", xml).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (_, book) = app.get_json("/books/2").await;
+    assert_eq!(book["title"], "Clean Architecture");
+}
+
+#[tokio::test]
+async fn created_book_is_returned_in_the_accepted_format() {
+    let app = TestApp::new();
+    let request = Request::post("/books/new")
+        .header(header::CONTENT_TYPE, "application/xml")
+        .header(header::ACCEPT, "text/csv")
+        .body(Body::from("<book><title>Dune</title><author>Frank Herbert</author></book>"))
+        .unwrap();
+
+    let (status, body) = app.request(request).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body, "id,title,author,deleted_at\n4,Dune,Frank Herbert,\n");
+}
+
+#[tokio::test]
+async fn bad_bodies_are_rejected() {
+    let app = TestApp::new();
+
+    let (status, _) = send_as(&app, Method::POST, "/books/new", "application/yaml", "title: Dune").await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let (status, _) = send_as(&app, Method::POST, "/books/new", "text/csv", "title,author\n").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send_as(&app, Method::POST, "/books/new", "application/xml", "<book><title>Dune").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Parsed fine but the author is missing
+    let (status, body) = send_as(&app, Method::POST, "/books/new", "text/csv", "title\nDune\n").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("Title & Author Required"));
+    assert_eq!(app.saved_csv(), common::SEED_CSV);
+}
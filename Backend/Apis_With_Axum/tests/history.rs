@@ -0,0 +1,146 @@
+mod common;
+
+use apis_with_axum::{audit::AuditLog, storage::Storage};
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use common::TestApp;
+
+const DUNE: &str = r#"{"title":"Dune","author":"Frank Herbert"}"#;
+
+async fn send_as(app: &TestApp, actor: &str, method: Method, uri: &str, json: Option<&str>) -> StatusCode {
+    let mut builder = Request::builder().method(method).uri(uri).header("x-actor", actor);
+    if json.is_some() {
+        builder = builder.header(header::CONTENT_TYPE, "application/json");
+    }
+    let body = json.map(|json| Body::from(json.to_string())).unwrap_or_default();
+    app.request(builder.body(body).unwrap()).await.0
+}
+
+#[tokio::test]
+async fn every_change_is_recorded_with_actor_and_before_after() {
+    let app = TestApp::new();
+    assert_eq!(send_as(&app, "alice", Method::POST, "/books/new", Some(DUNE)).await, StatusCode::CREATED);
+    assert_eq!(
+        send_as(&app, "bob", Method::PUT, "/books/4", Some(r#"{"title":"Dune Messiah","author":"Frank Herbert"}"#)).await,
+        StatusCode::OK
+    );
+    assert_eq!(send_as(&app, "carol", Method::DELETE, "/books/4", None).await, StatusCode::OK);
+
+    let (status, history) = app.get_json("/books/4/history").await;
+    assert_eq!(status, StatusCode::OK);
+    let history = history.as_array().unwrap();
+    assert_eq!(history.len(), 3);
+
+    assert_eq!(history[0]["rev"], 1);
+    assert_eq!(history[0]["action"], "created");
+    assert_eq!(history[0]["actor"], "alice");
+    assert!(history[0]["before"].is_null());
+
+    assert_eq!(history[1]["action"], "updated");
+    assert_eq!(history[1]["actor"], "bob");
+    assert_eq!(history[1]["before"]["title"], "Dune");
+    assert_eq!(history[1]["after"]["title"], "Dune Messiah");
+
+    assert_eq!(history[2]["action"], "deleted");
+    assert!(history[2]["after"].is_null());
+}
+
+#[tokio::test]
+async fn missing_actor_header_is_anonymous() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/1", None).await;
+
+    let (_, history) = app.get_json("/books/1/history").await;
+    assert_eq!(history[0]["actor"], "anonymous");
+}
+
+#[tokio::test]
+async fn history_of_untouched_book_is_empty() {
+    let app = TestApp::new();
+    let (status, history) = app.get_json("/books/2/history").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(history.as_array().unwrap().is_empty());
+    assert_eq!(app.get("/books/99/history").await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn revert_restores_an_earlier_title() {
+    let app = TestApp::new();
+    app.send(Method::PUT, "/books/2", Some(DUNE)).await;
+    app.send(Method::PUT, "/books/2", Some(r#"{"title":"Children of Dune","author":"Frank Herbert"}"#)).await;
+
+    let status = send_as(&app, "dave", Method::POST, "/books/2/revert/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, book) = app.get_json("/books/2").await;
+    assert_eq!(book["title"], "Dune");
+    assert!(app.saved_csv().contains("2,Dune,Frank Herbert"));
+
+    let (_, history) = app.get_json("/books/2/history").await;
+    let last = &history.as_array().unwrap()[2];
+    assert_eq!(last["action"], "reverted");
+    assert_eq!(last["reverted_to"], 1);
+    assert_eq!(last["actor"], "dave");
+    assert_eq!(last["before"]["title"], "Children of Dune");
+}
+
+#[tokio::test]
+async fn revert_recreates_a_deleted_book_with_its_id() {
+    let app = TestApp::new();
+    app.send(Method::PUT, "/books/2", Some(DUNE)).await;
+    app.send(Method::DELETE, "/books/2", None).await;
+
+    let (status, _) = app.send(Method::POST, "/books/2/revert/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, books) = app.get_json("/books").await;
+    let ids: Vec<u64> = books.as_array().unwrap().iter().map(|b| b["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn revert_to_a_deletion_or_unknown_revision_fails() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/2", None).await;
+
+    let (status, _) = app.send(Method::POST, "/books/2/revert/1", None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, _) = app.send(Method::POST, "/books/2/revert/9", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn history_survives_a_restart() {
+    let app = TestApp::new();
+    app.send(Method::PUT, "/books/3", Some(DUNE)).await;
+
+    let reopened = AuditLog::open(&Storage::Csv(app.data_path.clone()));
+    let history = reopened.history(3);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].after.as_ref().unwrap().title, "Dune");
+}
+
+#[tokio::test]
+async fn a_torn_line_costs_only_itself() {
+    let app = TestApp::new();
+    app.send(Method::PUT, "/books/1", Some(DUNE)).await;
+    app.send(Method::PUT, "/books/2", Some(DUNE)).await;
+    app.send(Method::PUT, "/books/3", Some(DUNE)).await;
+
+    // Lose the middle entry, drop the first one's seq as older logs did and tear the end
+    let path = app.dir.path().join("books.audit.jsonl");
+    let log = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    let legacy = lines[0].replacen(r#""seq":1,"#, "", 1);
+    let torn = [legacy.as_bytes(), b"\n{\"seq\":2,\"bo\xff\n", lines[2].as_bytes(), b"\n{\"seq\""].concat();
+    std::fs::write(&path, torn).unwrap();
+
+    let reopened = AuditLog::open(&Storage::Csv(app.data_path.clone()));
+    let seqs: Vec<u64> = reopened.page(0, 10).iter().map(|entry| entry.seq).collect();
+    assert_eq!(seqs, vec![1, 3]);
+    assert_eq!(reopened.history(3)[0].after.as_ref().unwrap().title, "Dune");
+    assert_eq!(reopened.last_seq(), 3);
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use apis_with_axum::{
+    config::LendingConfig,
+    holds::HoldStatus,
+    lending::{Lending, LendingError},
+    storage::Storage,
+};
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::{TestApp, ONE_COPY};
+use serde_json::Value;
+
+fn day(n: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap() + Duration::days(n)
+}
+
+// A desk with book 1 out to the first member and three more members waiting
+fn desk(dir: &tempfile::TempDir) -> (Lending, Vec<u32>, u32) {
+    let lending = Lending::open(&Storage::Csv(dir.path().join("books.csv")));
+    lending.configure(LendingConfig { pickup_days: 2, ..LendingConfig::default() });
+    let members: Vec<u32> = ["Ada", "Grace", "Linus", "Barbara"]
+        .iter()
+        .map(|name| lending.add_member(name.to_string(), None, day(0)).unwrap().id)
+        .collect();
+    let loan = lending.checkout(1, members[0], ONE_COPY, None, day(0)).unwrap();
+    (lending, members, loan.id)
+}
+
+fn status_of(lending: &Lending, hold_id: u32) -> HoldStatus {
+    lending.read(|data| data.hold(hold_id).unwrap().status)
+}
+
+#[test]
+fn each_returned_copy_serves_one_holder() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, members, _) = desk(&dir);
+    const TWO_COPIES: &[u32] = &[1, 2];
+    let second = lending.checkout(1, members[1], TWO_COPIES, None, day(0)).unwrap();
+    assert_eq!(second.copy_id, Some(2));
+    assert!(matches!(lending.checkout(1, members[2], TWO_COPIES, None, day(0)), Err(LendingError::OnLoan)));
+    let linus = lending.place_hold(1, members[2], 0, TWO_COPIES, day(1)).unwrap().hold;
+    let barbara = lending.place_hold(1, members[3], 0, TWO_COPIES, day(1)).unwrap().hold;
+
+    lending.return_loan(second.id, day(2)).unwrap();
+    assert_eq!((status_of(&lending, linus.id), status_of(&lending, barbara.id)), (HoldStatus::Ready, HoldStatus::Waiting));
+    // The one free copy is Linus's
+    assert!(matches!(lending.checkout(1, members[3], TWO_COPIES, None, day(2)), Err(LendingError::Reserved)));
+    assert_eq!(lending.checkout(1, members[2], TWO_COPIES, None, day(2)).unwrap().copy_id, Some(2));
+}
+
+#[test]
+fn holds_queue_first_come_first_served_with_priority_overrides() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, members, _) = desk(&dir);
+
+    let grace = lending.place_hold(1, members[1], 0, ONE_COPY, day(1)).unwrap();
+    let linus = lending.place_hold(1, members[2], 0, ONE_COPY, day(2)).unwrap();
+    assert_eq!((grace.position, linus.position), (Some(1), Some(2)));
+
+    let barbara = lending.place_hold(1, members[3], 5, ONE_COPY, day(3)).unwrap();
+    assert_eq!(barbara.position, Some(1));
+    let order: Vec<u32> = lending.read(|data| data.hold_queue(1).iter().map(|hold| hold.member_id).collect());
+    assert_eq!(order, vec![members[3], members[1], members[2]]);
+
+    let linus = lending.set_hold_priority(linus.hold.id, 9, day(3)).unwrap();
+    assert_eq!(linus.position, Some(1));
+}
+
+#[test]
+fn a_returned_copy_is_reserved_for_the_first_holder() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, members, loan) = desk(&dir);
+    let grace = lending.place_hold(1, members[1], 0, ONE_COPY, day(1)).unwrap().hold;
+    lending.place_hold(1, members[2], 0, ONE_COPY, day(1)).unwrap();
+
+    lending.return_loan(loan, day(5)).unwrap();
+    let hold = lending.read(|data| data.hold(grace.id).cloned()).unwrap();
+    assert_eq!(hold.status, HoldStatus::Ready);
+    assert_eq!(hold.expires_at, Some(day(7)));
+
+    // Only the holder may take it
+    assert!(matches!(lending.checkout(1, members[2], ONE_COPY, None, day(6)), Err(LendingError::Reserved)));
+    lending.checkout(1, members[1], ONE_COPY, None, day(6)).unwrap();
+    assert_eq!(status_of(&lending, grace.id), HoldStatus::Fulfilled);
+}
+
+#[test]
+fn expired_reservations_roll_to_the_next_holder() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, members, loan) = desk(&dir);
+    let grace = lending.place_hold(1, members[1], 0, ONE_COPY, day(1)).unwrap().hold;
+    let linus = lending.place_hold(1, members[2], 0, ONE_COPY, day(1)).unwrap().hold;
+    let barbara = lending.place_hold(1, members[3], 0, ONE_COPY, day(1)).unwrap().hold;
+    lending.return_loan(loan, day(5)).unwrap();
+
+    // Grace's window ends on day 7, Linus's on day 9
+    assert_eq!(lending.expire_holds(day(6)).unwrap(), 0);
+    assert_eq!(lending.expire_holds(day(8)).unwrap(), 1);
+    assert_eq!(status_of(&lending, grace.id), HoldStatus::Expired);
+    let linus = lending.read(|data| data.queued(data.hold(linus.id).unwrap()));
+    assert_eq!(linus.hold.status, HoldStatus::Ready);
+    assert_eq!(linus.hold.expires_at, Some(day(9)));
+    assert_eq!(linus.position, Some(1));
+
+    // Nobody came for a while: every lapsed window is accounted for at once
+    lending.checkout(1, members[3], ONE_COPY, None, day(10)).unwrap();
+    assert_eq!(status_of(&lending, linus.hold.id), HoldStatus::Expired);
+    assert_eq!(status_of(&lending, barbara.id), HoldStatus::Fulfilled);
+}
+
+#[test]
+fn cancelling_a_reservation_passes_the_copy_on() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, members, loan) = desk(&dir);
+    let grace = lending.place_hold(1, members[1], 0, ONE_COPY, day(1)).unwrap().hold;
+    let linus = lending.place_hold(1, members[2], 0, ONE_COPY, day(1)).unwrap().hold;
+    lending.return_loan(loan, day(5)).unwrap();
+
+    lending.cancel_hold(grace.id, day(6)).unwrap();
+    let linus = lending.read(|data| data.hold(linus.id).cloned()).unwrap();
+    assert_eq!((linus.status, linus.ready_at), (HoldStatus::Ready, Some(day(6))));
+    assert!(matches!(lending.cancel_hold(grace.id, day(6)), Err(LendingError::HoldClosed)));
+}
+
+#[test]
+fn holds_are_only_for_books_someone_else_has() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, members, _) = desk(&dir);
+
+    assert!(matches!(lending.place_hold(2, members[1], 0, ONE_COPY, day(1)), Err(LendingError::Available)));
+    assert!(matches!(lending.place_hold(1, members[0], 0, ONE_COPY, day(1)), Err(LendingError::AlreadyHolding)));
+    lending.place_hold(1, members[1], 0, ONE_COPY, day(1)).unwrap();
+    assert!(matches!(lending.place_hold(1, members[1], 0, ONE_COPY, day(1)), Err(LendingError::AlreadyHolding)));
+    assert!(matches!(lending.place_hold(1, 99, 0, ONE_COPY, day(1)), Err(LendingError::MemberNotFound)));
+}
+
+#[test]
+fn loans_others_are_waiting_for_cannot_be_renewed() {
+    let dir = tempfile::tempdir().unwrap();
+    let (lending, members, loan) = desk(&dir);
+    lending.renew(loan, day(1)).unwrap();
+
+    let grace = lending.place_hold(1, members[1], 0, ONE_COPY, day(2)).unwrap().hold;
+    assert!(matches!(lending.renew(loan, day(2)), Err(LendingError::HoldsWaiting)));
+    lending.cancel_hold(grace.id, day(3)).unwrap();
+    lending.renew(loan, day(3)).unwrap();
+}
+
+#[tokio::test]
+async fn priorities_need_the_admin_key() {
+    let app = TestApp::new();
+    app.stock(1, 1);
+    for name in ["Ada", "Grace", "Linus"] {
+        app.send(Method::POST, "/members", Some(&format!(r#"{{"name":"{name}"}}"#))).await;
+    }
+    app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":1}"#)).await;
+
+    let jump = Some(r#"{"book_id":1,"member_id":2,"priority":5}"#);
+    assert_eq!(app.send(Method::POST, "/holds", jump).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.admin(Method::POST, "/holds", jump).await.0, StatusCode::CREATED);
+    app.send(Method::POST, "/holds", Some(r#"{"book_id":1,"member_id":3}"#)).await;
+
+    let raise = Some(r#"{"priority":9}"#);
+    assert_eq!(app.send(Method::PUT, "/holds/2/priority", raise).await.0, StatusCode::UNAUTHORIZED);
+    let (_, hold) = app.get_json("/holds/2").await;
+    assert_eq!(hold["position"], 2);
+    assert_eq!(app.admin(Method::PUT, "/holds/2/priority", raise).await.0, StatusCode::OK);
+    let (_, hold) = app.get_json("/holds/2").await;
+    assert_eq!(hold["position"], 1);
+
+    assert_eq!(app.send(Method::POST, "/loans/1/renew", None).await.0, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn queue_positions_over_http() {
+    let app = TestApp::new();
+    app.stock(1, 1);
+    for name in ["Ada", "Grace", "Linus"] {
+        let (status, _) = app.send(Method::POST, "/members", Some(&format!(r#"{{"name":"{name}"}}"#))).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    let (status, _) = app.send(Method::POST, "/loans", Some(r#"{"book_id":1,"member_id":1}"#)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, body) = app.send(Method::POST, "/holds", Some(r#"{"book_id":1,"member_id":2}"#)).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let first: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((first["position"].as_u64(), first["status"].as_str()), (Some(1), Some("waiting")));
+    let (_, body) = app.send(Method::POST, "/holds", Some(r#"{"book_id":1,"member_id":3}"#)).await;
+    let second: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(second["position"], 2);
+
+    let (_, queue) = app.get_json("/books/1/holds").await;
+    assert_eq!(queue.as_array().unwrap().len(), 2);
+    let (_, holds) = app.get_json("/members/3/holds").await;
+    assert_eq!(holds[0]["position"], 2);
+
+    let (status, _) = app.send(Method::DELETE, &format!("/holds/{}", first["id"]), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, hold) = app.get_json(&format!("/holds/{}", second["id"])).await;
+    assert_eq!(hold["position"], 1);
+    let (_, hold) = app.get_json(&format!("/holds/{}", first["id"])).await;
+    assert!(hold["position"].is_null());
+
+    assert_eq!(app.send(Method::POST, "/holds", Some(r#"{"book_id":99,"member_id":2}"#)).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.send(Method::POST, "/holds", Some(r#"{"member_id":2}"#)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.get("/holds/99").await.0, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use std::{fs, sync::mpsc, time::Duration as StdDuration};
+
+use apis_with_axum::{
+    config::{JobsConfig, TrashConfig},
+    cron::Schedule,
+    jobs::{JobKind, Outcome, Trigger},
+};
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, TimeZone, Utc};
+use common::TestApp;
+use serde_json::Value;
+
+fn at(text: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(text).unwrap().with_timezone(&Utc)
+}
+
+fn next(expression: &str, after: &str) -> DateTime<Utc> {
+    Schedule::parse(expression).unwrap().next_after(at(after)).unwrap()
+}
+
+#[test]
+fn cron_schedules_find_the_next_run() {
+    assert_eq!(next("*/15 * * * *", "2025-03-01T10:07:30Z"), at("2025-03-01T10:15:00Z"));
+    // Strictly after: a run at exactly 03:00 is followed by the next day's
+    assert_eq!(next("0 3 * * *", "2025-03-01T03:00:00Z"), at("2025-03-02T03:00:00Z"));
+    assert_eq!(next("@hourly", "2025-12-31T23:30:00Z"), at("2026-01-01T00:00:00Z"));
+    // Weekdays: 2025-03-01 is a Saturday
+    assert_eq!(next("30 9 * * 1-5", "2025-03-01T12:00:00Z"), at("2025-03-03T09:30:00Z"));
+    assert_eq!(next("0 0 * * 7", "2025-03-01T12:00:00Z"), at("2025-03-02T00:00:00Z"));
+    // With both day fields set, either one matches
+    assert_eq!(next("0 0 13 * 5", "2025-06-01T00:00:00Z"), at("2025-06-06T00:00:00Z"));
+    // ...but a stepped `*` still counts as unrestricted, so both must match
+    assert_eq!(next("0 0 13 * */2", "2025-06-01T00:00:00Z"), at("2025-07-13T00:00:00Z"));
+    assert_eq!(next("0 0 */10 * 1", "2025-06-01T00:00:00Z"), at("2025-07-21T00:00:00Z"));
+    assert_eq!(next("0 12 29 2 *", "2025-01-01T00:00:00Z"), at("2028-02-29T12:00:00Z"));
+    assert_eq!(next("5,10-12/2 * * * *", "2025-01-01T00:10:00Z"), at("2025-01-01T00:12:00Z"));
+
+    assert!(Schedule::parse("0 0 30 2 *").unwrap().next_after(at("2025-01-01T00:00:00Z")).is_none());
+    for invalid in ["", "* * * *", "60 * * * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+        assert!(Schedule::parse(invalid).is_err(), "{invalid:?} should not parse");
+    }
+}
+
+#[test]
+fn job_settings_decide_what_is_scheduled() {
+    let app = TestApp::new();
+    let jobs = JobsConfig { backup: String::new(), purge_trash: "0 * * * *".to_string(), ..JobsConfig::default() };
+    app.state.jobs.configure(jobs, TrashConfig::default());
+
+    let now = Utc.with_ymd_and_hms(2025, 1, 1, 7, 30, 0).unwrap();
+    let status = app.state.jobs.status(now);
+    assert_eq!(status[0].job, JobKind::Backup);
+    assert!(status[0].schedule.is_none() && status[0].next_run.is_none());
+
+    let (due_at, due) = app.state.jobs.next_due(now).unwrap();
+    assert_eq!((due_at, due), (Utc.with_ymd_and_hms(2025, 1, 1, 8, 0, 0).unwrap(), vec![
+        JobKind::PurgeTrash,
+        JobKind::OverdueReminders
+    ]));
+}
+
+#[tokio::test]
+async fn manual_runs_are_recorded() {
+    let app = TestApp::new();
+
+    let (status, body) = app.admin(Method::POST, "/jobs/purge_trash/run", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let run: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((run["outcome"].as_str(), run["trigger"].as_str()), (Some("succeeded"), Some("manual")));
+    assert_eq!(run["message"], "purged 0 book(s)");
+
+    let (_, run) = app.get_json("/jobs/runs").await;
+    assert_eq!(run.as_array().unwrap().len(), 1);
+    let (_, jobs) = app.get_json("/jobs").await;
+    assert_eq!(jobs[1]["job"], "purge_trash");
+    assert_eq!(jobs[1]["last_run"]["outcome"], "succeeded");
+    assert!(jobs[1]["next_run"].is_string());
+
+    let (status, body) = app.admin(Method::POST, "/jobs/overdue_reminders/run", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("0 overdue loan(s) for 0 member(s)"));
+    let (_, runs) = app.get_json("/jobs/runs?job=overdue_reminders").await;
+    assert_eq!(runs.as_array().unwrap().len(), 1);
+
+    assert_eq!(app.admin(Method::POST, "/jobs/nope/run", None).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn backups_copy_the_catalog_and_keep_the_newest() {
+    let app = TestApp::customized(|state| {
+        let jobs = JobsConfig { backups_kept: 2, ..JobsConfig::default() };
+        state.with_job_settings(jobs, TrashConfig::default())
+    });
+    app.send(Method::POST, "/members", Some(r#"{"name":"Ada"}"#)).await;
+
+    for _ in 0..3 {
+        let (status, body) = app.admin(Method::POST, "/jobs/backup/run", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let run: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(run["outcome"], "succeeded", "{body}");
+        tokio::time::sleep(StdDuration::from_millis(5)).await;
+    }
+
+    let backups_dir = app.dir.path().join("backups");
+    let mut backups: Vec<_> = fs::read_dir(&backups_dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    backups.sort();
+    assert_eq!(backups.len(), 2);
+    let newest = backups.last().unwrap();
+    assert_eq!(fs::read_to_string(newest.join("books.csv")).unwrap(), app.saved_csv());
+    assert!(newest.join("books.lending.json").exists());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn runs_never_overlap() {
+    let app = TestApp::new();
+    let state = app.state.clone();
+
+    // The purge needs the catalog's write lock, so a reader holds the first run open
+    let (locked_tx, locked_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let books = state.books.clone();
+    let reader = std::thread::spawn(move || {
+        let _guard = books.read().unwrap();
+        locked_tx.send(()).unwrap();
+        let _ = release_rx.recv();
+    });
+    locked_rx.recv().unwrap();
+
+    let first = tokio::spawn({
+        let state = state.clone();
+        async move { state.jobs.run(&state, JobKind::PurgeTrash, Trigger::Manual).await.map(|run| run.outcome) }
+    });
+    while !state.jobs.status(Utc::now())[1].running {
+        tokio::time::sleep(StdDuration::from_millis(5)).await;
+    }
+
+    assert_eq!(app.admin(Method::POST, "/jobs/purge_trash/run", None).await.0, StatusCode::CONFLICT);
+    assert!(state.jobs.run(&state, JobKind::PurgeTrash, Trigger::Scheduled).await.is_err());
+    release_tx.send(()).unwrap();
+    reader.join().unwrap();
+    assert_eq!(first.await.unwrap().unwrap(), Outcome::Succeeded);
+
+    let outcomes: Vec<Outcome> = state.jobs.runs(Some(JobKind::PurgeTrash)).iter().map(|run| run.outcome).collect();
+    assert_eq!(outcomes, vec![Outcome::Succeeded, Outcome::Skipped]);
+}
+
+#[tokio::test]
+async fn running_jobs_needs_the_admin_key() {
+    let app = TestApp::new();
+    assert_eq!(app.send(Method::POST, "/jobs/purge_trash/run", None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.admin(Method::POST, "/jobs/purge_trash/run", None).await.0, StatusCode::OK);
+
+    // Without a configured key nobody may run jobs
+    let app = TestApp::customized(|state| state.with_library_admin_key(None));
+    assert_eq!(app.admin(Method::POST, "/jobs/purge_trash/run", None).await.0, StatusCode::UNAUTHORIZED);
+}
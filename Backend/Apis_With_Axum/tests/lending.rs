@@ -0,0 +1,184 @@
+mod common;
+
+use apis_with_axum::{
+    config::LendingConfig,
+    lending::{Lending, LendingError},
+    storage::Storage,
+};
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::{TestApp, ONE_COPY};
+use serde_json::Value;
+
+fn day(n: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap() + Duration::days(n)
+}
+
+fn lending(dir: &tempfile::TempDir, settings: LendingConfig) -> Lending {
+    let lending = Lending::open(&Storage::Csv(dir.path().join("books.csv")));
+    lending.configure(settings);
+    lending
+}
+
+async fn add_member(app: &TestApp, name: &str) -> u64 {
+    let (status, body) = app.send(Method::POST, "/members", Some(&format!(r#"{{"name":"{name}"}}"#))).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    serde_json::from_str::<Value>(&body).unwrap()["id"].as_u64().unwrap()
+}
+
+async fn checkout(app: &TestApp, book_id: u32, member_id: u64) -> (StatusCode, Value) {
+    let json = format!(r#"{{"book_id":{book_id},"member_id":{member_id}}}"#);
+    let (status, body) = app.send(Method::POST, "/loans", Some(&json)).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn checkout_and_return_over_http() {
+    let app = TestApp::new();
+    app.stock(1, 1);
+    let ada = add_member(&app, "Ada").await;
+
+    let (status, loan) = checkout(&app, 1, ada).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(loan["book_id"], 1);
+    assert_eq!(loan["renewals"], 0);
+    assert!(loan["returned_at"].is_null());
+
+    let (_, loans) = app.get_json(&format!("/members/{ada}/loans")).await;
+    assert_eq!(loans.as_array().unwrap().len(), 1);
+
+    let id = loan["id"].as_u64().unwrap();
+    let (status, body) = app.send(Method::POST, &format!("/loans/{id}/return"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let returned: Value = serde_json::from_str(&body).unwrap();
+    assert!(returned["returned_at"].is_string());
+
+    let (status, _) = app.send(Method::POST, &format!("/loans/{id}/return"), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, loans) = app.get_json(&format!("/members/{ada}/loans")).await;
+    assert!(loans.as_array().unwrap().is_empty());
+
+    // Returned books can go out again
+    let (status, _) = checkout(&app, 1, ada).await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn each_copy_goes_out_once() {
+    let app = TestApp::new();
+    let ada = add_member(&app, "Ada").await;
+    let grace = add_member(&app, "Grace").await;
+    let linus = add_member(&app, "Linus").await;
+
+    // No copies, nothing to lend
+    assert_eq!(checkout(&app, 2, ada).await.0, StatusCode::CONFLICT);
+
+    app.stock(2, 2);
+    let (status, first) = checkout(&app, 2, ada).await;
+    assert_eq!((status, &first["copy_id"]), (StatusCode::CREATED, &serde_json::json!(1)));
+    let (status, second) = checkout(&app, 2, grace).await;
+    assert_eq!((status, &second["copy_id"]), (StatusCode::CREATED, &serde_json::json!(2)));
+    let (status, body) = app.send(Method::POST, "/loans", Some(&format!(r#"{{"book_id":2,"member_id":{linus}}}"#))).await;
+    assert_eq!((status, body.as_str()), (StatusCode::CONFLICT, "🚫 No Copy Is Free To Lend"));
+}
+
+#[tokio::test]
+async fn a_scanned_copy_is_the_one_lent() {
+    let app = TestApp::new();
+    let ada = add_member(&app, "Ada").await;
+    let grace = add_member(&app, "Grace").await;
+    app.stock(1, 2);
+    app.stock(2, 1);
+    app.send(Method::PUT, "/copies/2", Some(r#"{"condition":"lost"}"#)).await;
+    app.stock(1, 1);
+
+    let scan = |member: u64, copy: u32| format!(r#"{{"book_id":1,"member_id":{member},"copy_id":{copy}}}"#);
+    let (status, body) = app.send(Method::POST, "/loans", Some(&scan(ada, 4))).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["copy_id"], 4);
+    assert_eq!(app.send(Method::POST, "/loans", Some(&scan(grace, 4))).await.0, StatusCode::CONFLICT);
+    // Lost, and another book's copy
+    assert_eq!(app.send(Method::POST, "/loans", Some(&scan(grace, 2))).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.send(Method::POST, "/loans", Some(&scan(grace, 3))).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(checkout(&app, 1, grace).await.1["copy_id"], 1);
+}
+
+#[tokio::test]
+async fn checkout_checks_its_input() {
+    let app = TestApp::new();
+    let ada = add_member(&app, "Ada").await;
+
+    let (status, _) = app.send(Method::POST, "/loans", Some(r#"{"book_id":1}"#)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(checkout(&app, 99, ada).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(checkout(&app, 1, 99).await.0, StatusCode::NOT_FOUND);
+
+    let (status, _) = app.send(Method::POST, "/members", Some(r#"{"name":"  "}"#)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(app.get("/members/99").await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/members/99/loans").await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.send(Method::POST, "/loans/99/renew", None).await.0, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn members_are_limited_to_max_loans() {
+    let dir = tempfile::tempdir().unwrap();
+    let lending = lending(&dir, LendingConfig { max_loans_per_member: 2, ..LendingConfig::default() });
+    let member = lending.add_member("Ada".to_string(), None, day(0)).unwrap();
+
+    lending.checkout(1, member.id, ONE_COPY, None, day(0)).unwrap();
+    let second = lending.checkout(2, member.id, ONE_COPY, None, day(0)).unwrap();
+    assert!(matches!(lending.checkout(3, member.id, ONE_COPY, None, day(0)), Err(LendingError::LoanLimit)));
+
+    lending.return_loan(second.id, day(1)).unwrap();
+    lending.checkout(3, member.id, ONE_COPY, None, day(1)).unwrap();
+}
+
+#[test]
+fn renewals_extend_from_the_due_date_until_the_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let lending = lending(&dir, LendingConfig { loan_days: 7, max_renewals: 2, ..LendingConfig::default() });
+    let member = lending.add_member("Ada".to_string(), None, day(0)).unwrap();
+
+    let loan = lending.checkout(1, member.id, ONE_COPY, None, day(0)).unwrap();
+    assert_eq!(loan.due_at, day(7));
+
+    let loan = lending.renew(loan.id, day(3)).unwrap();
+    assert_eq!((loan.renewals, loan.due_at), (1, day(14)));
+    let loan = lending.renew(loan.id, day(13)).unwrap();
+    assert_eq!((loan.renewals, loan.due_at), (2, day(21)));
+    assert!(matches!(lending.renew(loan.id, day(14)), Err(LendingError::RenewalLimit)));
+}
+
+#[test]
+fn overdue_loans_cannot_be_renewed_and_are_listed_oldest_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let lending = lending(&dir, LendingConfig { loan_days: 7, ..LendingConfig::default() });
+    let member = lending.add_member("Ada".to_string(), None, day(0)).unwrap();
+
+    let late = lending.checkout(1, member.id, ONE_COPY, None, day(0)).unwrap();
+    let later = lending.checkout(2, member.id, ONE_COPY, None, day(2)).unwrap();
+    let returned = lending.checkout(3, member.id, ONE_COPY, None, day(0)).unwrap();
+    lending.return_loan(returned.id, day(6)).unwrap();
+
+    assert!(lending.overdue(day(7)).is_empty());
+    let overdue: Vec<u32> = lending.overdue(day(10)).iter().map(|loan| loan.id).collect();
+    assert_eq!(overdue, vec![late.id, later.id]);
+
+    assert!(matches!(lending.renew(late.id, day(10)), Err(LendingError::Overdue)));
+}
+
+#[test]
+fn loans_survive_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = lending(&dir, LendingConfig::default());
+    let member = first.add_member("Ada".to_string(), Some("ada@example.com".to_string()), day(0)).unwrap();
+    let loan = first.checkout(1, member.id, ONE_COPY, None, day(0)).unwrap();
+    drop(first);
+
+    let reopened = lending(&dir, LendingConfig::default());
+    let loans = reopened.read(|data| data.active_loans_for_member(member.id));
+    assert_eq!(loans.len(), 1);
+    assert_eq!(loans[0].id, loan.id);
+    assert!(matches!(reopened.checkout(1, member.id, ONE_COPY, None, day(1)), Err(LendingError::OnLoan)));
+}
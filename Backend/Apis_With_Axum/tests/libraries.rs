@@ -0,0 +1,191 @@
+mod common;
+
+use apis_with_axum::{app, config::Config, storage::Storage, AppState};
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use common::TestApp;
+use serde_json::Value;
+use tower::ServiceExt;
+
+const DUNE: &str = r#"{"title":"Dune","author":"Frank Herbert"}"#;
+
+async fn create_library(app: &TestApp, name: &str) -> String {
+    let (status, body) = app.admin(Method::POST, "/libraries", Some(&format!(r#"{{"name":"{name}"}}"#))).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let created: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(created["name"], name);
+    created["key"].as_str().unwrap().to_string()
+}
+
+fn with_key(method: Method, uri: &str, key: &str, json: Option<&str>) -> Request<Body> {
+    let mut request = common::json_request(method, uri, json);
+    request.headers_mut().insert("x-library-key", key.parse().unwrap());
+    request
+}
+
+async fn library_json(app: &TestApp, uri: &str, key: &str) -> (StatusCode, Value) {
+    let (status, body) = app.request(with_key(Method::GET, uri, key, None)).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn libraries_have_their_own_catalog_and_ids() {
+    let app = TestApp::new();
+    let alpha = create_library(&app, "alpha").await;
+    let beta = create_library(&app, "beta").await;
+
+    let (status, body) = app.request(with_key(Method::POST, "/libraries/alpha/books/new", &alpha, Some(DUNE))).await;
+    assert_eq!(status, StatusCode::CREATED, "{body}");
+    let (status, _) = app
+        .request(with_key(Method::POST, "/libraries/beta/books/new", &beta, Some(r#"{"title":"Emma","author":"Jane Austen"}"#)))
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (_, books) = library_json(&app, "/libraries/alpha/books", &alpha).await;
+    assert_eq!(books.as_array().unwrap().len(), 1);
+    assert_eq!(books[0]["id"], 1);
+    assert_eq!(books[0]["title"], "Dune");
+    let (_, book) = library_json(&app, "/libraries/beta/books/1", &beta).await;
+    assert_eq!(book["title"], "Emma");
+
+    // The main catalog is untouched
+    let (_, books) = app.get_json("/books").await;
+    assert_eq!(books.as_array().unwrap().len(), 3);
+    assert_eq!(app.saved_csv(), common::SEED_CSV);
+}
+
+#[tokio::test]
+async fn every_catalog_route_works_inside_a_library() {
+    let app = TestApp::new();
+    let key = create_library(&app, "alpha").await;
+    app.request(with_key(Method::POST, "/libraries/alpha/books/new", &key, Some(DUNE))).await;
+    app.request(with_key(Method::DELETE, "/libraries/alpha/books/1", &key, None)).await;
+
+    let (_, history) = library_json(&app, "/libraries/alpha/books/1/history", &key).await;
+    assert_eq!(history.as_array().unwrap().len(), 2);
+    let (_, trash) = library_json(&app, "/libraries/alpha/books/trash", &key).await;
+    assert_eq!(trash[0]["title"], "Dune");
+    let (_, changes) = library_json(&app, "/libraries/alpha/books/changes?since=1", &key).await;
+    assert_eq!(changes["changes"][0]["kind"], "deleted");
+}
+
+#[tokio::test]
+async fn access_requires_the_library_key() {
+    let app = TestApp::new();
+    let alpha = create_library(&app, "alpha").await;
+    let beta = create_library(&app, "beta").await;
+
+    let (status, _) = app.get("/libraries/alpha/books").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = library_json(&app, "/libraries/alpha/books", &beta).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = library_json(&app, "/libraries/gamma/books", &alpha).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn names_are_validated_and_unique() {
+    let app = TestApp::new();
+    create_library(&app, "team-a").await;
+
+    for body in [r#"{"name":"team-a"}"#, r#"{"name":"../etc"}"#, r#"{"name":"Team A"}"#, "{}"] {
+        let (status, _) = app.admin(Method::POST, "/libraries", Some(body)).await;
+        let expected = if body.contains("team-a") { StatusCode::CONFLICT } else { StatusCode::BAD_REQUEST };
+        assert_eq!(status, expected, "{body}");
+    }
+
+    let (_, listed) = app.admin(Method::GET, "/libraries", None).await;
+    let listed: Value = serde_json::from_str(&listed).unwrap();
+    assert_eq!(listed[0]["name"], "team-a");
+    assert_eq!(listed[0]["catalog_size"], 0);
+    assert!(listed[0].get("key").is_none());
+}
+
+#[tokio::test]
+async fn libraries_survive_a_restart() {
+    let app = TestApp::new();
+    let key = create_library(&app, "alpha").await;
+    app.request(with_key(Method::POST, "/libraries/alpha/books/new", &key, Some(DUNE))).await;
+    assert!(app.dir.path().join("libraries/alpha/books.csv").is_file());
+
+    let reopened = AppState::load(Storage::Csv(app.data_path.clone()));
+    let router = app_router(reopened);
+    let response = router.oneshot(with_key(Method::GET, "/libraries/alpha/books/1", &key, None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+fn app_router(state: AppState) -> axum::Router {
+    app(state, &Config::default())
+}
+
+#[tokio::test]
+async fn rotating_the_key_revokes_the_old_one() {
+    let app = TestApp::new();
+    let old = create_library(&app, "alpha").await;
+
+    let (status, body) = app.request(with_key(Method::POST, "/libraries/alpha/rotate-key", &old, None)).await;
+    assert_eq!(status, StatusCode::OK);
+    let new: Value = serde_json::from_str(&body).unwrap();
+    let new = new["key"].as_str().unwrap();
+
+    let (status, _) = library_json(&app, "/libraries/alpha/books", &old).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = library_json(&app, "/libraries/alpha/books", new).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn deleting_a_library_removes_its_data() {
+    let app = TestApp::new();
+    let key = create_library(&app, "alpha").await;
+
+    let (status, _) = app.send(Method::DELETE, "/libraries/alpha", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.request(with_key(Method::DELETE, "/libraries/alpha", &key, None)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = library_json(&app, "/libraries/alpha/books", &key).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(!app.dir.path().join("libraries/alpha").exists());
+}
+
+#[tokio::test]
+async fn admin_key_guards_management_and_opens_every_library() {
+    let app = TestApp::customized(|state| state.with_library_admin_key(Some("root".to_string())));
+
+    let (status, _) = app.send(Method::POST, "/libraries", Some(r#"{"name":"alpha"}"#)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.get("/libraries").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let mut request = common::json_request(Method::POST, "/libraries", Some(r#"{"name":"alpha"}"#));
+    request.headers_mut().insert("x-admin-key", "root".parse().unwrap());
+    let (status, _) = app.request(request).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let request = Request::get("/libraries/alpha/books").header("x-admin-key", "root").body(Body::empty()).unwrap();
+    let (status, _) = app.request(request).await;
+    assert_eq!(status, StatusCode::OK);
+    let request = Request::get("/libraries/alpha/books").header("x-admin-key", "toor").body(Body::empty()).unwrap();
+    assert_eq!(app.request(request).await.0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn management_is_closed_without_an_admin_key() {
+    let app = TestApp::customized(|state| state.with_library_admin_key(None));
+    assert_eq!(app.admin(Method::POST, "/libraries", Some(r#"{"name":"alpha"}"#)).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.admin(Method::GET, "/libraries", None).await.0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn paths_are_forwarded_as_sent() {
+    let app = TestApp::new();
+    let alpha = create_library(&app, "alpha").await;
+    app.request(with_key(Method::POST, "/libraries/alpha/books/new", &alpha, Some(DUNE))).await;
+
+    assert_eq!(library_json(&app, "/libraries/alpha/books/1", &alpha).await.0, StatusCode::OK);
+    // An encoded slash stays part of one segment
+    assert_eq!(library_json(&app, "/libraries/alpha/books%2F1", &alpha).await.0, StatusCode::NOT_FOUND);
+}
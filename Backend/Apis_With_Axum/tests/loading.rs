@@ -0,0 +1,152 @@
+mod common;
+
+use std::fs;
+
+use apis_with_axum::{
+    book::{load_books_leniently, RowError},
+    config::{Config, LoadFailure, LoadingConfig},
+    storage::Storage,
+    AppState,
+};
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+
+const MESSY_CSV: &str = "id,title,author\n\
+1,Dune,Frank Herbert\n\
+two,Neuromancer,William Gibson\n\
+3,Foundation\n\
+1,Dune Messiah,Frank Herbert\n\
+4,,Ursula K. Le Guin\n\
+5,Hyperion,Dan Simmons\n";
+
+fn loading(max_row_errors: usize, on_too_many_errors: LoadFailure) -> LoadingConfig {
+    LoadingConfig { max_row_errors, on_too_many_errors }
+}
+
+fn row_error(line: u64, column: Option<&str>, reason: &str) -> RowError {
+    RowError { line, column: column.map(str::to_string), reason: reason.to_string() }
+}
+
+#[test]
+fn bad_rows_are_reported_and_the_rest_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("books.csv");
+    fs::write(&path, MESSY_CSV).unwrap();
+
+    let load = load_books_leniently(&path).unwrap();
+    let ids: Vec<u32> = load.books.iter().map(|book| book.id).collect();
+    assert_eq!(ids, vec![1, 5]);
+    assert_eq!(
+        load.errors,
+        vec![
+            row_error(3, Some("id"), "invalid digit found in string"),
+            row_error(4, None, "expected 3 fields, found 2"),
+            row_error(5, Some("id"), "duplicate id 1 (first on line 2)"),
+            row_error(6, Some("title"), "title is empty"),
+        ]
+    );
+    assert_eq!(load.errors[0].to_string(), "line 3, column id: invalid digit found in string");
+    assert_eq!(load.errors[1].to_string(), "line 4: expected 3 fields, found 2");
+    assert_eq!(load.rejected.len(), 4);
+
+    fs::write(&path, b"id,title,author\n1,Dune,Frank Herbert\n2,\xff\xfe,Nobody\n").unwrap();
+    let load = load_books_leniently(&path).unwrap();
+    assert_eq!(load.books.len(), 1);
+    assert_eq!(load.errors, vec![row_error(3, Some("title"), "not valid UTF-8")]);
+}
+
+#[test]
+fn a_header_without_required_columns_fails_the_whole_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("books.csv");
+    fs::write(&path, "id,name,author\n1,Dune,Frank Herbert\n").unwrap();
+
+    let err = load_books_leniently(&path).unwrap_err();
+    assert!(err.to_string().contains("header is missing column(s) title"));
+}
+
+#[tokio::test]
+async fn skipped_rows_are_reported_and_set_aside_before_saving() {
+    let app = TestApp::with_csv(MESSY_CSV);
+    let (_, books) = app.get_json("/books").await;
+    assert_eq!(books.as_array().unwrap().len(), 2);
+
+    let (status, ready) = app.get_json("/health/ready").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ready["storage"]["read_only"], false);
+    assert_eq!(ready["storage"]["row_errors"].as_array().unwrap().len(), 4);
+    assert_eq!(ready["storage"]["row_errors"][1], json!({"line": 4, "column": null, "reason": "expected 3 fields, found 2"}));
+
+    // The first save drops the bad rows from the data file, so they are copied out
+    let rejected = app.dir.path().join("books.rejected.csv");
+    assert!(!rejected.exists());
+    let (status, _) = app.send(Method::POST, "/books/new", Some(r#"{"title":"Dune","author":"Frank Herbert"}"#)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(
+        fs::read_to_string(&rejected).unwrap(),
+        "id,title,author\n\
+two,Neuromancer,William Gibson\n\
+3,Foundation\n\
+1,Dune Messiah,Frank Herbert\n\
+4,,Ursula K. Le Guin\n"
+    );
+    assert!(!app.saved_csv().contains("Neuromancer"));
+
+    // Only once
+    app.send(Method::DELETE, "/books/5", None).await;
+    assert_eq!(fs::read_to_string(&rejected).unwrap().lines().count(), 5);
+}
+
+#[tokio::test]
+async fn too_many_bad_rows_refuse_to_start() {
+    let err = TestApp::opened(MESSY_CSV, &loading(3, LoadFailure::Refuse)).err().unwrap();
+    assert_eq!(err, "4 rows could not be loaded, more than the 3 allowed");
+    let err = TestApp::opened("id,name\n", &loading(3, LoadFailure::Refuse)).err().unwrap();
+    assert!(err.starts_with("Data file could not be read"));
+
+    // At the limit, and with no data file yet, it starts as usual
+    let app = TestApp::opened(MESSY_CSV, &loading(4, LoadFailure::Refuse)).unwrap();
+    assert!(!app.state.status.read().unwrap().read_only);
+    let dir = tempfile::tempdir().unwrap();
+    let storage = Storage::Csv(dir.path().join("missing.csv"));
+    assert!(AppState::open(storage, &loading(0, LoadFailure::Refuse)).is_ok());
+}
+
+#[tokio::test]
+async fn a_missing_data_file_starts_ready() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("books.csv");
+    let state = AppState::open(Storage::Csv(path.clone()), &loading(0, LoadFailure::Refuse)).unwrap();
+    let router = apis_with_axum::app(state.clone(), &Config::default());
+    let app = TestApp { dir, data_path: path.clone(), state, router };
+
+    let (status, ready) = app.get_json("/health/ready").await;
+    assert_eq!(status, StatusCode::OK, "{ready}");
+    assert_eq!((ready["catalog_size"].as_u64(), &ready["storage"]["last_load"]["ok"]), (Some(0), &json!(true)));
+
+    // And stays ready once the first save creates the file
+    app.send(Method::POST, "/books/new", Some(r#"{"title":"Dune","author":"Frank Herbert"}"#)).await;
+    assert!(path.exists());
+    assert_eq!(app.get("/health/ready").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn too_many_bad_rows_can_start_read_only() {
+    let app = TestApp::opened(MESSY_CSV, &loading(1, LoadFailure::ReadOnly)).unwrap();
+
+    let (status, books) = app.get_json("/books").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(books.as_array().unwrap().len(), 2);
+    let (_, ready) = app.get_json("/health/ready").await;
+    assert_eq!(ready["storage"]["read_only"], true);
+
+    let (status, body) = app.send(Method::POST, "/books/new", Some(r#"{"title":"Dune","author":"Frank Herbert"}"#)).await;
+    assert_eq!((status, body.as_str()), (StatusCode::SERVICE_UNAVAILABLE, "🔒 Catalog Is Read-Only"));
+    assert_eq!(app.send(Method::DELETE, "/books/1", None).await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+    // Nothing touches the data file, not even a shutdown flush
+    assert!(app.state.flush().is_ok());
+    assert_eq!(fs::read_to_string(&app.data_path).unwrap(), MESSY_CSV);
+    assert!(!app.dir.path().join("books.rejected.csv").exists());
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use common::{json_request, TestApp};
+use http_body_util::BodyExt;
+use serde_json::Value;
+
+async fn fetch_spec() -> Value {
+    let (status, spec) = TestApp::new().get_json("/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+    spec
+}
+
+fn operations(spec: &Value) -> BTreeSet<(String, String)> {
+    let mut operations = BTreeSet::new();
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for method in item.as_object().unwrap().keys() {
+            operations.insert((method.to_uppercase(), path.clone()));
+        }
+    }
+    operations
+}
+
+#[tokio::test]
+async fn spec_lists_every_public_route() {
+    let spec = fetch_spec().await;
+    let expected: BTreeSet<(String, String)> = [
+        ("GET", "/books"),
+        ("POST", "/books/new"),
+        ("GET", "/books/search"),
+        ("GET", "/books/{id}"),
+        ("PUT", "/books/{id}"),
+        ("DELETE", "/books/{id}"),
+        ("GET", "/books/{id}/history"),
+        ("POST", "/books/{id}/revert/{rev}"),
+        ("GET", "/books/trash"),
+        ("POST", "/books/{id}/restore"),
+        ("DELETE", "/books/{id}/purge"),
+        ("GET", "/books/changes"),
+        ("GET", "/books/events"),
+        ("GET", "/books/ws"),
+        ("POST", "/webhooks"),
+        ("GET", "/webhooks"),
+        ("DELETE", "/webhooks/{id}"),
+        ("GET", "/webhooks/deliveries"),
+        ("GET", "/webhooks/dead-letters"),
+        ("POST", "/webhooks/dead-letters/{id}/retry"),
+        ("POST", "/members"),
+        ("GET", "/members"),
+        ("GET", "/members/{id}"),
+        ("GET", "/members/{id}/loans"),
+        ("POST", "/loans"),
+        ("POST", "/loans/{id}/return"),
+        ("POST", "/loans/{id}/renew"),
+        ("GET", "/loans/overdue"),
+        ("GET", "/members/{id}/ledger"),
+        ("GET", "/members/{id}/balance"),
+        ("POST", "/members/{id}/payments"),
+        ("POST", "/members/{id}/waivers"),
+        ("GET", "/loans/{id}/fine"),
+        ("GET", "/books/duplicates"),
+        ("POST", "/books/merge"),
+        ("GET", "/books/{id}/tags"),
+        ("PUT", "/books/{id}/tags"),
+        ("GET", "/books/{id}/metadata"),
+        ("PUT", "/books/{id}/metadata"),
+        ("GET", "/books/{id}/similar"),
+        ("GET", "/stats"),
+        ("POST", "/books/{id}/reviews"),
+        ("GET", "/books/{id}/reviews"),
+        ("GET", "/books/{id}/reviews/{review_id}"),
+        ("PUT", "/books/{id}/reviews/{review_id}"),
+        ("DELETE", "/books/{id}/reviews/{review_id}"),
+        ("POST", "/books/{id}/reviews/{review_id}/flags"),
+        ("PUT", "/books/{id}/reviews/{review_id}/moderation"),
+        ("PUT", "/members/{id}/email-preferences"),
+        ("GET", "/emails"),
+        ("POST", "/holds"),
+        ("GET", "/holds/{id}"),
+        ("DELETE", "/holds/{id}"),
+        ("PUT", "/holds/{id}/priority"),
+        ("GET", "/books/{id}/holds"),
+        ("GET", "/members/{id}/holds"),
+        ("POST", "/books/{id}/copies"),
+        ("GET", "/books/{id}/copies"),
+        ("GET", "/copies/{id}"),
+        ("PUT", "/copies/{id}"),
+        ("DELETE", "/copies/{id}"),
+        ("POST", "/copies/labels"),
+        ("POST", "/libraries"),
+        ("GET", "/jobs"),
+        ("GET", "/jobs/runs"),
+        ("POST", "/jobs/{job}/run"),
+        ("GET", "/libraries"),
+        ("DELETE", "/libraries/{lib}"),
+        ("POST", "/libraries/{lib}/rotate-key"),
+        ("GET", "/health/live"),
+        ("GET", "/health/ready"),
+    ]
+    .iter()
+    .map(|(method, path)| (method.to_string(), path.to_string()))
+    .collect();
+
+    assert_eq!(operations(&spec), expected);
+    for schema in ["Book", "CreateBook"] {
+        assert!(spec["components"]["schemas"][schema].is_object(), "missing schema {schema}");
+    }
+}
+
+// Every documented operation must be routed and answer with a documented status
+#[tokio::test]
+async fn spec_matches_router() {
+    let spec = fetch_spec().await;
+
+    for (method, path) in operations(&spec) {
+        let uri = path.replace("{id}", "1").replace("{rev}", "1").replace("{review_id}", "1").replace("{lib}", "main");
+        let method = Method::from_bytes(method.as_bytes()).unwrap();
+        let body = (method == Method::POST || method == Method::PUT)
+            .then_some(r#"{"title":"Dune","author":"Frank Herbert"}"#);
+
+        let response = TestApp::new().response(json_request(method.clone(), &uri, body)).await;
+        let status = response.status();
+        let streaming = response.headers().get(header::CONTENT_TYPE)
+            == Some(&HeaderValue::from_static("text/event-stream"));
+        let body = if streaming {
+            "stream".to_string()
+        } else {
+            let bytes = response.into_body().collect().await.unwrap().to_bytes();
+            String::from_utf8_lossy(&bytes).to_string()
+        };
+        let documented = &spec["paths"][&path][method.as_str().to_lowercase()]["responses"];
+
+        // The router's own 404/405 have empty bodies; handler errors never do
+        let unrouted = status == StatusCode::METHOD_NOT_ALLOWED
+            || (status == StatusCode::NOT_FOUND && body.is_empty());
+        assert!(!unrouted, "{method} {uri} is documented but not routed");
+        assert!(
+            documented.get(status.as_str()).is_some(),
+            "{method} {path} returned undocumented status {status}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn docs_page_loads_the_spec() {
+    let app = TestApp::new();
+    assert_eq!(app.get("/docs").await.0, StatusCode::SEE_OTHER);
+    let (status, html) = app.get("/docs/").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("swagger-ui"));
+    // Everything the page needs is served from here, not a CDN
+    assert!(!html.contains("://"), "{html}");
+    for asset in ["swagger-ui.css", "swagger-ui-bundle.js"] {
+        assert_eq!(app.get(&format!("/docs/{asset}")).await.0, StatusCode::OK, "{asset}");
+    }
+    let (_, initializer) = app.get("/docs/swagger-initializer.js").await;
+    assert!(initializer.contains("/openapi.json"), "{initializer}");
+}
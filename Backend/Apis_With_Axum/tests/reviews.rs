@@ -0,0 +1,187 @@
+mod common;
+
+use apis_with_axum::book::Book;
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::Value;
+
+async fn add_member(app: &TestApp, name: &str) -> u64 {
+    let (_, body) = app.send(Method::POST, "/members", Some(&format!(r#"{{"name":"{name}"}}"#))).await;
+    serde_json::from_str::<Value>(&body).unwrap()["id"].as_u64().unwrap()
+}
+
+async fn review(app: &TestApp, book_id: u32, member_id: u64, rating: u32) -> (StatusCode, Value) {
+    let json = format!(r#"{{"member_id":{member_id},"rating":{rating},"text":"  Worth reading  "}}"#);
+    let (status, body) = app.send(Method::POST, &format!("/books/{book_id}/reviews"), Some(&json)).await;
+    (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn members_review_a_book_once() {
+    let app = TestApp::new();
+    let ada = add_member(&app, "Ada").await;
+
+    let (status, created) = review(&app, 2, ada, 4).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!((created["rating"].as_u64(), created["text"].as_str()), (Some(4), Some("Worth reading")));
+    assert_eq!(review(&app, 2, ada, 5).await.0, StatusCode::CONFLICT);
+    // Another book is fine
+    assert_eq!(review(&app, 3, ada, 5).await.0, StatusCode::CREATED);
+
+    assert_eq!(review(&app, 2, ada, 0).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(review(&app, 1, ada, 6).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(review(&app, 99, ada, 3).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(review(&app, 1, 99, 3).await.0, StatusCode::NOT_FOUND);
+    let (status, _) = app.send(Method::POST, "/books/1/reviews", Some(r#"{"rating":3}"#)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn reviews_can_be_read_edited_and_deleted() {
+    let app = TestApp::new();
+    let ada = add_member(&app, "Ada").await;
+    let grace = add_member(&app, "Grace").await;
+    let (_, created) = review(&app, 2, ada, 4).await;
+    let path = format!("/books/2/reviews/{}", created["id"]);
+
+    let (status, fetched) = app.get_json(&path).await;
+    assert_eq!((status, fetched["member_id"].as_u64()), (StatusCode::OK, Some(ada)));
+    // Only through its own book
+    assert_eq!(app.get(&format!("/books/3/reviews/{}", created["id"])).await.0, StatusCode::NOT_FOUND);
+
+    let edit = |member: u64, changes: &str| format!(r#"{{"member_id":{member},{changes}}}"#);
+    let (status, body) = app.send(Method::PUT, &path, Some(&edit(ada, r#""rating":2,"text":"""#))).await;
+    assert_eq!(status, StatusCode::OK);
+    let edited: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((edited["rating"].as_u64(), &edited["text"]), (Some(2), &Value::Null));
+    assert!(edited["updated_at"].is_string());
+    assert_eq!(app.send(Method::PUT, &path, Some(&edit(ada, r#""rating":9"#))).await.0, StatusCode::BAD_REQUEST);
+    // Only naming the reviewer
+    assert_eq!(app.send(Method::PUT, &path, Some(r#"{"rating":1}"#)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.send(Method::PUT, &path, Some(&edit(grace, r#""rating":1"#))).await.0, StatusCode::FORBIDDEN);
+
+    let (_, listed) = app.get_json("/books/2/reviews").await;
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["rating"], 2);
+
+    // Deleting is for moderators; naming the reviewer is not enough
+    assert_eq!(app.send(Method::DELETE, &path, None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.send(Method::DELETE, &format!("{path}?member_id={ada}"), None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.admin(Method::DELETE, &path, None).await.0, StatusCode::OK);
+    assert_eq!(app.admin(Method::DELETE, &path, None).await.0, StatusCode::NOT_FOUND);
+    let (_, listed) = app.get_json("/books/2/reviews").await;
+    assert!(listed.as_array().unwrap().is_empty());
+    // Once deleted, the member may review it again
+    assert_eq!(review(&app, 2, ada, 5).await.0, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn books_carry_their_rating_and_sort_by_it() {
+    let app = TestApp::new();
+    let members = [add_member(&app, "Ada").await, add_member(&app, "Grace").await, add_member(&app, "Alan").await];
+    for (member, rating) in members.iter().zip([5, 4, 4]) {
+        review(&app, 3, *member, rating).await;
+    }
+    review(&app, 1, members[0], 5).await;
+
+    let (_, book) = app.get_json("/books/3").await;
+    assert_eq!((book["average_rating"].as_f64(), book["review_count"].as_u64()), (Some(4.33), Some(3)));
+    assert!(book["availability"].is_object());
+    // Unrated books have no rating fields at all
+    let (_, book) = app.get_json("/books/2").await;
+    assert!(book.get("average_rating").is_none() && book.get("review_count").is_none());
+
+    let (_, books) = app.get_json("/books").await;
+    assert_eq!(books[0]["average_rating"], 5.0);
+    let (_, found) = app.get_json("/books/search?title=rust").await;
+    assert_eq!(found[1]["review_count"], 3);
+
+    let (_, sorted) = app.get_json("/books?sort=rating").await;
+    let ids: Vec<u64> = sorted.as_array().unwrap().iter().map(|book| book["id"].as_u64().unwrap()).collect();
+    assert_eq!(ids, vec![1, 3, 2]);
+    assert_eq!(app.get("/books?sort=popularity").await.0, StatusCode::BAD_REQUEST);
+
+    // CSV keeps the catalog's own columns
+    let mut request = common::json_request(Method::GET, "/books?sort=rating", None);
+    request.headers_mut().insert("accept", "text/csv".parse().unwrap());
+    let (_, csv) = app.request(request).await;
+    assert!(csv.starts_with("id,title,author,deleted_at\n1,"));
+}
+
+#[tokio::test]
+async fn rated_books_still_read_as_plain_books() {
+    let app = TestApp::new();
+    review(&app, 2, add_member(&app, "Ada").await, 4).await;
+    let plain = |book: &Book| (book.id, book.title.clone(), book.author.clone(), book.deleted_at);
+    let catalog: Vec<_> = app.state.books.read().unwrap().iter().map(plain).collect();
+
+    // A client that only knows Book keeps working and sees the same values
+    let (_, books) = app.get_json("/books").await;
+    let books: Vec<Book> = serde_json::from_value(books).unwrap();
+    assert_eq!(books.iter().map(plain).collect::<Vec<_>>(), catalog);
+    let (_, book) = app.get_json("/books/2").await;
+    let book: Book = serde_json::from_value(book).unwrap();
+    assert_eq!(plain(&book), catalog[1]);
+}
+
+#[tokio::test]
+async fn flagged_reviews_are_hidden_until_moderated() {
+    let app = TestApp::new();
+    let ada = add_member(&app, "Ada").await;
+    let (_, other) = review(&app, 3, add_member(&app, "Grace").await, 2).await;
+    let (_, created) = review(&app, 3, ada, 5).await;
+    let path = format!("/books/3/reviews/{}", created["id"]);
+
+    let flag = |member: u64| format!(r#"{{"member_id":{member},"reason":"spoilers"}}"#);
+    let flags = format!("{path}/flags");
+    assert_eq!(app.send(Method::POST, &flags, Some(r#"{"reason":"spoilers"}"#)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.send(Method::POST, &flags, Some(&flag(99))).await.0, StatusCode::NOT_FOUND);
+    for n in 1..=3 {
+        let member = add_member(&app, &format!("Reader {n}")).await;
+        let (status, body) = app.send(Method::POST, &flags, Some(&flag(member))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let flagged: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(flagged["hidden"], n == 3);
+        // Flagging again does not count twice
+        if n == 1 {
+            assert_eq!(app.send(Method::POST, &flags, Some(&flag(member))).await.0, StatusCode::CONFLICT);
+        }
+    }
+
+    // Hidden reviews leave listings and ratings
+    assert_eq!(app.get(&path).await.0, StatusCode::NOT_FOUND);
+    let (_, listed) = app.get_json("/books/3/reviews").await;
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["id"], other["id"]);
+    let (_, book) = app.get_json("/books/3").await;
+    assert_eq!((book["average_rating"].as_f64(), book["review_count"].as_u64()), (Some(2.0), Some(1)));
+    let (_, everything) = app.admin(Method::GET, "/books/3/reviews?include_hidden=true", None).await;
+    let everything: Value = serde_json::from_str(&everything).unwrap();
+    assert_eq!(everything.as_array().unwrap().len(), 2);
+
+    let (status, body) = app.admin(Method::PUT, &format!("{path}/moderation"), Some(r#"{"hidden":false}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    let restored: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((restored["hidden"].as_bool(), restored["flags"].as_array().unwrap().len()), (Some(false), 0));
+    let (_, book) = app.get_json("/books/3").await;
+    assert_eq!(book["average_rating"], 3.5);
+}
+
+#[tokio::test]
+async fn moderation_needs_the_admin_key() {
+    let app = TestApp::new();
+    let ada = add_member(&app, "Ada").await;
+    let (_, created) = review(&app, 1, ada, 1).await;
+    let path = format!("/books/1/reviews/{}/moderation", created["id"]);
+
+    assert_eq!(app.send(Method::PUT, &path, Some(r#"{"hidden":true}"#)).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.get("/books/1/reviews?include_hidden=true").await.0, StatusCode::UNAUTHORIZED);
+
+    assert_eq!(app.admin(Method::PUT, &path, Some(r#"{"hidden":true}"#)).await.0, StatusCode::OK);
+    let (_, listed) = app.get_json("/books/1/reviews").await;
+    assert!(listed.as_array().unwrap().is_empty());
+
+    // Moderators may delete any review
+    let review_path = format!("/books/1/reviews/{}", created["id"]);
+    assert_eq!(app.admin(Method::DELETE, &review_path, None).await.0, StatusCode::OK);
+}
@@ -0,0 +1,149 @@
+use std::{fs, path::Path, time::Duration};
+
+use apis_with_axum::{
+    app,
+    book::load_books_from_csv,
+    config::Config,
+    shutdown::{serve_with_shutdown, ShutdownOutcome},
+    storage::Storage,
+    AppState,
+};
+use axum::routing::get;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::oneshot,
+    task::JoinHandle,
+};
+
+struct Server {
+    addr: std::net::SocketAddr,
+    state: AppState,
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<ShutdownOutcome>,
+}
+
+async fn start_server(data_path: &Path, drain_timeout: Duration) -> Server {
+    fs::write(data_path, "id,title,author\n1,Clean Code,Robert C. Martin\n").unwrap();
+
+    let storage = Storage::Csv(data_path.to_path_buf());
+    let state = AppState::new(storage.load().unwrap(), storage);
+    let router = app(state.clone(), &Config::default()).route(
+        "/slow",
+        get(|| async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            "done"
+        }),
+    );
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let signal = async move {
+        let _ = stopped.await;
+    };
+
+    let handle = tokio::spawn(serve_with_shutdown(
+        listener,
+        router,
+        state.clone(),
+        signal,
+        drain_timeout,
+    ));
+
+    Server { addr, state, stop, handle }
+}
+
+// Minimal HTTP/1.1 client so the tests control exactly when bytes are sent
+async fn send_request(addr: std::net::SocketAddr, method: &str, path: &str, body: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_waits_for_active_add_book_and_flushes_it() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_path = dir.path().join("books.csv");
+    let server = start_server(&data_path, Duration::from_secs(5)).await;
+
+    // Hold the catalog lock on another thread so add_book is stuck mid-request
+    let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let books = server.state.books.clone();
+    let holder = std::thread::spawn(move || {
+        let _guard = books.write().unwrap();
+        locked_tx.send(()).unwrap();
+        release_rx.recv().unwrap();
+    });
+    locked_rx.recv().unwrap();
+
+    let addr = server.addr;
+    let request = tokio::spawn(async move {
+        send_request(addr, "POST", "/books/new", r#"{"title":"Dune","author":"Frank Herbert"}"#).await
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    server.stop.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!server.handle.is_finished(), "server must wait for the active request");
+    release_tx.send(()).unwrap();
+    holder.join().unwrap();
+
+    let response = request.await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 201"), "unexpected response: {response}");
+    assert_eq!(server.handle.await.unwrap(), ShutdownOutcome::Clean);
+
+    let books = load_books_from_csv(&data_path).unwrap();
+    assert!(books.iter().any(|book| book.title == "Dune"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_stops_accepting_new_connections() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = start_server(&dir.path().join("books.csv"), Duration::from_secs(5)).await;
+
+    server.stop.send(()).unwrap();
+    assert_eq!(server.handle.await.unwrap(), ShutdownOutcome::Clean);
+    assert!(TcpStream::connect(server.addr).await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_closes_open_event_streams() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = start_server(&dir.path().join("books.csv"), Duration::from_secs(5)).await;
+
+    let addr = server.addr;
+    let stream = tokio::spawn(async move { send_request(addr, "GET", "/books/events", "").await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    server.stop.send(()).unwrap();
+    assert_eq!(server.handle.await.unwrap(), ShutdownOutcome::Clean);
+    assert!(stream.await.unwrap().contains("text/event-stream"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_gives_up_after_drain_timeout() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_path = dir.path().join("books.csv");
+    let server = start_server(&data_path, Duration::from_millis(300)).await;
+
+    let addr = server.addr;
+    tokio::spawn(async move { send_request(addr, "GET", "/slow", "").await });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    server.stop.send(()).unwrap();
+    let outcome = server.handle.await.unwrap();
+    assert_eq!(outcome, ShutdownOutcome::DrainTimedOut);
+    assert_eq!(outcome.exit_code(), 3);
+
+    // The catalog is still flushed on a forced shutdown
+    assert_eq!(load_books_from_csv(&data_path).unwrap().len(), 1);
+}
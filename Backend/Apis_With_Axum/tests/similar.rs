@@ -0,0 +1,128 @@
+mod common;
+
+use apis_with_axum::similar::split_authors;
+use axum::http::{Method, StatusCode};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::{TestApp, ONE_COPY};
+use serde_json::Value;
+
+fn day(n: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap() + Duration::days(n)
+}
+
+fn ids(suggestions: &Value) -> Vec<u64> {
+    suggestions.as_array().unwrap().iter().map(|book| book["id"].as_u64().unwrap()).collect()
+}
+
+async fn add_book(app: &TestApp, title: &str, author: &str) {
+    let json = format!(r#"{{"title":"{title}","author":"{author}"}}"#);
+    assert_eq!(app.send(Method::POST, "/books/new", Some(&json)).await.0, StatusCode::CREATED);
+}
+
+#[test]
+fn co_authors_are_split_apart() {
+    assert_eq!(split_authors("Steve Klabnik and Carol Nichols"), vec!["Steve Klabnik", "Carol Nichols"]);
+    assert_eq!(split_authors("Ada  Lovelace & Grace Hopper; Alan Turing"), vec!["Ada Lovelace", "Grace Hopper", "Alan Turing"]);
+    assert_eq!(split_authors("Robert C. Martin"), vec!["Robert C. Martin"]);
+}
+
+#[tokio::test]
+async fn books_by_the_same_author_are_suggested() {
+    let app = TestApp::new();
+    add_book(&app, "Rust for Rustaceans", "carol nichols").await;
+    add_book(&app, "Clean Architecture", "Robert C. Martin").await;
+
+    let (status, similar) = app.get_json("/books/1/similar").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(ids(&similar), vec![4]);
+    assert_eq!(similar[0]["score"], 3);
+    assert_eq!(similar[0]["shared_authors"][0], "Carol Nichols");
+    assert_eq!(similar[0]["reasons"][0], "Also by Carol Nichols");
+
+    let (_, similar) = app.get_json("/books/2/similar").await;
+    assert_eq!((ids(&similar), similar[0]["title"].as_str()), (vec![5], Some("Clean Architecture")));
+    // Nothing in common
+    let (_, similar) = app.get_json("/books/3/similar").await;
+    assert!(similar.as_array().unwrap().is_empty());
+
+    assert_eq!(app.get("/books/99/similar").await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn tags_are_normalized_and_shared() {
+    let app = TestApp::new();
+    let (status, body) = app.send(Method::PUT, "/books/1/tags", Some(r#"{"tags":["Rust "," Systems  Programming","rust",""]}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, r#"["rust","systems programming"]"#);
+    app.send(Method::PUT, "/books/3/tags", Some(r#"{"tags":["rust","systems programming"]}"#)).await;
+    app.send(Method::PUT, "/books/2/tags", Some(r#"{"tags":["RUST"]}"#)).await;
+
+    let (_, tags) = app.get_json("/books/3/tags").await;
+    assert_eq!(tags, serde_json::json!(["rust", "systems programming"]));
+
+    let (_, similar) = app.get_json("/books/1/similar").await;
+    assert_eq!(ids(&similar), vec![3, 2]);
+    assert_eq!(similar[0]["reasons"][0], "Also tagged rust, systems programming");
+    assert_eq!(similar[1]["score"], 1);
+
+    // Clearing tags takes a book out of the running
+    app.send(Method::PUT, "/books/2/tags", Some(r#"{"tags":[]}"#)).await;
+    let (_, similar) = app.get_json("/books/1/similar").await;
+    assert_eq!(ids(&similar), vec![3]);
+
+    assert_eq!(app.send(Method::PUT, "/books/1/tags", Some("{}")).await.0, StatusCode::BAD_REQUEST);
+    let long = format!(r#"{{"tags":["{}"]}}"#, "x".repeat(41));
+    assert_eq!(app.send(Method::PUT, "/books/1/tags", Some(&long)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.send(Method::PUT, "/books/99/tags", Some(r#"{"tags":["rust"]}"#)).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/books/99/tags").await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn co_borrowing_ranks_and_explains_suggestions() {
+    let app = TestApp::new();
+    let lending = &app.state.lending;
+    for name in ["Ada", "Grace"] {
+        let member = lending.add_member(name.to_string(), None, day(0)).unwrap().id;
+        for book in [1, 3] {
+            let loan = lending.checkout(book, member, ONE_COPY, None, day(0)).unwrap().id;
+            lending.return_loan(loan, day(1)).unwrap();
+        }
+    }
+    let loan = lending.checkout(2, 1, ONE_COPY, None, day(2)).unwrap().id;
+    lending.return_loan(loan, day(3)).unwrap();
+
+    let (_, similar) = app.get_json("/books/1/similar").await;
+    assert_eq!(ids(&similar), vec![3, 2]);
+    assert_eq!((similar[0]["co_borrowers"].as_u64(), similar[1]["co_borrowers"].as_u64()), (Some(2), Some(1)));
+    assert_eq!(similar[0]["reasons"][0], "Borrowed by 2 member(s) who also borrowed this book");
+
+    // Loans made after the first query are picked up
+    let grace = 2;
+    let loan = lending.checkout(2, grace, ONE_COPY, None, day(4)).unwrap().id;
+    lending.return_loan(loan, day(5)).unwrap();
+    let (_, similar) = app.get_json("/books/1/similar?limit=1").await;
+    assert_eq!(ids(&similar), vec![2]);
+    assert_eq!(similar[0]["score"], 2);
+}
+
+#[tokio::test]
+async fn the_index_follows_edits_deletes_and_restores() {
+    let app = TestApp::new();
+    let (_, similar) = app.get_json("/books/2/similar").await;
+    assert!(similar.as_array().unwrap().is_empty());
+
+    let json = r#"{"title":"The Clean Coder","author":"Robert C. Martin"}"#;
+    app.send(Method::PUT, "/books/3", Some(json)).await;
+    let (_, similar) = app.get_json("/books/2/similar").await;
+    assert_eq!(ids(&similar), vec![3]);
+    assert_eq!(similar[0]["title"], "The Clean Coder");
+
+    app.send(Method::DELETE, "/books/3", None).await;
+    let (_, similar) = app.get_json("/books/2/similar").await;
+    assert!(similar.as_array().unwrap().is_empty());
+    assert_eq!(app.get("/books/3/similar").await.0, StatusCode::NOT_FOUND);
+
+    app.send(Method::POST, "/books/3/restore", None).await;
+    let (_, similar) = app.get_json("/books/2/similar").await;
+    assert_eq!(ids(&similar), vec![3]);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use apis_with_axum::{metadata::normalize_isbn, stats::normalize_title};
+use axum::http::{header, Method, StatusCode};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use common::{TestApp, ONE_COPY};
+use serde_json::{json, Value};
+
+fn day(n: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap() + Duration::days(n)
+}
+
+#[test]
+fn isbns_and_titles_are_normalized() {
+    assert_eq!(normalize_isbn("978-1-59327-828-1").as_deref(), Some("9781593278281"));
+    assert_eq!(normalize_isbn("0 8044 2957 x").as_deref(), Some("080442957X"));
+    assert_eq!(normalize_isbn("978-1-59327-828-2"), None);
+    assert_eq!(normalize_isbn("X804429570"), None);
+    assert_eq!(normalize_isbn("12345"), None);
+    assert_eq!(normalize_title("  The Rust  Programming Language!"), "the rust programming language");
+}
+
+#[tokio::test]
+async fn metadata_is_validated_and_stored() {
+    let app = TestApp::new();
+    let (status, body) = app.send(Method::PUT, "/books/1/metadata", Some(r#"{"isbn":"978-1-59327-828-1","year":2018}"#)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), json!({"isbn": "9781593278281", "year": 2018}));
+    let (_, metadata) = app.get_json("/books/1/metadata").await;
+    assert_eq!(metadata["year"], 2018);
+
+    assert_eq!(app.send(Method::PUT, "/books/1/metadata", Some(r#"{"isbn":"978-1-59327-828-2"}"#)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.send(Method::PUT, "/books/1/metadata", Some(r#"{"year":3000}"#)).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.send(Method::PUT, "/books/99/metadata", Some(r#"{"year":2000}"#)).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(app.get("/books/99/metadata").await.0, StatusCode::NOT_FOUND);
+
+    // Missing fields are cleared
+    app.send(Method::PUT, "/books/1/metadata", Some(r#"{"year":2019}"#)).await;
+    let (_, metadata) = app.get_json("/books/1/metadata").await;
+    assert_eq!(metadata, json!({"isbn": null, "year": 2019}));
+}
+
+#[tokio::test]
+async fn stats_count_authors_tags_and_decades() {
+    let app = TestApp::new();
+    app.send(Method::POST, "/books/new", Some(r#"{"title":"Rust for Rustaceans","author":"Carol Nichols"}"#)).await;
+    app.send(Method::PUT, "/books/1/tags", Some(r#"{"tags":["rust","systems"]}"#)).await;
+    app.send(Method::PUT, "/books/3/tags", Some(r#"{"tags":["rust"]}"#)).await;
+    app.send(Method::PUT, "/books/1/metadata", Some(r#"{"isbn":"9781593278281","year":2018}"#)).await;
+    app.send(Method::PUT, "/books/2/metadata", Some(r#"{"isbn":"0132350882","year":2008}"#)).await;
+    app.send(Method::PUT, "/books/3/metadata", Some(r#"{"year":2017}"#)).await;
+
+    let (status, stats) = app.get_json("/stats").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(stats["total_books"], 4);
+    assert_eq!(stats["by_author"][0], json!({"name": "Carol Nichols", "books": 2}));
+    assert_eq!(stats["by_author"].as_array().unwrap().len(), 4);
+    assert_eq!(stats["by_tag"], json!([{"name": "rust", "books": 2}, {"name": "systems", "books": 1}]));
+    assert_eq!(stats["by_decade"], json!([{"name": "2000s", "books": 1}, {"name": "2010s", "books": 2}]));
+    assert_eq!(stats["quality"]["missing_isbn"], json!([3, 4]));
+    assert_eq!(stats["quality"]["missing_year"], json!([4]));
+}
+
+#[tokio::test]
+async fn stats_rank_loans_and_list_newest_and_duplicates() {
+    let app = TestApp::new();
+    app.send(Method::POST, "/books/new", Some(r#"{"title":"clean code.","author":"Someone Else"}"#)).await;
+    let lending = &app.state.lending;
+    let member = lending.add_member("Ada".to_string(), None, day(0)).unwrap().id;
+    for (n, book) in [3, 2, 3].into_iter().enumerate() {
+        let loan = lending.checkout(book, member, ONE_COPY, None, day(n as i64)).unwrap().id;
+        lending.return_loan(loan, day(n as i64)).unwrap();
+    }
+
+    let (_, stats) = app.get_json("/stats?top=2").await;
+    let most_borrowed: Vec<(u64, u64)> = stats["most_borrowed"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|book| (book["id"].as_u64().unwrap(), book["loans"].as_u64().unwrap()))
+        .collect();
+    assert_eq!(most_borrowed, vec![(3, 2), (2, 1)]);
+    assert_eq!(stats["newest"].as_array().unwrap().len(), 2);
+    assert_eq!(stats["newest"][0]["id"], 4);
+    assert!(stats["newest"][0]["added_at"].is_string());
+    // Loaded from the data file, so when it was added is unknown
+    assert!(stats["newest"][1]["added_at"].is_null());
+    assert_eq!(stats["quality"]["duplicate_titles"], json!([{"title": "Clean Code", "ids": [2, 4]}]));
+
+    // Deleted books drop out
+    app.send(Method::DELETE, "/books/4", None).await;
+    let (_, stats) = app.get_json("/stats").await;
+    assert_eq!(stats["total_books"], 3);
+    assert!(stats["quality"]["duplicate_titles"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn stats_export_as_csv() {
+    let app = TestApp::new();
+    app.send(Method::PUT, "/books/2/tags", Some(r#"{"tags":["craft"]}"#)).await;
+    app.send(Method::PUT, "/books/2/metadata", Some(r#"{"isbn":"0132350882","year":2008}"#)).await;
+
+    let mut request = common::json_request(Method::GET, "/stats", None);
+    request.headers_mut().insert("accept", "text/csv".parse().unwrap());
+    let response = app.response(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[header::CONTENT_DISPOSITION].to_str().unwrap().contains("catalog-stats.csv"));
+
+    let mut request = common::json_request(Method::GET, "/stats", None);
+    request.headers_mut().insert("accept", "text/csv".parse().unwrap());
+    let (_, csv) = app.request(request).await;
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "section,id,name,value");
+    assert_eq!(lines[1], "total,,books,3");
+    assert!(lines.contains(&"author,,Robert C. Martin,1"));
+    assert!(lines.contains(&"tag,,craft,1"));
+    assert!(lines.contains(&"decade,,2000s,1"));
+    assert!(lines.contains(&"newest,3,Programming Rust,"));
+    assert!(lines.contains(&"missing_isbn,1,,"));
+
+    let mut request = common::json_request(Method::GET, "/stats", None);
+    request.headers_mut().insert("accept", "application/xml".parse().unwrap());
+    assert_eq!(app.request(request).await.0, StatusCode::NOT_ACCEPTABLE);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use apis_with_axum::{book::load_books_from_csv, trash::purge_expired};
+use axum::http::{Method, StatusCode};
+use chrono::{Duration, Utc};
+use common::TestApp;
+
+#[tokio::test]
+async fn deleted_books_are_hidden_from_list_and_search() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/3", None).await;
+
+    let (_, books) = app.get_json("/books").await;
+    assert_eq!(books.as_array().unwrap().len(), 2);
+
+    let (_, found) = app.get_json("/books/search?title=rust").await;
+    assert_eq!(found.as_array().unwrap().len(), 1);
+
+    // Deleting or updating it again is a 404
+    let (status, _) = app.send(Method::DELETE, "/books/3", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.send(Method::PUT, "/books/3", Some(r#"{"title":"x","author":"y"}"#)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn trash_lists_deleted_books() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::DELETE, "/books/3", None).await;
+
+    let (status, trash) = app.get_json("/books/trash").await;
+    assert_eq!(status, StatusCode::OK);
+    let trash = trash.as_array().unwrap();
+    assert_eq!(trash.len(), 2);
+    assert_eq!(trash[0]["id"], 3, "most recently deleted first");
+    assert!(trash[0]["deleted_at"].is_string());
+}
+
+#[tokio::test]
+async fn restore_brings_a_book_back() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/2", None).await;
+
+    let (status, _) = app.send(Method::POST, "/books/2/restore", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, book) = app.get_json("/books/2").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(book["deleted_at"].is_null());
+
+    let (_, history) = app.get_json("/books/2/history").await;
+    assert_eq!(history[1]["action"], "restored");
+
+    // Only trashed books can be restored
+    let (status, _) = app.send(Method::POST, "/books/2/restore", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn purge_removes_a_trashed_book_for_good() {
+    let app = TestApp::new();
+
+    let (status, _) = app.send(Method::DELETE, "/books/2/purge", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "live books cannot be purged");
+
+    app.send(Method::DELETE, "/books/2", None).await;
+    let (status, _) = app.send(Method::DELETE, "/books/2/purge", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let saved = load_books_from_csv(&app.data_path).unwrap();
+    assert!(saved.iter().all(|book| book.id != 2));
+    let (_, trash) = app.get_json("/books/trash").await;
+    assert_eq!(trash.as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn purged_ids_are_not_reused() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/3", None).await;
+    app.send(Method::DELETE, "/books/3/purge", None).await;
+
+    let (_, body) = app.send(Method::POST, "/books/new", Some(r#"{"title":"Dune","author":"Frank Herbert"}"#)).await;
+    assert!(body.contains(r#""id":4"#), "{body}");
+}
+
+#[tokio::test]
+async fn scheduled_purge_only_removes_expired_books() {
+    let app = TestApp::new();
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::DELETE, "/books/2", None).await;
+
+    // Pretend book 1 was deleted 40 days ago
+    {
+        let mut books = app.state.books.write().unwrap();
+        books[0].deleted_at = Some(Utc::now() - Duration::days(40));
+    }
+
+    let purged = purge_expired(&app.state, Duration::days(30), Utc::now());
+    assert_eq!(purged.iter().map(|book| book.id).collect::<Vec<_>>(), vec![1]);
+
+    let saved = load_books_from_csv(&app.data_path).unwrap();
+    assert_eq!(saved.iter().map(|book| book.id).collect::<Vec<_>>(), vec![2, 3]);
+
+    let (_, history) = app.get_json("/books/1/history").await;
+    assert_eq!(history[1]["action"], "purged");
+    assert_eq!(history[1]["actor"], "trash-purger");
+}
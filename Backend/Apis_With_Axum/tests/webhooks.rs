@@ -0,0 +1,362 @@
+mod common;
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use apis_with_axum::{
+    config::WebhookConfig,
+    events::ChangeEvent,
+    storage::Storage,
+    webhooks::sign,
+    AppState,
+};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode},
+    routing::post,
+    Router,
+};
+use common::TestApp;
+use serde_json::Value;
+use tokio::{net::TcpListener, sync::mpsc};
+
+const DUNE: &str = r#"{"title":"Dune","author":"Frank Herbert"}"#;
+
+struct Received {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+// Stand-in receiver: answers 500 to the first `failures` requests
+struct Receiver {
+    addr: SocketAddr,
+    failures: Arc<AtomicUsize>,
+    requests: mpsc::UnboundedReceiver<Received>,
+}
+
+#[derive(Clone)]
+struct ReceiverState {
+    failures: Arc<AtomicUsize>,
+    sender: mpsc::UnboundedSender<Received>,
+}
+
+async fn receive(State(state): State<ReceiverState>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let _ = state.sender.send(Received { headers, body });
+    let failing = state
+        .failures
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1))
+        .is_ok();
+    if failing {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
+
+impl Receiver {
+    async fn start(failures: usize) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let failures = Arc::new(AtomicUsize::new(failures));
+        let (sender, requests) = mpsc::unbounded_channel();
+        let router = Router::new()
+            .route("/hook", post(receive))
+            .with_state(ReceiverState { failures: failures.clone(), sender });
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        Receiver { addr, failures, requests }
+    }
+
+    fn url(&self) -> String {
+        format!("http://{}/hook", self.addr)
+    }
+
+    async fn next(&mut self) -> Received {
+        tokio::time::timeout(Duration::from_secs(5), self.requests.recv())
+            .await
+            .expect("timed out waiting for a webhook")
+            .unwrap()
+    }
+}
+
+fn fast_retries(max_attempts: u32) -> WebhookConfig {
+    // The stand-in receiver listens on loopback
+    WebhookConfig { max_attempts, initial_backoff_ms: 10, timeout_secs: 2, allow_private_targets: true }
+}
+
+fn app_with_dispatcher(settings: WebhookConfig) -> TestApp {
+    let app = TestApp::customized(|state| state.with_webhook_settings(settings));
+    app.state.spawn_webhook_dispatcher();
+    app
+}
+
+async fn subscribe(app: &TestApp, body: Value) -> Value {
+    let (status, created) = app.admin(Method::POST, "/webhooks", Some(&body.to_string())).await;
+    assert_eq!(status, StatusCode::CREATED);
+    serde_json::from_str(&created).unwrap()
+}
+
+// Polls until the condition holds for the JSON at `uri`
+async fn wait_for_json(app: &TestApp, uri: &str, done: impl Fn(&Value) -> bool) -> Value {
+    for _ in 0..100 {
+        let (_, value) = app.get_json(uri).await;
+        if done(&value) {
+            return value;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("condition on {uri} never held");
+}
+
+#[tokio::test]
+async fn delivers_signed_change_events() {
+    let mut receiver = Receiver::start(0).await;
+    let app = app_with_dispatcher(fast_retries(3));
+    subscribe(&app, serde_json::json!({ "url": receiver.url(), "secret": "s3cret" })).await;
+
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+
+    let received = receiver.next().await;
+    let expected = format!("sha256={}", sign("s3cret", &received.body));
+    assert_eq!(received.headers["x-signature-256"], expected.as_str());
+    assert_eq!(received.headers["x-webhook-event"], "created");
+    assert_eq!(received.headers["content-type"], "application/json");
+    let event: ChangeEvent = serde_json::from_slice(&received.body).unwrap();
+    assert_eq!(event.book.title, "Dune");
+
+    let log = wait_for_json(&app, "/webhooks/deliveries", |log| log.as_array().unwrap().len() == 1).await;
+    assert_eq!(log[0]["succeeded"], true);
+    assert_eq!(log[0]["status"], 204);
+}
+
+#[tokio::test]
+async fn only_subscribed_kinds_are_sent() {
+    let mut receiver = Receiver::start(0).await;
+    let app = app_with_dispatcher(fast_retries(3));
+    subscribe(&app, serde_json::json!({ "url": receiver.url(), "events": ["deleted"] })).await;
+
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+    app.send(Method::DELETE, "/books/2", None).await;
+
+    let received = receiver.next().await;
+    assert_eq!(received.headers["x-webhook-event"], "deleted");
+    let event: ChangeEvent = serde_json::from_slice(&received.body).unwrap();
+    assert_eq!(event.book.id, 2);
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried() {
+    let mut receiver = Receiver::start(2).await;
+    let app = app_with_dispatcher(fast_retries(5));
+    subscribe(&app, serde_json::json!({ "url": receiver.url() })).await;
+
+    app.send(Method::DELETE, "/books/1", None).await;
+
+    let attempts: Vec<String> = [
+        receiver.next().await,
+        receiver.next().await,
+        receiver.next().await,
+    ]
+    .iter()
+    .map(|received| received.headers["x-webhook-attempt"].to_str().unwrap().to_string())
+    .collect();
+    assert_eq!(attempts, vec!["1", "2", "3"]);
+
+    let log = wait_for_json(&app, "/webhooks/deliveries", |log| log.as_array().unwrap().len() == 3).await;
+    let outcomes: Vec<bool> = log.as_array().unwrap().iter().map(|record| record["succeeded"] == true).collect();
+    assert_eq!(outcomes, vec![false, false, true]);
+    // The same delivery ID is reused across attempts
+    assert_eq!(log[0]["delivery_id"], log[2]["delivery_id"]);
+    let (_, dead) = app.get_json("/webhooks/dead-letters").await;
+    assert_eq!(dead, serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn exhausted_deliveries_become_dead_letters_and_can_be_retried() {
+    let mut receiver = Receiver::start(usize::MAX).await;
+    let app = app_with_dispatcher(fast_retries(2));
+    subscribe(&app, serde_json::json!({ "url": receiver.url() })).await;
+
+    app.send(Method::POST, "/books/new", Some(DUNE)).await;
+    receiver.next().await;
+    receiver.next().await;
+
+    let dead = wait_for_json(&app, "/webhooks/dead-letters", |dead| dead.as_array().unwrap().len() == 1).await;
+    assert_eq!(dead[0]["attempts"], 2);
+    assert_eq!(dead[0]["event"]["book"]["title"], "Dune");
+    assert!(dead[0]["last_error"].as_str().unwrap().contains("500"));
+
+    // The receiver recovers and the dead letter is replayed by hand
+    receiver.failures.store(0, Ordering::SeqCst);
+    let (status, _) = app.admin(Method::POST, "/webhooks/dead-letters/1/retry", None).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let received = receiver.next().await;
+    assert_eq!(received.headers["x-webhook-event"], "created");
+
+    let (_, dead) = app.get_json("/webhooks/dead-letters").await;
+    assert_eq!(dead, serde_json::json!([]));
+    let (status, _) = app.admin(Method::POST, "/webhooks/dead-letters/1/retry", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn subscriptions_are_managed_and_persisted() {
+    let app = TestApp::new();
+
+    let (status, _) = app.admin(Method::POST, "/webhooks", Some(r#"{"url":"ftp://example.com"}"#)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let created = subscribe(&app, serde_json::json!({ "url": "https://hooks.example.com/books" })).await;
+    assert_eq!(created["id"], 1);
+    assert!(!created["secret"].as_str().unwrap().is_empty(), "a secret is generated");
+
+    let (_, listed) = app.get_json("/webhooks").await;
+    assert_eq!(listed[0]["url"], "https://hooks.example.com/books");
+    assert!(listed[0].get("secret").is_none(), "secrets are not listed");
+
+    // A restarted server sees the same subscription
+    let reopened = AppState::load(Storage::Csv(app.data_path.clone()));
+    assert_eq!(reopened.webhooks.subscriptions().len(), 1);
+
+    let (status, _) = app.admin(Method::DELETE, "/webhooks/1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.admin(Method::DELETE, "/webhooks/1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn managing_webhooks_needs_the_admin_key() {
+    let app = TestApp::new();
+    let body = r#"{"url":"https://hooks.example.com/books"}"#;
+    assert_eq!(app.send(Method::POST, "/webhooks", Some(body)).await.0, StatusCode::UNAUTHORIZED);
+    assert!(app.state.webhooks.subscriptions().is_empty());
+    subscribe(&app, serde_json::json!({ "url": "https://hooks.example.com/books" })).await;
+    assert_eq!(app.send(Method::DELETE, "/webhooks/1", None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.send(Method::POST, "/webhooks/dead-letters/1/retry", None).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.state.webhooks.subscriptions().len(), 1);
+
+    let app = TestApp::customized(|state| state.with_library_admin_key(None));
+    assert_eq!(app.admin(Method::POST, "/webhooks", Some(body)).await.0, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn changes_made_while_the_dispatcher_was_down_are_delivered() {
+    let mut receiver = Receiver::start(0).await;
+    let app = TestApp::customized(|state| state.with_webhook_settings(fast_retries(3)));
+    subscribe(&app, serde_json::json!({ "url": receiver.url() })).await;
+    app.send(Method::DELETE, "/books/3", None).await;
+
+    app.state.spawn_webhook_dispatcher();
+
+    let event: ChangeEvent = serde_json::from_slice(&receiver.next().await.body).unwrap();
+    assert_eq!(event.book.id, 3);
+}
+
+#[tokio::test]
+async fn private_addresses_are_refused_unless_allowed() {
+    let app = TestApp::new();
+    for url in [
+        "http://127.0.0.1:8080/hook",
+        "http://localhost/hook",
+        "http://[::1]/hook",
+        "http://10.1.2.3/hook",
+        "http://169.254.169.254/latest/meta-data",
+        "http://[::ffff:192.168.0.1]/hook",
+    ] {
+        let body = serde_json::json!({ "url": url }).to_string();
+        let (status, reason) = app.admin(Method::POST, "/webhooks", Some(&body)).await;
+        assert_eq!((status, reason.as_str()), (StatusCode::BAD_REQUEST, "🚫 Webhook URL Points At A Private Address"), "{url}");
+    }
+
+    let app = TestApp::customized(|state| state.with_webhook_settings(fast_retries(1)));
+    subscribe(&app, serde_json::json!({ "url": "http://127.0.0.1:8080/hook" })).await;
+}
+
+#[tokio::test]
+async fn stored_private_targets_are_never_called() {
+    let mut receiver = Receiver::start(0).await;
+    let app = app_with_dispatcher(fast_retries(3));
+    subscribe(&app, serde_json::json!({ "url": receiver.url() })).await;
+    // The subscription predates private targets being refused
+    app.state.webhooks.configure(WebhookConfig { allow_private_targets: false, ..fast_retries(3) });
+
+    app.send(Method::DELETE, "/books/1", None).await;
+    let dead = wait_for_json(&app, "/webhooks/dead-letters", |dead| dead.as_array().unwrap().len() == 1).await;
+    assert_eq!(dead[0]["attempts"], 0);
+    assert!(receiver.requests.try_recv().is_err());
+}
+
+// Last change the subscription is done with, as saved
+fn saved_cursor(app: &TestApp, id: u32) -> u64 {
+    let store: Value = serde_json::from_str(&std::fs::read_to_string(app.dir.path().join("books.webhooks.json")).unwrap()).unwrap();
+    store["cursors"][id.to_string()].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn a_failing_receiver_does_not_hold_up_the_others() {
+    let mut failing = Receiver::start(usize::MAX).await;
+    let mut healthy = Receiver::start(0).await;
+    let app = app_with_dispatcher(fast_retries(1000));
+    subscribe(&app, serde_json::json!({ "url": failing.url() })).await;
+    subscribe(&app, serde_json::json!({ "url": healthy.url() })).await;
+
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::DELETE, "/books/2", None).await;
+    failing.next().await;
+    for book in [1, 2] {
+        let event: ChangeEvent = serde_json::from_slice(&healthy.next().await.body).unwrap();
+        assert_eq!(event.book.id, book);
+    }
+    for _ in 0..100 {
+        if saved_cursor(&app, 2) == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!((saved_cursor(&app, 1), saved_cursor(&app, 2)), (0, 2));
+
+    // Removing the failing subscription stops its retries
+    app.admin(Method::DELETE, "/webhooks/1", None).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    while failing.requests.try_recv().is_ok() {}
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(failing.requests.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn each_subscription_gets_changes_in_order_and_the_cursor_waits() {
+    let mut receiver = Receiver::start(usize::MAX).await;
+    let app = app_with_dispatcher(fast_retries(1000));
+    subscribe(&app, serde_json::json!({ "url": receiver.url() })).await;
+    let cursor = || saved_cursor(&app, 1);
+
+    app.send(Method::DELETE, "/books/1", None).await;
+    app.send(Method::DELETE, "/books/2", None).await;
+    receiver.next().await;
+    receiver.next().await;
+    // Still retrying the first change: the second waits and nothing is marked done
+    assert_eq!(cursor(), 0);
+
+    receiver.failures.store(0, Ordering::SeqCst);
+    let mut books = Vec::new();
+    while books.len() < 2 {
+        let event: ChangeEvent = serde_json::from_slice(&receiver.next().await.body).unwrap();
+        if books.last() != Some(&event.book.id) {
+            books.push(event.book.id);
+        }
+    }
+    assert_eq!(books, vec![1, 2]);
+    for _ in 0..100 {
+        if cursor() == 2 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the cursor never reached the second change");
+}